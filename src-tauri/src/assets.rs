@@ -0,0 +1,129 @@
+//! Question audio, prompt images and the bundled instruction sounds are
+//! addressed by asset ID: the bare name of a file inside one of the asset
+//! folders. IDs never carry a directory, so the frontend cannot name a file
+//! outside those folders, and every lookup is checked again after symlinks
+//! are resolved.
+//!
+//! Files saved or imported since the content-addressed store are named after
+//! the SHA-256 of their bytes, see `content_id`. Older names stay valid IDs.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Longest file name the common file systems accept.
+const MAX_ASSET_ID_BYTES: usize = 255;
+
+/// Checks that `id` is a plain file name: no separators, drive letters or
+/// `..`, and nothing Windows would read as a device or quietly rewrite.
+pub fn validate_asset_id(id: &str) -> Result<(), String> {
+    if id.is_empty() {
+        return Err("The asset name is empty".to_string());
+    }
+    if id.len() > MAX_ASSET_ID_BYTES {
+        return Err(format!(
+            "The asset name is longer than {} bytes",
+            MAX_ASSET_ID_BYTES
+        ));
+    }
+
+    let invalid = || Err(format!("{:?} is not a valid asset name", id));
+    if id.chars().any(|c| {
+        c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+    }) {
+        return invalid();
+    }
+    if id.starts_with(char::is_whitespace) || id.ends_with(['.', ' ']) {
+        return invalid();
+    }
+    if is_device_name(id) {
+        return invalid();
+    }
+
+    let mut components = Path::new(id).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(name)), None) if name == id => Ok(()),
+        _ => invalid(),
+    }
+}
+
+/// Whether Windows reserves `name` for a device, e.g. `CON` or `com1.txt`.
+pub fn is_device_name(name: &str) -> bool {
+    let stem = name
+        .split('.')
+        .next()
+        .unwrap_or_default()
+        .trim_end()
+        .to_ascii_uppercase();
+    matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || ((stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && stem.as_bytes()[3].is_ascii_digit())
+}
+
+/// The asset ID for content with hash `sha256`: the hash, plus the
+/// lowercased extension of `filename` when it has a short alphanumeric one.
+/// Equal content always gets the same ID, so it is stored once.
+pub fn content_id(sha256: &str, filename: &str) -> String {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.len() <= 10 && ext.chars().all(|c| c.is_ascii_alphanumeric()));
+    match extension {
+        Some(extension) => format!("{}.{}", sha256, extension.to_ascii_lowercase()),
+        None => sha256.to_string(),
+    }
+}
+
+/// The hash a content-addressed asset ID starts with, or `None` for names
+/// from before the store.
+pub fn content_hash(id: &str) -> Option<&str> {
+    let hash = id.split('.').next()?;
+    (hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')))
+        .then_some(hash)
+}
+
+/// Finds the file `id` names in the first of `roots` that has it. Roots that
+/// do not exist are skipped. A file that resolves outside its root, through a
+/// symlink for example, is refused rather than passed over.
+pub fn resolve(roots: &[PathBuf], id: &str) -> Result<PathBuf, String> {
+    validate_asset_id(id)?;
+
+    for root in roots {
+        let Ok(root) = fs::canonicalize(root) else {
+            continue;
+        };
+        let Ok(path) = fs::canonicalize(root.join(id)) else {
+            continue;
+        };
+        if !path.starts_with(&root) {
+            return Err(format!("Asset {} points outside its folder", id));
+        }
+        if path.is_file() {
+            return Ok(path);
+        }
+    }
+
+    Err(format!("Asset {} was not found", id))
+}
+
+/// Reads the file `id` names, see `resolve`.
+pub fn read(roots: &[PathBuf], id: &str) -> Result<Vec<u8>, String> {
+    let path = resolve(roots, id)?;
+    fs::read(&path).map_err(|e| format!("Failed to read asset {}: {}", id, e))
+}
+
+/// Returns `path` with symlinks resolved, provided it is a file under `root`.
+/// For files the database records by full path, such as recordings.
+pub fn confine(root: &Path, path: &Path) -> Result<PathBuf, String> {
+    let root =
+        fs::canonicalize(root).map_err(|e| format!("Failed to open folder {:?}: {}", root, e))?;
+    let resolved =
+        fs::canonicalize(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    if !resolved.starts_with(&root) || !resolved.is_file() {
+        return Err(format!("{:?} is not a file under {:?}", path, root));
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests;
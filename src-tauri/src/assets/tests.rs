@@ -0,0 +1,173 @@
+//! Tries to read files outside the asset folders through every kind of name
+//! the frontend could send, and checks that ordinary names still resolve.
+
+use super::*;
+
+struct Fixture {
+    dir: tempfile::TempDir,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let dir = tempfile::tempdir().expect("create temp dir");
+        for folder in ["audio", "sounds", "outside"] {
+            fs::create_dir_all(dir.path().join(folder)).unwrap();
+        }
+        fs::write(dir.path().join("audio").join("q1.mp3"), b"question").unwrap();
+        fs::write(dir.path().join("sounds").join("bell.mp3"), b"bell").unwrap();
+        fs::write(dir.path().join("outside").join("secret.txt"), b"secret").unwrap();
+        fs::write(dir.path().join("exam.db"), b"database").unwrap();
+        Fixture { dir }
+    }
+
+    fn path(&self, relative: &str) -> PathBuf {
+        self.dir.path().join(relative)
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        vec![self.path("audio"), self.path("sounds")]
+    }
+}
+
+#[test]
+fn accepts_plain_file_names() {
+    for id in [
+        "q1.mp3",
+        "part_1.1.mp3",
+        "Picture 3.png",
+        ".hidden.png",
+        "файл.mp3",
+    ] {
+        assert!(validate_asset_id(id).is_ok(), "{id} should be valid");
+    }
+}
+
+#[test]
+fn rejects_names_that_leave_the_folder() {
+    let ids = [
+        "",
+        ".",
+        "..",
+        "../exam.db",
+        "../../etc/passwd",
+        "a/../b.mp3",
+        "audio/q1.mp3",
+        "./q1.mp3",
+        "/etc/passwd",
+        "\\\\server\\share\\x.mp3",
+        "..\\exam.db",
+        "C:\\Windows\\win.ini",
+        "C:secret.txt",
+        "q1.mp3\0.png",
+        "q1.mp3\n",
+        " q1.mp3",
+        "q1.mp3.",
+        "CON",
+        "nul.txt",
+        "com1.mp3",
+    ];
+    for id in ids {
+        assert!(validate_asset_id(id).is_err(), "{id:?} should be refused");
+    }
+    assert!(validate_asset_id(&"a".repeat(256)).is_err());
+}
+
+#[test]
+fn resolves_in_root_order() {
+    let fixture = Fixture::new();
+    fs::write(fixture.path("sounds").join("q1.mp3"), b"bundled").unwrap();
+
+    let path = resolve(&fixture.roots(), "q1.mp3").unwrap();
+    assert_eq!(fs::read(path).unwrap(), b"question");
+    assert_eq!(read(&fixture.roots(), "bell.mp3").unwrap(), b"bell");
+}
+
+#[test]
+fn traversal_never_reads_outside_the_roots() {
+    let fixture = Fixture::new();
+    let absolute = fixture.path("outside").join("secret.txt");
+    let ids = [
+        "../outside/secret.txt".to_string(),
+        "../exam.db".to_string(),
+        absolute.to_string_lossy().to_string(),
+        "..".to_string(),
+        "secret.txt".to_string(),
+    ];
+    for id in &ids {
+        assert!(
+            read(&fixture.roots(), id).is_err(),
+            "{id} should be refused"
+        );
+    }
+}
+
+#[test]
+fn reports_missing_files_and_skips_missing_roots() {
+    let fixture = Fixture::new();
+    let roots = vec![fixture.path("absent"), fixture.path("sounds")];
+
+    assert_eq!(read(&roots, "bell.mp3").unwrap(), b"bell");
+    let error = read(&roots, "missing.mp3").unwrap_err();
+    assert!(error.contains("not found"));
+}
+
+#[test]
+fn folders_are_not_assets() {
+    let fixture = Fixture::new();
+    fs::create_dir_all(fixture.path("audio").join("nested")).unwrap();
+
+    assert!(read(&fixture.roots(), "nested").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn refuses_symlinks_that_escape_the_root() {
+    let fixture = Fixture::new();
+    std::os::unix::fs::symlink(
+        fixture.path("outside").join("secret.txt"),
+        fixture.path("audio").join("link.mp3"),
+    )
+    .unwrap();
+    std::os::unix::fs::symlink(fixture.path("outside"), fixture.path("audio").join("dir")).unwrap();
+
+    let error = read(&fixture.roots(), "link.mp3").unwrap_err();
+    assert!(error.contains("outside its folder"));
+    assert!(read(&fixture.roots(), "dir").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn follows_symlinks_that_stay_inside_the_root() {
+    let fixture = Fixture::new();
+    std::os::unix::fs::symlink("q1.mp3", fixture.path("audio").join("alias.mp3")).unwrap();
+
+    assert_eq!(read(&fixture.roots(), "alias.mp3").unwrap(), b"question");
+}
+
+#[test]
+fn confines_recorded_paths_to_their_root() {
+    let fixture = Fixture::new();
+    let root = fixture.path("audio");
+
+    assert!(confine(&root, &root.join("q1.mp3")).is_ok());
+    assert!(confine(&root, &root.join("..").join("exam.db")).is_err());
+    assert!(confine(&root, &fixture.path("outside").join("secret.txt")).is_err());
+    assert!(confine(&root, &root).is_err());
+    assert!(confine(&root, &root.join("missing.mp3")).is_err());
+}
+
+#[test]
+fn content_ids_follow_the_bytes() {
+    let hash = "ab".repeat(32);
+
+    assert_eq!(content_id(&hash, "Prompt.JPG"), format!("{hash}.jpg"));
+    assert_eq!(content_id(&hash, "../../x.mp3"), format!("{hash}.mp3"));
+    assert_eq!(content_id(&hash, "no_extension"), hash);
+    assert_eq!(content_id(&hash, "odd.ext ension"), hash);
+    assert!(validate_asset_id(&content_id(&hash, "a.png")).is_ok());
+
+    assert_eq!(content_hash(&format!("{hash}.png")), Some(hash.as_str()));
+    assert_eq!(content_hash(&hash), Some(hash.as_str()));
+    assert_eq!(content_hash("part1_1_17.png"), None);
+    assert_eq!(content_hash(&format!("{}.png", "AB".repeat(32))), None);
+}
@@ -1,26 +1,138 @@
 use base64::{engine::general_purpose, Engine as _};
 use rand::{distributions::Alphanumeric, Rng};
 use reqwest::multipart::{Form, Part};
-use rusqlite::{params, Connection, OptionalExtension};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-use tauri::{AppHandle, Manager};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use tokio::sync::Notify;
+
+use crate::assets;
+use crate::db::Db;
+use crate::media::{self, MediaKind};
+use crate::migrations;
+use crate::package::{self, PackageReader, PackageWriter};
+use crate::webm;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Question {
     pub id: i64,
     pub part: i32,
     pub sub_part: i32,
+    /// Asset ID of the question audio under `audio/`, or empty.
     pub audio_path: String,
+    /// Asset ID of the prompt image under `images/`, or empty.
     pub image_path: String,
     pub text: String,
     pub pack_id: String,
     pub pack_order: i32,
     pub response_time: i32,
     pub active: bool,
+    /// Seconds the candidate gets to prepare before recording starts.
+    pub prep_time: i32,
+}
+
+/// A section to draw for `get_random_questions`.
+#[derive(Deserialize, Debug)]
+pub struct QuestionDraw {
+    pub part: i32,
+    pub sub_part: Option<i32>,
+    pub count: i32,
+    #[serde(default)]
+    pub exclude_ids: Vec<i64>,
+    pub blueprint_id: Option<i64>,
+    pub attempt_id: Option<i64>,
+    /// Draws from this seed instead of the attempt's.
+    pub seed: Option<i64>,
+}
+
+/// Question fields sent by the admin form to `add_question` and
+/// `update_question`.
+#[derive(Deserialize, Debug)]
+pub struct QuestionInput {
+    pub part: i32,
+    pub sub_part: Option<i32>,
+    pub response_time: i32,
+    /// Seconds of preparation; the section's usual time when left out.
+    #[serde(default)]
+    pub prep_time: Option<i32>,
+    pub audio_path: Option<String>,
+    pub image_path: Option<String>,
+    pub text: Option<String>,
+    pub pack_id: Option<String>,
+    pub pack_order: Option<i32>,
+}
+
+/// One saved version of a question. Responses point at the revision that was
+/// current when they were recorded.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QuestionRevision {
+    pub id: i64,
+    pub question_id: i64,
+    pub revision: i32,
+    pub part: i32,
+    pub sub_part: i32,
+    pub audio_path: String,
+    pub image_path: String,
+    pub text: String,
+    pub pack_id: String,
+    pub pack_order: i32,
+    pub response_time: i32,
+    pub prep_time: i32,
+    pub created_at: String,
+    pub response_count: i64,
+}
+
+/// One section of an exam blueprint: which questions it draws and how they
+/// are presented.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlueprintSection {
+    pub part: i32,
+    #[serde(default)]
+    pub sub_part: i32,
+    #[serde(default)]
+    pub label: String,
+    pub question_count: i32,
+    /// `pack` serves one whole test pack in pack order; `random` draws
+    /// single questions.
+    #[serde(default = "default_section_selection")]
+    pub selection: String,
+    /// Seconds the candidate gets to prepare before answering.
+    #[serde(default)]
+    pub prep_time: i32,
+    #[serde(default)]
+    pub image_required: bool,
+    /// A bundled audio file played when the section starts.
+    #[serde(default)]
+    pub instruction_audio: String,
+    #[serde(default)]
+    pub instruction_text: String,
+}
+
+/// An exam format and its sections, in the order they are taken.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Blueprint {
+    pub id: i64,
+    pub name: String,
+    pub description: String,
+    pub is_default: bool,
+    pub sections: Vec<BlueprintSection>,
+}
+
+/// Blueprint fields sent by the admin form, and the blueprint definition
+/// carried by full test exports.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlueprintInput {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub sections: Vec<BlueprintSection>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -28,6 +140,10 @@ pub struct FullTest {
     pub id: i64,
     pub name: String,
     pub created_at: String,
+    /// The exam format the test is laid out for.
+    pub blueprint_id: Option<i64>,
+    pub blueprint_name: String,
+    pub attempt_count: i64,
     pub questions: Vec<Question>,
 }
 
@@ -37,6 +153,53 @@ pub struct Attempt {
     pub student_name: String,
     pub started_at: String,
     pub finished_at: Option<String>,
+    pub overall_score: Option<f64>,
+    /// One of `created`, `in_progress`, `paused`, `finished`, `abandoned` or
+    /// `voided`.
+    pub status: String,
+    /// `random` or `full_test`; `None` for attempts recorded before the mode
+    /// was stored.
+    pub mode: Option<String>,
+    pub full_test_id: Option<i64>,
+    pub full_test_name: Option<String>,
+    /// The exam format the attempt was taken in.
+    pub blueprint_id: Option<i64>,
+    pub blueprint_name: Option<String>,
+    /// The seed a random exam was drawn from; `None` for full tests and for
+    /// attempts from before seeds were kept.
+    pub selection_seed: Option<i64>,
+    /// Whether the seed was given out as a paper code.
+    pub shared_seed: bool,
+    pub served_count: i64,
+    pub unanswered_count: i64,
+}
+
+/// What the exam screen needs to carry on with an interrupted attempt.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResumedAttempt {
+    pub attempt_id: i64,
+    pub student_name: String,
+    pub mode: String,
+    pub full_test_id: Option<i64>,
+    pub blueprint_id: i64,
+    /// Questions that already have a saved response, in serving order.
+    pub answered: Vec<Question>,
+    /// Questions still to be answered. For a full test this is the rest of
+    /// the form; for a random exam it is what was served but not answered.
+    pub remaining: Vec<Question>,
+}
+
+/// A question shown to the candidate during an attempt, in serving order.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ServedQuestion {
+    pub position: i32,
+    pub question_id: i64,
+    pub question_revision_id: Option<i64>,
+    pub part: i32,
+    pub sub_part: i32,
+    pub question_text: String,
+    pub served_at: String,
+    pub response_count: i64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -59,19 +222,66 @@ pub struct Recording {
     pub part: i32,
     pub sub_part: i32,
     pub question_text: String,
+    /// When the question was first put on screen; `None` for responses
+    /// saved before served questions were tracked.
+    pub served_at: Option<String>,
+    /// When the response was saved.
     pub recorded_at: String,
+    /// Seconds of audio, as measured by the backend when the recording could
+    /// be read.
     pub duration: i32,
+    /// The duration reported by the exam screen.
+    pub claimed_duration: Option<i32>,
+    pub audio_duration_ms: Option<i64>,
+    /// Timing problems found when the response was saved: `unreadable_audio`,
+    /// `duration_mismatch`, `over_time_limit` or `answered_late`.
+    pub timing_flags: Vec<String>,
+    pub rating: Option<Rating>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Rating {
+    pub response_id: i64,
+    pub fluency: Option<i32>,
+    pub lexical: Option<i32>,
+    pub grammar: Option<i32>,
+    pub pronunciation: Option<i32>,
+    pub comment: String,
+    pub score: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AttemptRatings {
+    pub attempt_id: i64,
+    pub student_name: String,
+    pub total_responses: usize,
+    pub rated_responses: usize,
+    pub fluency: Option<f64>,
+    pub lexical: Option<f64>,
+    pub grammar: Option<f64>,
+    pub pronunciation: Option<f64>,
+    pub overall_score: Option<f64>,
+    pub recordings: Vec<Recording>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ExportedAsset {
     filename: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     data_base64: String,
+    #[serde(default)]
+    sha256: String,
+    /// Zip entry holding the file in a `.cefrpkg` package. Legacy JSON
+    /// exports embed the file in `data_base64` instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    entry: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct ExportedQuestion {
     source_id: i64,
+    #[serde(default)]
+    uuid: String,
     part: i32,
     sub_part: i32,
     audio_path: String,
@@ -80,6 +290,10 @@ struct ExportedQuestion {
     pack_id: String,
     pack_order: i32,
     response_time: i32,
+    /// Missing from exports made before questions had their own
+    /// preparation time; the section's usual time is used then.
+    #[serde(default)]
+    prep_time: Option<i32>,
     audio_file: Option<ExportedAsset>,
     image_file: Option<ExportedAsset>,
 }
@@ -95,6 +309,34 @@ struct QuestionsExportFile {
 #[derive(Serialize, Debug)]
 pub struct ImportQuestionsResult {
     pub imported: usize,
+    pub questions: ImportCounts,
+}
+
+/// How an import treats questions and full tests that are already in the
+/// bank, matched by their content ID.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStrategy {
+    /// Keep the existing item and link to it.
+    #[default]
+    Skip,
+    /// Overwrite the existing item with the imported content.
+    Update,
+    /// Always add a new copy.
+    Duplicate,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ImportCounts {
+    pub created: usize,
+    pub reused: usize,
+    pub updated: usize,
+}
+
+impl ImportCounts {
+    fn total(&self) -> usize {
+        self.created + self.reused + self.updated
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -103,8 +345,15 @@ struct FullTestExportFile {
     version: i32,
     exported_at: String,
     source_id: i64,
+    #[serde(default)]
+    uuid: String,
     name: String,
     created_at: String,
+    /// The exam format the test was built for, so it can be set up on import
+    /// when the bank does not have it yet. Missing from older exports, which
+    /// use the default format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blueprint: Option<BlueprintInput>,
     questions: Vec<ExportedQuestion>,
 }
 
@@ -116,156 +365,348 @@ struct FullTestsExportFile {
     tests: Vec<FullTestExportFile>,
 }
 
+#[derive(Serialize, Debug)]
+pub struct ExportPackageResult {
+    pub path: String,
+    pub size_bytes: u64,
+    pub tests: usize,
+    pub questions: usize,
+}
+
 #[derive(Serialize, Debug)]
 pub struct ImportFullTestResult {
     pub imported_tests: usize,
     pub imported_questions: usize,
     pub full_test_id: i64,
+    pub tests: ImportCounts,
+    pub questions: ImportCounts,
 }
 
-const DEFAULT_TELEGRAM_BOT_TOKEN: &str = "8505694385:AAELharS3HfJoSc4CmAxinNFRR-LteH5dIA";
-const TELEGRAM_API_BASE: &str = "https://api.telegram.org";
-const TELEGRAM_CHAT_ID_SETTING_KEY: &str = "telegram_chat_id";
-const TELEGRAM_CHAT_IDS_SETTING_KEY: &str = "telegram_chat_ids";
+/// Disk use of one asset folder, from `get_asset_storage_report`.
+#[derive(Serialize, Debug)]
+pub struct AssetFolderUsage {
+    pub folder: String,
+    pub file_count: u64,
+    pub total_bytes: u64,
+    /// Files something in the bank still uses.
+    pub referenced_count: u64,
+    pub referenced_bytes: u64,
+    /// Uses of those files; questions with the same content share one file.
+    /// `None` for thumbnails, which belong to their image rather than being
+    /// referred to.
+    pub reference_count: Option<u64>,
+}
 
-#[derive(Deserialize, Debug)]
-struct TelegramApiResponse {
-    ok: bool,
-    description: Option<String>,
+/// A stored file nothing refers to, found by `collect_unused_assets`.
+#[derive(Serialize, Debug)]
+pub struct UnusedAsset {
+    pub folder: String,
+    pub asset_id: String,
+    pub size_bytes: u64,
+    /// False for dry runs and for files too recent to collect.
+    pub removed: bool,
 }
 
-// Initialize database connection
-fn init_db(app_handle: &AppHandle) -> Result<Connection, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+/// A small preview of a prompt image, from `get_image_thumbnail`.
+#[derive(Serialize, Debug)]
+pub struct ImageThumbnail {
+    pub mime: String,
+    pub data: Vec<u8>,
+}
 
-    fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
-    let db_path = app_dir.join("exam.db");
+/// A file an item of the bank relies on that is not fit to use, found by
+/// `check_bank`.
+#[derive(Serialize, Debug)]
+pub struct BankIssue {
+    /// `question`, `full_test` or `response`.
+    pub item_kind: String,
+    pub item_id: i64,
+    pub item_label: String,
+    /// `audio`, `images` or `responses`; empty for problems of a full test.
+    pub folder: String,
+    /// The asset ID or recording path the item refers to.
+    pub reference: String,
+    /// `invalid_name`, `missing`, `unreadable`, `checksum_mismatch`,
+    /// `wrong_type`, `corrupt`, `question_unavailable` or `question_broken`.
+    pub problem: String,
+    pub detail: String,
+    /// A stored file of the same kind, audio, image or recording, that
+    /// `repair_bank` can relink the item to.
+    pub relink_to: Option<String>,
+}
 
-    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+#[derive(Serialize, Debug)]
+pub struct BankCheckReport {
+    pub questions_checked: u64,
+    pub full_tests_checked: u64,
+    pub responses_checked: u64,
+    pub files_checked: u64,
+    pub issues: Vec<BankIssue>,
+}
 
-    // Create tables if they don't exist
-    conn.execute_batch(
-        "CREATE TABLE IF NOT EXISTS attempts (
-            id INTEGER PRIMARY KEY,
-            student_name TEXT NOT NULL,
-            started_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            finished_at DATETIME
-        );
+/// A fix for one `BankIssue`, sent back by the admin screen.
+#[derive(Deserialize, Debug)]
+pub struct BankRepair {
+    /// `relink` points the item at `relink_to`; `deactivate` deletes a
+    /// question or full test the way the admin screen does.
+    pub action: String,
+    pub item_kind: String,
+    pub item_id: i64,
+    pub folder: String,
+    pub reference: String,
+}
 
-        CREATE TABLE IF NOT EXISTS questions (
-            id INTEGER PRIMARY KEY,
-            part INTEGER NOT NULL,
-            sub_part INTEGER NOT NULL DEFAULT 0,
-            audio_path TEXT NOT NULL,
-            image_path TEXT NOT NULL DEFAULT '',
-            text TEXT NOT NULL DEFAULT '',
-            pack_id TEXT NOT NULL DEFAULT '',
-            pack_order INTEGER NOT NULL DEFAULT 0,
-            response_time INTEGER NOT NULL,
-            active BOOLEAN DEFAULT 1
-        );
+/// What a dry run found for one asset in an export.
+#[derive(Serialize, Debug)]
+pub struct ImportPreviewAsset {
+    pub folder: String,
+    pub filename: String,
+    /// `new`, `stored` when the same content is already in the bank,
+    /// `missing` or `corrupt`.
+    pub status: String,
+    pub sha256: String,
+    pub size_bytes: usize,
+    pub stored_filename: Option<String>,
+}
 
-        CREATE TABLE IF NOT EXISTS responses (
-            id INTEGER PRIMARY KEY,
-            attempt_id INTEGER NOT NULL,
-            question_id INTEGER NOT NULL,
-            audio_path TEXT NOT NULL,
-            duration INTEGER NOT NULL,
-            recorded_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (attempt_id) REFERENCES attempts (id),
-            FOREIGN KEY (question_id) REFERENCES questions (id)
-        );
+#[derive(Serialize, Debug)]
+pub struct ImportPreviewQuestion {
+    pub source_id: i64,
+    pub test_source_id: Option<i64>,
+    pub uuid: String,
+    pub section: String,
+    /// `create`, `reuse` or `update`, or `repeat` when the question already
+    /// appeared earlier in the same file.
+    pub action: String,
+    pub existing_question_id: Option<i64>,
+    /// Active questions in the bank with the same section and text or image
+    /// that the import would not match by content ID.
+    pub similar_question_ids: Vec<i64>,
+    pub assets: Vec<ImportPreviewAsset>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
 
-        CREATE TABLE IF NOT EXISTS ratings (
-            response_id INTEGER PRIMARY KEY,
-            fluency INTEGER CHECK (fluency BETWEEN 1 AND 9),
-            lexical INTEGER CHECK (lexical BETWEEN 1 AND 9),
-            grammar INTEGER CHECK (grammar BETWEEN 1 AND 9),
-            pronunciation INTEGER CHECK (pronunciation BETWEEN 1 AND 9),
-            comment TEXT,
-            FOREIGN KEY (response_id) REFERENCES responses (id)
-        );
+#[derive(Serialize, Debug)]
+pub struct ImportPreviewTest {
+    pub source_id: i64,
+    pub uuid: String,
+    pub name: String,
+    pub action: String,
+    pub existing_full_test_id: Option<i64>,
+    pub question_count: usize,
+    /// The exam format the test will use.
+    pub blueprint_name: String,
+    /// Whether the import sets that format up because the bank lacks it.
+    pub creates_blueprint: bool,
+    pub errors: Vec<String>,
+}
 
-        CREATE TABLE IF NOT EXISTS app_settings (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL DEFAULT ''
-        );
+/// Dry-run report for an export file. Building it reads the bank but never
+/// writes to the database or the app data folder.
+#[derive(Serialize, Debug)]
+pub struct ImportPreview {
+    pub format: String,
+    pub strategy: ImportStrategy,
+    pub valid: bool,
+    pub errors: Vec<String>,
+    pub tests: Vec<ImportPreviewTest>,
+    pub questions: Vec<ImportPreviewQuestion>,
+    pub test_counts: ImportCounts,
+    pub question_counts: ImportCounts,
+}
 
-        CREATE TABLE IF NOT EXISTS full_tests (
-            id INTEGER PRIMARY KEY,
-            name TEXT NOT NULL,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            active BOOLEAN DEFAULT 1
-        );
+const DEFAULT_TELEGRAM_API_BASE: &str = "https://api.telegram.org";
+const TELEGRAM_API_BASE_SETTING_KEY: &str = "telegram_api_base";
+const TELEGRAM_CHAT_ID_SETTING_KEY: &str = "telegram_chat_id";
+const TELEGRAM_CHAT_IDS_SETTING_KEY: &str = "telegram_chat_ids";
+const TELEGRAM_BOT_TOKEN_SECRET_FILE: &str = "telegram_bot_token";
+const RESPONSE_STORAGE_VERSION_SETTING_KEY: &str = "response_storage_version";
+const EXPOSURE_LOOKBACK_DAYS_SETTING_KEY: &str = "exposure_lookback_days";
+const DEFAULT_EXPOSURE_LOOKBACK_DAYS: i64 = 365;
+const MAX_EXPOSURE_LOOKBACK_DAYS: i64 = 3650;
+/// Seeds stay well inside the integers JavaScript represents exactly.
+const MAX_SELECTION_SEED: i64 = 999_999_999;
+const RESPONSE_STORAGE_VERSION: &str = "2";
+const ASSET_STORAGE_VERSION_SETTING_KEY: &str = "asset_storage_version";
+const ASSET_STORAGE_VERSION: &str = "2";
+/// Unused assets younger than this are left alone: an upload is saved before
+/// the question that refers to it.
+const UNUSED_ASSET_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+/// Folders of the content-addressed store, in report order.
+const ASSET_FOLDERS: [&str; 2] = ["audio", "images"];
+/// Thumbnails of stored images, named after the image's asset ID plus the
+/// extension of the thumbnail's own format.
+const THUMBNAIL_DIR: &str = "thumbnails";
+const IMPORT_STAGING_DIR: &str = "import-staging";
+const ATTEMPT_MODE_RANDOM: &str = "random";
+const ATTEMPT_MODE_FULL_TEST: &str = "full_test";
+const SECTION_SELECTION_PACK: &str = "pack";
+const SECTION_SELECTION_RANDOM: &str = "random";
+const MAX_SECTION_PREP_TIME_SECONDS: i32 = 600;
+/// Sections with at least this much preparation are long turns, timed on a
+/// separate preparation screen; anything shorter is a quick countdown.
+const LONG_PREP_MIN_SECONDS: i32 = 30;
+const ATTEMPT_STATUS_CREATED: &str = "created";
+const ATTEMPT_STATUS_IN_PROGRESS: &str = "in_progress";
+const ATTEMPT_STATUS_PAUSED: &str = "paused";
+const ATTEMPT_STATUS_FINISHED: &str = "finished";
+const ATTEMPT_STATUS_ABANDONED: &str = "abandoned";
+const ATTEMPT_STATUS_VOIDED: &str = "voided";
+// Recorders take a moment to start and stop, so a response may run slightly
+// past its question's response time before it is flagged.
+const RESPONSE_TIME_GRACE_SECONDS: i64 = 3;
+const DURATION_MISMATCH_TOLERANCE_SECONDS: i64 = 2;
+const TIMING_FLAG_UNREADABLE_AUDIO: &str = "unreadable_audio";
+const TIMING_FLAG_DURATION_MISMATCH: &str = "duration_mismatch";
+const TIMING_FLAG_OVER_TIME_LIMIT: &str = "over_time_limit";
+const TIMING_FLAG_ANSWERED_LATE: &str = "answered_late";
+// A question is served before its section instructions and prompt audio
+// play, and the first picture of a section gets double preparation, so the
+// time from serving to answering is allowed this much on top.
+const SERVED_TIME_ALLOWANCE_SECONDS: i64 = 120;
+const LEGACY_EXPORT_VERSION: i32 = 1;
+const PACKAGE_EXPORT_VERSION: i32 = 2;
+
+const DELIVERY_STATUS_PENDING: &str = "pending";
+const DELIVERY_STATUS_SENDING: &str = "sending";
+const DELIVERY_STATUS_SENT: &str = "sent";
+const DELIVERY_STATUS_FAILED: &str = "failed";
+const DELIVERY_STATUS_CANCELLED: &str = "cancelled";
+const DELIVERY_MAX_ATTEMPTS: i32 = 8;
+// A delivery is sent as a summary message, the prompt image and the
+// recording, in that order. `steps_sent` counts how many have gone through.
+const DELIVERY_STEP_MESSAGE: i32 = 0;
+const DELIVERY_STEP_PHOTO: i32 = 1;
+const DELIVERY_STEP_DOCUMENT: i32 = 2;
+const DELIVERY_STEPS: i32 = 3;
+const DELIVERY_BASE_RETRY_SECS: i64 = 30;
+const DELIVERY_MAX_RETRY_SECS: i64 = 3600;
+const DELIVERY_POLL_INTERVAL: Duration = Duration::from_secs(15);
+const TELEGRAM_CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Long enough to upload a recording over a slow link, short enough that a
+/// stalled request fails and is retried instead of holding up the queue.
+const TELEGRAM_REQUEST_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 
-        CREATE TABLE IF NOT EXISTS full_test_questions (
-            full_test_id INTEGER NOT NULL,
-            question_id INTEGER NOT NULL,
-            position INTEGER NOT NULL,
-            PRIMARY KEY (full_test_id, question_id),
-            FOREIGN KEY (full_test_id) REFERENCES full_tests (id),
-            FOREIGN KEY (question_id) REFERENCES questions (id)
-        );",
-    )
-    .map_err(|e| e.to_string())?;
+#[derive(Deserialize, Debug)]
+struct TelegramApiResponse {
+    ok: bool,
+    description: Option<String>,
+    parameters: Option<TelegramResponseParameters>,
+    result: Option<serde_json::Value>,
+}
 
-    ensure_column(&conn, "questions", "sub_part", "INTEGER NOT NULL DEFAULT 0")?;
-    ensure_column(&conn, "questions", "image_path", "TEXT NOT NULL DEFAULT ''")?;
-    ensure_column(&conn, "questions", "text", "TEXT NOT NULL DEFAULT ''")?;
-    ensure_column(&conn, "questions", "pack_id", "TEXT NOT NULL DEFAULT ''")?;
-    ensure_column(
-        &conn,
-        "questions",
-        "pack_order",
-        "INTEGER NOT NULL DEFAULT 0",
-    )?;
-    conn.execute(
-        "UPDATE questions SET sub_part = 1 WHERE part = 1 AND sub_part = 0",
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+#[derive(Deserialize, Debug)]
+struct TelegramResponseParameters {
+    retry_after: Option<u64>,
+}
 
-    Ok(conn)
+/// Bot API endpoint plus credentials. `api_base` points at api.telegram.org
+/// by default, or at a self-hosted telegram-bot-api server.
+struct TelegramBotApi {
+    client: reqwest::Client,
+    api_base: String,
+    bot_token: String,
 }
 
-fn ensure_column(
-    conn: &Connection,
-    table: &str,
-    column: &str,
-    column_def: &str,
-) -> Result<(), String> {
-    let mut stmt = conn
-        .prepare(&format!("PRAGMA table_info({})", table))
-        .map_err(|e| e.to_string())?;
-    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+impl TelegramBotApi {
+    fn new(api_base: &str, bot_token: &str) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(TELEGRAM_CONNECT_TIMEOUT)
+            .timeout(TELEGRAM_REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to set up the Telegram client: {}", e))?;
+
+        Ok(TelegramBotApi {
+            client,
+            api_base: api_base.trim_end_matches('/').to_string(),
+            bot_token: bot_token.to_string(),
+        })
+    }
 
-    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
-        let name: String = row.get(1).map_err(|e| e.to_string())?;
-        if name == column {
-            return Ok(());
+    fn method_url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", self.api_base, self.bot_token, method)
+    }
+}
+
+#[derive(Debug)]
+struct TelegramError {
+    message: String,
+    retry_after: Option<u64>,
+}
+
+impl From<String> for TelegramError {
+    fn from(message: String) -> Self {
+        TelegramError {
+            message,
+            retry_after: None,
         }
     }
+}
 
-    conn.execute(
-        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, column_def),
-        [],
-    )
-    .map_err(|e| e.to_string())?;
+impl std::fmt::Display for TelegramError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
 
-    Ok(())
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DatabaseDiagnostics {
+    pub database_path: String,
+    pub database_size_bytes: u64,
+    pub schema_version: i32,
+    pub latest_schema_version: i32,
+    pub migrations: Vec<migrations::AppliedMigration>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TelegramBotTokenStatus {
+    pub configured: bool,
+    pub source: String,
+    pub masked_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TelegramBotInfo {
+    pub id: i64,
+    pub is_bot: bool,
+    pub first_name: String,
+    pub username: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TelegramDelivery {
+    pub id: i64,
+    pub response_id: i64,
+    pub attempt_id: i64,
+    pub student_name: String,
+    pub question_id: i64,
+    pub chat_id: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: String,
+    pub next_attempt_at: Option<String>,
+    pub created_at: String,
+    pub sent_at: Option<String>,
+}
+
+/// Wakes the background Telegram delivery worker when new deliveries are
+/// queued, so it does not have to wait for its next poll.
+#[derive(Default)]
+pub struct TelegramDeliveryWorker {
+    wake: Notify,
 }
 
 fn unique_filename(dir: &Path, filename: &str) -> Result<String, String> {
+    let is_free = |name: &str| !dir.join(name).exists();
+
     let base_name = Path::new(filename)
         .file_name()
         .and_then(|name| name.to_str())
-        .ok_or_else(|| "Invalid filename".to_string())?
-        .to_string();
+        .map(safe_file_name)
+        .ok_or_else(|| "Invalid filename".to_string())?;
 
-    if !dir.join(&base_name).exists() {
+    if is_free(&base_name) {
         return Ok(base_name);
     }
 
@@ -284,7 +725,7 @@ fn unique_filename(dir: &Path, filename: &str) -> Result<String, String> {
         } else {
             format!("{}_{}.{}", stem, suffix, ext)
         };
-        if !dir.join(&candidate).exists() {
+        if is_free(&candidate) {
             return Ok(candidate);
         }
     }
@@ -292,1969 +733,7505 @@ fn unique_filename(dir: &Path, filename: &str) -> Result<String, String> {
     Err("Failed to generate unique filename".to_string())
 }
 
-fn question_from_row(row: &rusqlite::Row) -> rusqlite::Result<Question> {
-    Ok(Question {
-        id: row.get(0)?,
-        part: row.get(1)?,
-        sub_part: row.get(2)?,
-        audio_path: row.get(3)?,
-        image_path: row.get(4)?,
-        text: row.get(5)?,
-        pack_id: row.get(6)?,
-        pack_order: row.get(7)?,
-        response_time: row.get(8)?,
-        active: row.get(9)?,
-    })
-}
+/// `filename` as a valid asset ID. The stem goes through
+/// `sanitize_path_component`; a short alphanumeric extension is kept so the
+/// file type can still be told from the name.
+fn safe_file_name(filename: &str) -> String {
+    let path = Path::new(filename);
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .filter(|ext| ext.len() <= 10 && ext.chars().all(|c| c.is_ascii_alphanumeric()));
+    let stem = match extension {
+        Some(_) => path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(""),
+        None => filename,
+    };
 
-fn section_label(part: i32, sub_part: i32) -> String {
-    if part == 1 && sub_part == 1 {
-        return "Part 1.1".to_string();
+    let stem = sanitize_path_component(stem, "file");
+    match extension {
+        Some(extension) => format!("{}.{}", stem, extension.to_ascii_lowercase()),
+        None => stem,
     }
-    if part == 1 && sub_part == 2 {
-        return "Part 1.2".to_string();
-    }
-    format!("Part {}", part)
 }
 
-fn validate_full_test_questions(
-    conn: &Connection,
-    question_ids: &[i64],
-) -> Result<Vec<Question>, String> {
-    if question_ids.is_empty() {
-        return Err("Choose at least one question for the full test".to_string());
-    }
-
-    let mut seen_ids = HashSet::new();
-    let mut questions = Vec::new();
-
-    for question_id in question_ids {
-        if !seen_ids.insert(*question_id) {
-            return Err(format!(
-                "Question {} was selected more than once",
-                question_id
-            ));
+/// Makes `value` safe to use as a single file or folder name on Windows,
+/// macOS and Linux. Reserved characters become `_`, trailing dots and spaces
+/// are dropped and DOS device names such as `CON` or `COM1` are prefixed.
+fn sanitize_path_component(value: &str, fallback: &str) -> String {
+    let mut sanitized = String::new();
+    for c in value.trim().chars() {
+        let replacement = if c.is_control()
+            || c.is_whitespace()
+            || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*')
+        {
+            '_'
+        } else {
+            c
+        };
+        if replacement == '_' && sanitized.ends_with('_') {
+            continue;
         }
-
-        let question = conn
-            .query_row(
-                "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active
-                 FROM questions WHERE id = ? AND active = 1",
-                params![question_id],
-                question_from_row,
-            )
-            .optional()
-            .map_err(|e| e.to_string())?
-            .ok_or_else(|| format!("Question {} does not exist or is inactive", question_id))?;
-
-        questions.push(question);
+        sanitized.push(replacement);
     }
 
-    let required_sections = [(1, 1), (1, 2), (2, 0), (3, 0)];
-    for (part, sub_part) in required_sections {
-        let exists = questions
-            .iter()
-            .any(|question| question.part == part && question.sub_part == sub_part);
+    let mut sanitized: String = sanitized
+        .trim_end_matches(['.', ' ', '_'])
+        .trim_start_matches('_')
+        .chars()
+        .take(64)
+        .collect();
+    if sanitized.is_empty() {
+        sanitized = fallback.to_string();
+    }
 
-        if !exists {
-            return Err(format!(
-                "A full test must include at least one {} question",
-                section_label(part, sub_part)
-            ));
-        }
+    if assets::is_device_name(&sanitized) {
+        sanitized.insert(0, '_');
     }
 
-    Ok(questions)
+    sanitized
 }
 
-fn telegram_bot_token() -> String {
-    std::env::var("TELEGRAM_BOT_TOKEN").unwrap_or_else(|_| DEFAULT_TELEGRAM_BOT_TOKEN.to_string())
+/// Folder holding every recording of one attempt:
+/// `responses/{attempt date}/attempt_{id}_{student}`. The attempt ID keeps
+/// candidates with the same name, and retakes, apart.
+fn attempt_response_dir(
+    app_dir: &Path,
+    attempt_id: i64,
+    started_at: &str,
+    student_name: &str,
+) -> PathBuf {
+    let date = started_at
+        .get(..10)
+        .and_then(|value| chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+        .unwrap_or_else(|| chrono::Local::now().date_naive())
+        .format("%Y-%m-%d")
+        .to_string();
+
+    app_dir.join("responses").join(date).join(format!(
+        "attempt_{}_{}",
+        attempt_id,
+        sanitize_path_component(student_name, "student")
+    ))
 }
 
-fn normalize_telegram_chat_ids(values: Vec<String>) -> Vec<String> {
-    let mut seen = HashSet::new();
-    let mut normalized = Vec::new();
+fn move_file(source: &Path, target: &Path) -> Result<(), String> {
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
 
-    for value in values {
-        let trimmed = value.trim();
-        if trimmed.is_empty() {
-            continue;
+    // rename fails across volumes, so fall back to copy and delete.
+    fs::copy(source, target)
+        .map_err(|e| format!("Failed to copy {:?} to {:?}: {}", source, target, e))?;
+    fs::remove_file(source).map_err(|e| format!("Failed to remove {:?}: {}", source, e))?;
+    Ok(())
+}
+
+fn remove_empty_dirs_up_to(start: &Path, stop: &Path) {
+    let mut current = start.to_path_buf();
+    while current.starts_with(stop) && current != stop {
+        if fs::remove_dir(&current).is_err() {
+            break;
         }
-        let candidate = trimmed.to_string();
-        if seen.insert(candidate.clone()) {
-            normalized.push(candidate);
+        if !current.pop() {
+            break;
         }
     }
-
-    normalized
 }
 
-fn parse_telegram_chat_ids(raw: &str) -> Vec<String> {
-    let trimmed = raw.trim();
-    if trimmed.is_empty() {
-        return Vec::new();
-    }
+/// Moves recordings saved under the old `responses/{date}/{student_name}`
+/// layout into attempt folders and updates `responses.audio_path`. The old
+/// layout let candidates with the same name overwrite each other, so when
+/// several rows share one file only the newest keeps it; the older rows are
+/// cleared because the audio they point at belongs to someone else.
+pub fn migrate_response_storage(db: &Db, app_dir: &Path) -> Result<(), String> {
+    db.with_connection(|conn| migrate_response_storage_rows(conn, app_dir))
+}
 
-    if trimmed.starts_with('[') {
-        if let Ok(values) = serde_json::from_str::<Vec<String>>(trimmed) {
-            return normalize_telegram_chat_ids(values);
-        }
+fn migrate_response_storage_rows(conn: &Connection, app_dir: &Path) -> Result<(), String> {
+    if app_setting(conn, RESPONSE_STORAGE_VERSION_SETTING_KEY)?.as_deref()
+        == Some(RESPONSE_STORAGE_VERSION)
+    {
+        return Ok(());
     }
 
-    let values = trimmed
-        .split(|c| c == ',' || c == ';' || c == '\n')
-        .map(|value| value.to_string())
-        .collect::<Vec<_>>();
-    normalize_telegram_chat_ids(values)
-}
+    let responses_root = app_dir.join("responses");
 
-fn env_telegram_chat_ids() -> Vec<String> {
-    if let Ok(value) = std::env::var("TELEGRAM_CHAT_IDS") {
-        let ids = parse_telegram_chat_ids(&value);
-        if !ids.is_empty() {
-            return ids;
+    let mut stmt = conn
+        .prepare(
+            "SELECT responses.id,
+                    responses.attempt_id,
+                    responses.question_id,
+                    responses.audio_path,
+                    attempts.started_at,
+                    attempts.student_name
+             FROM responses
+             JOIN attempts ON responses.attempt_id = attempts.id
+             WHERE responses.audio_path <> ''
+             ORDER BY responses.recorded_at DESC, responses.id DESC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    let mut claimed_paths = HashSet::new();
+    for (response_id, attempt_id, question_id, audio_path, started_at, student_name) in rows {
+        let source = PathBuf::from(&audio_path);
+        let target_dir = attempt_response_dir(app_dir, attempt_id, &started_at, &student_name);
+        if source.starts_with(&target_dir) {
+            continue;
         }
-    }
 
-    if let Ok(value) = std::env::var("TELEGRAM_CHAT_ID") {
-        return parse_telegram_chat_ids(&value);
-    }
+        if !claimed_paths.insert(audio_path.clone()) {
+            println!(
+                "Warning: response {} shared {:?} with a newer recording; clearing its audio path",
+                response_id, source
+            );
+            conn.execute(
+                "UPDATE responses SET audio_path = '' WHERE id = ?",
+                params![response_id],
+            )
+            .map_err(|e| e.to_string())?;
+            continue;
+        }
 
-    Vec::new()
-}
+        if !source.exists() {
+            println!(
+                "Warning: recording for response {} is missing at {:?}; leaving it in place",
+                response_id, source
+            );
+            continue;
+        }
 
-fn stored_telegram_chat_ids(conn: &Connection) -> Result<Option<Vec<String>>, String> {
-    let saved_chat_ids_raw: Option<String> = conn
-        .query_row(
-            "SELECT value FROM app_settings WHERE key = ?",
-            params![TELEGRAM_CHAT_IDS_SETTING_KEY],
-            |row| row.get(0),
+        fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
+        let filename = unique_filename(&target_dir, &format!("q{}.webm", question_id))?;
+        let target = target_dir.join(filename);
+        move_file(&source, &target)?;
+
+        conn.execute(
+            "UPDATE responses SET audio_path = ? WHERE id = ?",
+            params![target.to_string_lossy().to_string(), response_id],
         )
-        .optional()
         .map_err(|e| e.to_string())?;
 
-    if let Some(value) = saved_chat_ids_raw {
-        return Ok(Some(parse_telegram_chat_ids(&value)));
+        if let Some(parent) = source.parent() {
+            remove_empty_dirs_up_to(parent, &responses_root);
+        }
     }
 
-    let legacy_chat_id_raw: Option<String> = conn
-        .query_row(
-            "SELECT value FROM app_settings WHERE key = ?",
-            params![TELEGRAM_CHAT_ID_SETTING_KEY],
-            |row| row.get(0),
-        )
-        .optional()
-        .map_err(|e| e.to_string())?;
+    persist_app_setting(
+        conn,
+        RESPONSE_STORAGE_VERSION_SETTING_KEY,
+        Some(RESPONSE_STORAGE_VERSION),
+    )?;
 
-    Ok(legacy_chat_id_raw.map(|value| parse_telegram_chat_ids(&value)))
+    Ok(())
 }
 
-fn persist_telegram_chat_ids(conn: &Connection, chat_ids: &[String]) -> Result<(), String> {
-    let serialized_ids = serde_json::to_string(chat_ids)
-        .map_err(|e| format!("Failed to serialize chat IDs: {}", e))?;
-    let primary_chat_id = chat_ids
-        .first()
-        .map(|value| value.as_str())
-        .unwrap_or_default();
+/// Moves question audio and images into the content-addressed store. Older
+/// versions saved them under whatever name or path the admin screen sent,
+/// including absolute paths and paths into subfolders. Each file is stored
+/// under its content ID and the questions and their revisions updated, so
+/// copies of one file collapse into one and the old names are left for
+/// `collect_unused_assets`. Files that cannot be found are reported and left
+/// as they are.
+pub fn migrate_asset_storage(db: &Db, app_dir: &Path) -> Result<(), String> {
+    db.with_connection(|conn| migrate_asset_storage_rows(conn, app_dir))
+}
 
-    conn.execute(
-        "INSERT INTO app_settings (key, value) VALUES (?, ?)
-         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
-        params![TELEGRAM_CHAT_IDS_SETTING_KEY, serialized_ids],
-    )
-    .map_err(|e| e.to_string())?;
+fn migrate_asset_storage_rows(conn: &Connection, app_dir: &Path) -> Result<(), String> {
+    if app_setting(conn, ASSET_STORAGE_VERSION_SETTING_KEY)?.as_deref()
+        == Some(ASSET_STORAGE_VERSION)
+    {
+        return Ok(());
+    }
 
-    conn.execute(
-        "INSERT INTO app_settings (key, value) VALUES (?, ?)
-         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
-        params![TELEGRAM_CHAT_ID_SETTING_KEY, primary_chat_id],
-    )
-    .map_err(|e| e.to_string())?;
+    for (column, folder) in [("audio_path", "audio"), ("image_path", "images")] {
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {column} FROM questions WHERE {column} <> ''
+                 UNION
+                 SELECT {column} FROM question_revisions WHERE {column} <> ''"
+            ))
+            .map_err(|e| e.to_string())?;
+        let values = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        drop(stmt);
 
-    Ok(())
-}
+        let root = app_dir.join(folder);
+        for value in values {
+            let trimmed = value.trim();
+            if trimmed.is_empty() || assets::content_hash(trimmed).is_some() {
+                continue;
+            }
 
-fn load_telegram_chat_ids(conn: &Connection) -> Result<Vec<String>, String> {
-    if let Some(chat_ids) = stored_telegram_chat_ids(conn)? {
-        if chat_ids.is_empty() {
-            return Err(
-                "Telegram chat IDs are not configured. Add at least one in Admin Dashboard."
-                    .to_string(),
-            );
+            let source = if Path::new(trimmed).is_absolute() {
+                PathBuf::from(trimmed)
+            } else {
+                root.join(trimmed)
+            };
+            if !source.is_file() {
+                println!(
+                    "Warning: {} file {:?} is missing; leaving it in place",
+                    folder, source
+                );
+                continue;
+            }
+
+            let data = fs::read(&source)
+                .map_err(|e| format!("Failed to read {} file {:?}: {}", folder, source, e))?;
+            let asset_id = store_asset(conn, app_dir, folder, trimmed, &data)?;
+
+            for table in ["questions", "question_revisions"] {
+                conn.execute(
+                    &format!("UPDATE {table} SET {column} = ? WHERE {column} = ?"),
+                    params![asset_id, value],
+                )
+                .map_err(|e| e.to_string())?;
+            }
         }
-        return Ok(chat_ids);
     }
 
-    let env_chat_ids = env_telegram_chat_ids();
-    if !env_chat_ids.is_empty() {
-        return Ok(env_chat_ids);
-    }
+    persist_app_setting(
+        conn,
+        ASSET_STORAGE_VERSION_SETTING_KEY,
+        Some(ASSET_STORAGE_VERSION),
+    )?;
 
-    Err("Telegram chat IDs are not configured. Add at least one in Admin Dashboard.".to_string())
+    Ok(())
 }
 
-fn truncate_for_telegram(value: &str, max_chars: usize) -> String {
-    let mut iter = value.chars();
-    let mut truncated: String = iter.by_ref().take(max_chars).collect();
-    if iter.next().is_some() {
-        if max_chars > 3 {
-            truncated.truncate(max_chars.saturating_sub(3));
-            truncated.push_str("...");
-        }
-    }
-    truncated
+fn question_from_row(row: &rusqlite::Row) -> rusqlite::Result<Question> {
+    Ok(Question {
+        id: row.get(0)?,
+        part: row.get(1)?,
+        sub_part: row.get(2)?,
+        audio_path: row.get(3)?,
+        image_path: row.get(4)?,
+        text: row.get(5)?,
+        pack_id: row.get(6)?,
+        pack_order: row.get(7)?,
+        response_time: row.get(8)?,
+        active: row.get(9)?,
+        prep_time: row.get(10)?,
+    })
 }
 
-fn format_part_label(part: i32, sub_part: i32) -> String {
-    if part == 1 && (sub_part == 1 || sub_part == 2) {
-        return format!("Part 1.{}", sub_part);
-    }
-    format!("Part {}", part)
+fn default_section_selection() -> String {
+    SECTION_SELECTION_RANDOM.to_string()
 }
 
-fn resolve_image_path(app_dir: &Path, image_path: &str) -> Option<PathBuf> {
-    let trimmed = image_path.trim();
-    if trimmed.is_empty() {
-        return None;
-    }
-
-    let path = Path::new(trimmed);
-    if path.is_absolute() {
-        return Some(path.to_path_buf());
+/// The label used for a section no blueprint names, e.g. `Part 2` or
+/// `Part 1.2`.
+fn fallback_section_label(part: i32, sub_part: i32) -> String {
+    if sub_part > 0 {
+        format!("Part {}.{}", part, sub_part)
+    } else {
+        format!("Part {}", part)
     }
-
-    Some(app_dir.join("images").join(trimmed))
 }
 
-fn resolve_question_asset_path(
-    app_handle: &AppHandle,
-    app_dir: &Path,
-    folder: &str,
-    asset_path: &str,
-) -> Option<PathBuf> {
-    let trimmed = asset_path.trim();
-    if trimmed.is_empty() {
-        return None;
-    }
-
-    let path = Path::new(trimmed);
-    if path.is_absolute() {
-        return Some(path.to_path_buf());
-    }
+fn default_blueprint_id(conn: &Connection) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT id FROM blueprints WHERE is_default = 1 AND active = 1 ORDER BY id LIMIT 1",
+        [],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| "No default exam format is set".to_string())
+}
 
-    let mut candidates = vec![app_dir.join(folder).join(trimmed)];
+fn blueprint_name(conn: &Connection, blueprint_id: i64) -> Result<String, String> {
+    conn.query_row(
+        "SELECT name FROM blueprints WHERE id = ? AND active = 1",
+        params![blueprint_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Exam format {} not found or inactive", blueprint_id))
+}
 
-    if folder == "audio" {
-        if let Ok(resource_dir) = app_handle.path().resource_dir() {
-            candidates.push(resource_dir.join(trimmed));
-            candidates.push(resource_dir.join("audio").join(trimmed));
-            candidates.push(resource_dir.join("instruction_sounds").join(trimmed));
-            candidates.push(
-                resource_dir
-                    .join("_up_")
-                    .join("instruction_sounds")
-                    .join(trimmed),
-            );
-            candidates.push(
-                resource_dir
-                    .join("_up_")
-                    .join("src")
-                    .join("instruction_sounds")
-                    .join(trimmed),
-            );
+/// The chosen blueprint, or the default one when none was chosen.
+fn resolve_blueprint_id(conn: &Connection, blueprint_id: Option<i64>) -> Result<i64, String> {
+    match blueprint_id {
+        Some(blueprint_id) => {
+            blueprint_name(conn, blueprint_id)?;
+            Ok(blueprint_id)
         }
-
-        candidates.push(
-            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-                .join("..")
-                .join("src")
-                .join("instruction_sounds")
-                .join(trimmed),
-        );
+        None => default_blueprint_id(conn),
     }
+}
 
-    candidates.into_iter().find(|candidate| candidate.exists())
+fn blueprint_section_from_row(row: &rusqlite::Row) -> rusqlite::Result<BlueprintSection> {
+    Ok(BlueprintSection {
+        part: row.get(0)?,
+        sub_part: row.get(1)?,
+        label: row.get(2)?,
+        question_count: row.get(3)?,
+        selection: row.get(4)?,
+        prep_time: row.get(5)?,
+        image_required: row.get(6)?,
+        instruction_audio: row.get(7)?,
+        instruction_text: row.get(8)?,
+    })
 }
 
-fn read_exported_asset(
-    app_handle: &AppHandle,
-    app_dir: &Path,
-    folder: &str,
-    asset_path: &str,
-) -> Result<Option<ExportedAsset>, String> {
-    let trimmed = asset_path.trim();
-    if trimmed.is_empty() {
-        return Ok(None);
-    }
+/// The sections of a blueprint in the order they are taken.
+fn blueprint_sections(
+    conn: &Connection,
+    blueprint_id: i64,
+) -> Result<Vec<BlueprintSection>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT part, sub_part, label, question_count, selection, prep_time, image_required,
+                    instruction_audio, instruction_text
+             FROM blueprint_sections
+             WHERE blueprint_id = ?
+             ORDER BY position",
+        )
+        .map_err(|e| e.to_string())?;
 
-    let resolved_path = resolve_question_asset_path(app_handle, app_dir, folder, trimmed)
-        .ok_or_else(|| format!("Could not find {} file for export: {}", folder, trimmed))?;
+    let sections = stmt
+        .query_map(params![blueprint_id], blueprint_section_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(sections)
+}
 
-    let data = fs::read(&resolved_path).map_err(|e| {
-        format!(
-            "Failed to read {} file {} for export: {}",
-            folder, trimmed, e
+fn load_blueprint(conn: &Connection, blueprint_id: i64) -> Result<Blueprint, String> {
+    let (name, description, is_default) = conn
+        .query_row(
+            "SELECT name, description, is_default FROM blueprints WHERE id = ? AND active = 1",
+            params![blueprint_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
         )
-    })?;
-
-    let filename = Path::new(trimmed)
-        .file_name()
-        .and_then(|name| name.to_str())
-        .or_else(|| resolved_path.file_name().and_then(|name| name.to_str()))
-        .ok_or_else(|| format!("Invalid {} filename for export: {}", folder, trimmed))?
-        .to_string();
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Exam format {} not found or inactive", blueprint_id))?;
 
-    Ok(Some(ExportedAsset {
-        filename,
-        data_base64: general_purpose::STANDARD.encode(data),
-    }))
+    Ok(Blueprint {
+        id: blueprint_id,
+        name,
+        description,
+        is_default,
+        sections: blueprint_sections(conn, blueprint_id)?,
+    })
 }
 
-fn write_imported_asset(
-    app_dir: &Path,
-    folder: &str,
-    asset: Option<&ExportedAsset>,
+/// How a section is named: by the given blueprint if it has the section,
+/// otherwise by the default blueprint or any other active one.
+fn section_label(
+    conn: &Connection,
+    blueprint_id: Option<i64>,
+    part: i32,
+    sub_part: i32,
 ) -> Result<String, String> {
-    let Some(asset) = asset else {
-        return Ok(String::new());
-    };
+    let label: Option<String> = conn
+        .query_row(
+            "SELECT blueprint_sections.label
+             FROM blueprint_sections
+             JOIN blueprints ON blueprint_sections.blueprint_id = blueprints.id
+             WHERE blueprint_sections.part = ?1
+               AND blueprint_sections.sub_part = ?2
+               AND (blueprints.id = ?3 OR blueprints.active = 1)
+             ORDER BY blueprints.id = ?3 DESC, blueprints.is_default DESC, blueprints.id ASC
+             LIMIT 1",
+            params![part, sub_part, blueprint_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(label.unwrap_or_else(|| fallback_section_label(part, sub_part)))
+}
 
-    let target_dir = app_dir.join(folder);
-    fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
+/// Every question each attempt before attempt `?1` was shown (`exposures`),
+/// and when its candidate last saw a question in an earlier attempt of
+/// theirs, started within `?2` days before it (`seen`). Only attempts made
+/// before `?1` count, and with `?3` false there is no history at all.
+/// Attempts are matched to a candidate by name; responses count for attempts
+/// from before served questions were recorded.
+const EXPOSURE_CTES: &str = "WITH exposures AS (
+         SELECT attempt_id, question_id FROM attempt_questions
+         WHERE ?3 AND (?1 IS NULL OR attempt_id < ?1)
+         UNION
+         SELECT attempt_id, question_id FROM responses
+         WHERE ?3 AND (?1 IS NULL OR attempt_id < ?1)
+     ),
+     candidate_attempts AS (
+         SELECT attempts.id, attempts.started_at
+         FROM attempts
+         JOIN attempts AS current ON current.id = ?1
+         WHERE ?2 > 0
+           AND attempts.id < current.id
+           AND LOWER(TRIM(attempts.student_name)) = LOWER(TRIM(current.student_name))
+           AND attempts.started_at >= DATETIME(current.started_at, '-' || ?2 || ' days')
+     ),
+     seen AS (
+         SELECT exposures.question_id, MAX(candidate_attempts.started_at) AS last_seen
+         FROM exposures
+         JOIN candidate_attempts ON exposures.attempt_id = candidate_attempts.id
+         GROUP BY exposures.question_id
+     )";
+
+/// Orders the questions of a full test the way they are taken: by where their
+/// section comes in the test's blueprint, then as the admin arranged them.
+const FULL_TEST_QUESTION_ORDER: &str = "ORDER BY (
+         SELECT blueprint_sections.position
+         FROM full_tests
+         JOIN blueprint_sections ON blueprint_sections.blueprint_id = COALESCE(
+             full_tests.blueprint_id,
+             (SELECT id FROM blueprints WHERE is_default = 1 AND active = 1 ORDER BY id LIMIT 1)
+         )
+         WHERE full_tests.id = full_test_questions.full_test_id
+           AND blueprint_sections.part = questions.part
+           AND blueprint_sections.sub_part = questions.sub_part
+     ) ASC,
+     questions.part ASC,
+     questions.sub_part ASC,
+     full_test_questions.position ASC,
+     questions.id ASC";
+
+fn validated_selection_seed(seed: i64) -> Result<i64, String> {
+    if !(0..=MAX_SELECTION_SEED).contains(&seed) {
+        return Err(format!(
+            "A paper code must be a number from 0 to {}",
+            MAX_SELECTION_SEED
+        ));
+    }
+    Ok(seed)
+}
 
-    let safe_filename = unique_filename(&target_dir, &asset.filename)?;
-    let data = general_purpose::STANDARD
-        .decode(&asset.data_base64)
-        .map_err(|e| {
-            format!(
-                "Failed to decode imported {} file {}: {}",
-                folder, asset.filename, e
-            )
-        })?;
+fn random_selection_seed() -> i64 {
+    rand::thread_rng().gen_range(1..=MAX_SELECTION_SEED)
+}
 
-    fs::write(target_dir.join(&safe_filename), data).map_err(|e| {
-        format!(
-            "Failed to write imported {} file {}: {}",
-            folder, safe_filename, e
+/// The seed an attempt draws its questions from, and whether it was given
+/// out as a paper code. `None` for attempts from before seeds were kept.
+fn attempt_selection_seed(
+    conn: &Connection,
+    attempt_id: i64,
+) -> Result<Option<(i64, bool)>, String> {
+    let seed: Option<(Option<i64>, bool)> = conn
+        .query_row(
+            "SELECT selection_seed, shared_seed FROM attempts WHERE id = ?",
+            params![attempt_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
-    })?;
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match seed {
+        Some((seed, shared)) => Ok(seed.map(|seed| (seed, shared))),
+        None => Err(format!("Attempt {} was not found", attempt_id)),
+    }
+}
 
-    Ok(safe_filename)
+/// Where an item falls in the shuffle a seed makes of one section. It is
+/// worked out from the seed and the item alone, so the same seed orders a
+/// section the same way on every machine with the same bank.
+fn seeded_rank(seed: i64, scope: &str, item: &str) -> u64 {
+    let digest = Sha256::digest(format!("{}:{}:{}", seed, scope, item).as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
 }
 
-fn validate_exported_question(question: &ExportedQuestion) -> Result<(), String> {
-    if question.response_time <= 0 {
-        return Err(format!(
-            "Question {} has an invalid response time",
-            question.source_id
-        ));
+fn load_exposure_lookback_days(conn: &Connection) -> Result<i64, String> {
+    match app_setting(conn, EXPOSURE_LOOKBACK_DAYS_SETTING_KEY)? {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("Stored exposure lookback {:?} is not a number", value)),
+        None => Ok(DEFAULT_EXPOSURE_LOOKBACK_DAYS),
     }
+}
 
-    let has_image_asset = question.image_file.is_some();
-    if question.part == 1 && question.sub_part == 2 && !has_image_asset {
-        return Err(format!(
-            "Question {} is Part 1.2 and needs an image file",
-            question.source_id
-        ));
-    }
+/// What the blueprints drawing on a section ask of its questions. The
+/// question bank is shared, so a question has to suit every active format
+/// that uses its section. `incoming` holds the sections of a blueprint that
+/// an import is about to create.
+struct SectionRules {
+    label: String,
+    image_required: bool,
+    pack_required: bool,
+    /// Preparation time for questions that do not set their own, taken from
+    /// the default blueprint when it has the section.
+    default_prep_time: i32,
+    long_prep: bool,
+}
 
-    if question.part == 3 && !has_image_asset {
-        return Err(format!(
-            "Question {} is Part 3 and needs an image file",
-            question.source_id
-        ));
+impl SectionRules {
+    fn prep_time_range(&self) -> std::ops::RangeInclusive<i32> {
+        if self.long_prep {
+            LONG_PREP_MIN_SECONDS..=MAX_SECTION_PREP_TIME_SECONDS
+        } else {
+            0..=LONG_PREP_MIN_SECONDS - 1
+        }
     }
 
-    if question.part == 1 && (question.sub_part == 1 || question.sub_part == 2) {
-        if question.pack_id.trim().is_empty() {
+    /// The preparation time to store for a question, checked against the
+    /// range its section allows.
+    fn prep_time(&self, prep_time: Option<i32>) -> Result<i32, String> {
+        let Some(prep_time) = prep_time else {
+            return Ok(self.default_prep_time);
+        };
+        let range = self.prep_time_range();
+        if !range.contains(&prep_time) {
             return Err(format!(
-                "Question {} needs a test pack ID",
-                question.source_id
-            ));
-        }
-        if question.pack_order <= 0 {
-            return Err(format!(
-                "Question {} needs a pack order",
-                question.source_id
+                "{} questions need between {} and {} seconds of preparation",
+                self.label,
+                range.start(),
+                range.end()
             ));
         }
+        Ok(prep_time)
     }
-
-    Ok(())
 }
 
-fn validate_exported_full_test_sections(questions: &[ExportedQuestion]) -> Result<(), String> {
-    let required_sections = [(1, 1), (1, 2), (2, 0), (3, 0)];
-    for (part, sub_part) in required_sections {
-        let exists = questions
-            .iter()
-            .any(|question| question.part == part && question.sub_part == sub_part);
+fn section_rules(
+    conn: &Connection,
+    part: i32,
+    sub_part: i32,
+    incoming: &[BlueprintSection],
+) -> Result<SectionRules, String> {
+    let (used, mut image_required, mut pack_required, mut max_prep_time, default_prep_time): (
+        i64,
+        bool,
+        bool,
+        i32,
+        Option<i32>,
+    ) = conn
+        .query_row(
+            "SELECT COUNT(*),
+                    COALESCE(MAX(blueprint_sections.image_required), 0),
+                    COALESCE(MAX(blueprint_sections.selection = ?3), 0),
+                    COALESCE(MAX(blueprint_sections.prep_time), 0),
+                    MAX(CASE WHEN blueprints.is_default = 1 THEN blueprint_sections.prep_time END)
+             FROM blueprint_sections
+             JOIN blueprints ON blueprint_sections.blueprint_id = blueprints.id
+             WHERE blueprints.active = 1
+               AND blueprint_sections.part = ?1
+               AND blueprint_sections.sub_part = ?2",
+            params![part, sub_part, SECTION_SELECTION_PACK],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?;
 
-        if !exists {
-            return Err(format!(
-                "A full test import must include at least one {} question",
-                section_label(part, sub_part)
-            ));
-        }
+    let incoming_section = incoming
+        .iter()
+        .find(|section| section.part == part && section.sub_part == sub_part);
+    if let Some(section) = incoming_section {
+        image_required |= section.image_required;
+        pack_required |= section.selection == SECTION_SELECTION_PACK;
+        max_prep_time = max_prep_time.max(section.prep_time);
+    } else if used == 0 {
+        return Err(format!(
+            "{} is not part of any exam format",
+            fallback_section_label(part, sub_part)
+        ));
     }
 
-    Ok(())
+    let label = match incoming_section {
+        Some(section) if used == 0 => section.label.clone(),
+        _ => section_label(conn, None, part, sub_part)?,
+    };
+    let default_prep_time = default_prep_time
+        .or(incoming_section.map(|section| section.prep_time))
+        .unwrap_or(max_prep_time);
+    Ok(SectionRules {
+        label,
+        image_required,
+        pack_required,
+        default_prep_time,
+        long_prep: max_prep_time >= LONG_PREP_MIN_SECONDS,
+    })
 }
 
-fn validate_full_test_export_file(import_file: &FullTestExportFile) -> Result<String, String> {
-    if import_file.format != "cefr-speaking-full-test" {
-        return Err("This file is not a CEFR speaking full test export".to_string());
+/// Problems with the way a full test's questions fill the sections of its
+/// blueprint: each section needs exactly its `question_count` questions.
+/// `sections_used` lists the `(part, sub_part)` of each question.
+fn full_test_section_problems(
+    blueprint_name: &str,
+    sections: &[BlueprintSection],
+    sections_used: &[(i32, i32)],
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut reported = HashSet::new();
+    for (part, sub_part) in sections_used {
+        let in_blueprint = sections
+            .iter()
+            .any(|section| section.part == *part && section.sub_part == *sub_part);
+        if !in_blueprint && reported.insert((*part, *sub_part)) {
+            problems.push(format!(
+                "{} is not part of the {} format",
+                fallback_section_label(*part, *sub_part),
+                blueprint_name
+            ));
+        }
     }
 
-    let trimmed_name = import_file.name.trim().to_string();
-    if trimmed_name.is_empty() {
-        return Err("The selected full test export does not have a test name".to_string());
+    for section in sections {
+        let used = sections_used
+            .iter()
+            .filter(|(part, sub_part)| section.part == *part && section.sub_part == *sub_part)
+            .count();
+        if used != section.question_count as usize {
+            problems.push(format!(
+                "The {} format takes {} {} question(s), but the full test has {}",
+                blueprint_name, section.question_count, section.label, used
+            ));
+        }
     }
 
-    if import_file.questions.is_empty() {
-        return Err("The selected full test export does not contain any questions".to_string());
+    problems
+}
+
+fn validate_full_test_questions(
+    conn: &Connection,
+    blueprint_id: i64,
+    question_ids: &[i64],
+) -> Result<Vec<Question>, String> {
+    if question_ids.is_empty() {
+        return Err("Choose at least one question for the full test".to_string());
     }
 
-    validate_exported_full_test_sections(&import_file.questions)?;
+    let mut seen_ids = HashSet::new();
+    let mut questions = Vec::new();
 
-    let mut source_ids = HashSet::new();
-    for question in &import_file.questions {
-        validate_exported_question(question)?;
-        if !source_ids.insert(question.source_id) {
+    for question_id in question_ids {
+        if !seen_ids.insert(*question_id) {
             return Err(format!(
-                "Question {} appears more than once in the full test export",
-                question.source_id
+                "Question {} was selected more than once",
+                question_id
             ));
         }
+
+        let question = conn
+            .query_row(
+                "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active, prep_time
+                 FROM questions WHERE id = ? AND active = 1",
+                params![question_id],
+                question_from_row,
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Question {} does not exist or is inactive", question_id))?;
+
+        questions.push(question);
+    }
+
+    let name = blueprint_name(conn, blueprint_id)?;
+    let sections = blueprint_sections(conn, blueprint_id)?;
+    let sections_used = questions
+        .iter()
+        .map(|question| (question.part, question.sub_part))
+        .collect::<Vec<_>>();
+    if let Some(problem) = full_test_section_problems(&name, &sections, &sections_used)
+        .into_iter()
+        .next()
+    {
+        return Err(problem);
     }
 
-    Ok(trimmed_name)
+    Ok(questions)
 }
 
-fn insert_exported_question(
-    conn: &Connection,
-    app_dir: &Path,
-    question: ExportedQuestion,
-) -> Result<i64, String> {
-    validate_exported_question(&question)?;
+fn app_data_dir(app_handle: &AppHandle) -> Result<PathBuf, String> {
+    app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))
+}
 
-    let sub_part_value = question.sub_part;
-    let pack_id_value = question.pack_id.trim().to_string();
-    let audio = write_imported_asset(app_dir, "audio", question.audio_file.as_ref())?;
-    let image = write_imported_asset(app_dir, "images", question.image_file.as_ref())?;
-    let question_text = if question.part == 3 {
-        String::new()
-    } else {
-        question.text
-    };
+fn telegram_bot_token_path(app_dir: &Path) -> PathBuf {
+    app_dir.join("secrets").join(TELEGRAM_BOT_TOKEN_SECRET_FILE)
+}
 
-    conn.execute(
-        "INSERT INTO questions (part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1)",
-        params![
-            question.part,
-            sub_part_value,
-            audio,
-            image,
-            question_text,
-            pack_id_value,
-            question.pack_order,
-            question.response_time
-        ],
-    )
-    .map_err(|e| e.to_string())?;
+fn validate_telegram_bot_token_format(token: &str) -> Result<(), String> {
+    let Some((bot_id, secret)) = token.split_once(':') else {
+        return Err("Telegram bot token must look like 123456789:ABC...".to_string());
+    };
 
-    Ok(conn.last_insert_rowid())
-}
+    if bot_id.is_empty() || !bot_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err("Telegram bot token must start with the numeric bot ID".to_string());
+    }
 
-fn guess_mime(path: &Path, is_image: bool) -> &'static str {
-    match path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .unwrap_or_default()
-        .to_ascii_lowercase()
-        .as_str()
+    if secret.len() < 30
+        || !secret
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
     {
-        "png" => "image/png",
-        "jpg" | "jpeg" => "image/jpeg",
-        "webp" => "image/webp",
-        "gif" => "image/gif",
-        "mp3" => "audio/mpeg",
-        "m4a" => "audio/mp4",
-        "ogg" => "audio/ogg",
-        "wav" => "audio/wav",
-        "webm" => "audio/webm",
-        _ if is_image => "application/octet-stream",
-        _ => "application/octet-stream",
+        return Err("Telegram bot token secret part is malformed".to_string());
     }
+
+    Ok(())
 }
 
-fn convert_audio_to_mp3(source_path: &Path) -> Result<PathBuf, String> {
-    if !source_path.exists() {
-        return Err(format!(
-            "Cannot convert to MP3 because source file does not exist: {:?}",
-            source_path
-        ));
+fn mask_telegram_bot_token(token: &str) -> String {
+    let Some((bot_id, secret)) = token.split_once(':') else {
+        return "****".to_string();
+    };
+    let tail: String = secret
+        .chars()
+        .rev()
+        .take(4)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .collect();
+    format!("{}:****{}", bot_id, tail)
+}
+
+fn stored_telegram_bot_token(app_dir: &Path) -> Result<Option<String>, String> {
+    match fs::read_to_string(telegram_bot_token_path(app_dir)) {
+        Ok(value) => {
+            let trimmed = value.trim();
+            if trimmed.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(trimmed.to_string()))
+            }
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(format!("Failed to read Telegram bot token: {}", error)),
     }
+}
 
-    let ffmpeg_available = Command::new("ffmpeg")
-        .arg("-version")
-        .output()
-        .map(|output| output.status.success())
-        .unwrap_or(false);
+fn env_telegram_bot_token() -> Option<String> {
+    std::env::var("TELEGRAM_BOT_TOKEN")
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
 
-    if !ffmpeg_available {
-        return Err(
-            "ffmpeg is not installed or not available in PATH. Install ffmpeg to send MP3 files."
-                .to_string(),
-        );
+/// Returns the bot token and where it came from. The token saved from the
+/// Admin Dashboard wins over the `TELEGRAM_BOT_TOKEN` environment variable;
+/// there is deliberately no built-in fallback.
+fn load_telegram_bot_token(app_dir: &Path) -> Result<Option<(String, &'static str)>, String> {
+    if let Some(token) = stored_telegram_bot_token(app_dir)? {
+        return Ok(Some((token, "settings")));
     }
+    Ok(env_telegram_bot_token().map(|token| (token, "environment")))
+}
 
-    let stem = source_path
-        .file_stem()
-        .and_then(|value| value.to_str())
-        .unwrap_or("response");
-    let suffix: String = rand::thread_rng()
-        .sample_iter(&Alphanumeric)
-        .take(8)
-        .map(char::from)
-        .collect();
-    let output_path = std::env::temp_dir().join(format!("{}_{}_telegram.mp3", stem, suffix));
+fn persist_telegram_bot_token(app_dir: &Path, token: Option<&str>) -> Result<(), String> {
+    let token_path = telegram_bot_token_path(app_dir);
 
-    let status = Command::new("ffmpeg")
-        .arg("-y")
-        .arg("-i")
-        .arg(source_path)
-        .arg("-vn")
-        .arg("-codec:a")
-        .arg("libmp3lame")
-        .arg("-q:a")
-        .arg("2")
-        .arg(&output_path)
-        .status()
-        .map_err(|e| format!("Failed to run ffmpeg for MP3 conversion: {}", e))?;
+    let Some(token) = token else {
+        if let Err(error) = fs::remove_file(&token_path) {
+            if error.kind() != std::io::ErrorKind::NotFound {
+                return Err(format!("Failed to remove Telegram bot token: {}", error));
+            }
+        }
+        return Ok(());
+    };
 
-    if !status.success() {
-        return Err("ffmpeg failed to convert recording to MP3".to_string());
-    }
+    let secrets_dir = token_path
+        .parent()
+        .ok_or_else(|| "Invalid Telegram bot token path".to_string())?;
+    fs::create_dir_all(secrets_dir).map_err(|e| e.to_string())?;
 
-    if !output_path.exists() {
-        return Err("MP3 conversion finished but output file was not created".to_string());
+    // Write next to the target and rename so a crash never leaves a
+    // half-written token behind.
+    let temp_path = token_path.with_extension("tmp");
+    {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(&temp_path)
+            .map_err(|e| format!("Failed to save Telegram bot token: {}", e))?;
+        std::io::Write::write_all(&mut file, token.as_bytes())
+            .map_err(|e| format!("Failed to save Telegram bot token: {}", e))?;
     }
+    fs::rename(&temp_path, &token_path)
+        .map_err(|e| format!("Failed to save Telegram bot token: {}", e))?;
 
-    Ok(output_path)
+    Ok(())
 }
 
-async fn parse_telegram_response(
-    response: reqwest::Response,
-    endpoint: &str,
-) -> Result<(), String> {
-    let status = response.status();
-    let body = response
-        .text()
-        .await
-        .map_err(|e| format!("Failed to read Telegram {} response: {}", endpoint, e))?;
+fn app_setting(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM app_settings WHERE key = ?",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
 
-    if !status.is_success() {
-        return Err(format!(
-            "Telegram {} failed with HTTP {}: {}",
-            endpoint, status, body
-        ));
+fn persist_app_setting(conn: &Connection, key: &str, value: Option<&str>) -> Result<(), String> {
+    match value {
+        Some(value) => conn.execute(
+            "INSERT INTO app_settings (key, value) VALUES (?, ?)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        ),
+        None => conn.execute("DELETE FROM app_settings WHERE key = ?", params![key]),
     }
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    let parsed: TelegramApiResponse = serde_json::from_str(&body).map_err(|e| {
-        format!(
-            "Failed to parse Telegram {} response JSON: {} (body: {})",
-            endpoint, e, body
-        )
-    })?;
+fn normalize_telegram_api_base(value: &str) -> Result<String, String> {
+    let trimmed = value.trim().trim_end_matches('/');
+    let url = reqwest::Url::parse(trimmed)
+        .map_err(|e| format!("Telegram API base URL is invalid: {}", e))?;
 
-    if !parsed.ok {
-        return Err(format!(
-            "Telegram {} returned error: {}",
-            endpoint,
-            parsed
-                .description
-                .unwrap_or_else(|| "Unknown Telegram error".to_string())
-        ));
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err("Telegram API base URL must start with http:// or https://".to_string());
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err("Telegram API base URL cannot contain a query or fragment".to_string());
     }
 
-    Ok(())
+    Ok(trimmed.to_string())
 }
 
-async fn send_telegram_message(
-    client: &reqwest::Client,
-    bot_token: &str,
-    chat_id: &str,
-    text: &str,
-) -> Result<(), String> {
-    let url = format!("{}/bot{}/sendMessage", TELEGRAM_API_BASE, bot_token);
-    let response = client
-        .post(url)
-        .form(&[("chat_id", chat_id), ("text", text)])
-        .send()
-        .await
-        .map_err(|e| format!("Failed to call Telegram sendMessage: {}", e))?;
+/// Resolves the Bot API base URL: the Admin Dashboard setting first, then the
+/// `TELEGRAM_API_BASE` environment variable, then api.telegram.org.
+fn load_telegram_api_base(conn: &Connection) -> Result<String, String> {
+    if let Some(value) = app_setting(conn, TELEGRAM_API_BASE_SETTING_KEY)? {
+        if !value.trim().is_empty() {
+            return normalize_telegram_api_base(&value);
+        }
+    }
 
-    parse_telegram_response(response, "sendMessage").await
+    if let Ok(value) = std::env::var("TELEGRAM_API_BASE") {
+        if !value.trim().is_empty() {
+            return normalize_telegram_api_base(&value);
+        }
+    }
+
+    Ok(DEFAULT_TELEGRAM_API_BASE.to_string())
 }
 
-async fn send_telegram_photo(
-    client: &reqwest::Client,
-    bot_token: &str,
-    chat_id: &str,
-    image_path: &Path,
-    caption: &str,
-) -> Result<(), String> {
-    let image_data = fs::read(image_path)
-        .map_err(|e| format!("Failed to read image file for Telegram: {}", e))?;
-    let image_name = image_path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .unwrap_or("prompt_image.bin")
-        .to_string();
+fn normalize_telegram_chat_ids(values: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut normalized = Vec::new();
 
-    let image_part = Part::bytes(image_data)
-        .file_name(image_name)
-        .mime_str(guess_mime(image_path, true))
-        .map_err(|e| format!("Failed to set Telegram image MIME type: {}", e))?;
+    for value in values {
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let candidate = trimmed.to_string();
+        if seen.insert(candidate.clone()) {
+            normalized.push(candidate);
+        }
+    }
 
-    let mut form = Form::new()
-        .text("chat_id", chat_id.to_string())
-        .part("photo", image_part);
+    normalized
+}
 
-    if !caption.trim().is_empty() {
-        form = form.text("caption", caption.to_string());
+fn parse_telegram_chat_ids(raw: &str) -> Vec<String> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        return Vec::new();
     }
 
-    let url = format!("{}/bot{}/sendPhoto", TELEGRAM_API_BASE, bot_token);
-    let response = client
-        .post(url)
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to call Telegram sendPhoto: {}", e))?;
+    if trimmed.starts_with('[') {
+        if let Ok(values) = serde_json::from_str::<Vec<String>>(trimmed) {
+            return normalize_telegram_chat_ids(values);
+        }
+    }
 
-    parse_telegram_response(response, "sendPhoto").await
+    let values = trimmed
+        .split(|c| c == ',' || c == ';' || c == '\n')
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>();
+    normalize_telegram_chat_ids(values)
 }
 
-async fn send_telegram_document(
-    client: &reqwest::Client,
-    bot_token: &str,
-    chat_id: &str,
-    file_path: &Path,
-    caption: &str,
-) -> Result<(), String> {
-    let file_data =
-        fs::read(file_path).map_err(|e| format!("Failed to read recording for Telegram: {}", e))?;
-    let file_name = file_path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .unwrap_or("response.webm")
-        .to_string();
+fn env_telegram_chat_ids() -> Vec<String> {
+    if let Ok(value) = std::env::var("TELEGRAM_CHAT_IDS") {
+        let ids = parse_telegram_chat_ids(&value);
+        if !ids.is_empty() {
+            return ids;
+        }
+    }
 
-    let document_part = Part::bytes(file_data)
-        .file_name(file_name)
-        .mime_str(guess_mime(file_path, false))
-        .map_err(|e| format!("Failed to set Telegram document MIME type: {}", e))?;
+    if let Ok(value) = std::env::var("TELEGRAM_CHAT_ID") {
+        return parse_telegram_chat_ids(&value);
+    }
 
-    let mut form = Form::new()
-        .text("chat_id", chat_id.to_string())
-        .part("document", document_part);
+    Vec::new()
+}
 
-    if !caption.trim().is_empty() {
-        form = form.text("caption", caption.to_string());
+fn stored_telegram_chat_ids(conn: &Connection) -> Result<Option<Vec<String>>, String> {
+    let saved_chat_ids_raw: Option<String> = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?",
+            params![TELEGRAM_CHAT_IDS_SETTING_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    if let Some(value) = saved_chat_ids_raw {
+        return Ok(Some(parse_telegram_chat_ids(&value)));
     }
 
-    let url = format!("{}/bot{}/sendDocument", TELEGRAM_API_BASE, bot_token);
-    let response = client
-        .post(url)
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| format!("Failed to call Telegram sendDocument: {}", e))?;
+    let legacy_chat_id_raw: Option<String> = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?",
+            params![TELEGRAM_CHAT_ID_SETTING_KEY],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
 
-    parse_telegram_response(response, "sendDocument").await
+    Ok(legacy_chat_id_raw.map(|value| parse_telegram_chat_ids(&value)))
 }
 
-async fn send_response_to_telegram(
-    app_dir: &Path,
-    chat_id: &str,
-    student_name: &str,
-    question_id: i64,
-    part: i32,
-    sub_part: i32,
-    question_text: &str,
-    image_path: &str,
-    duration: i32,
-    audio_file_path: &Path,
-) -> Result<(), String> {
-    let bot_token = telegram_bot_token();
-    let client = reqwest::Client::new();
-    let part_label = format_part_label(part, sub_part);
-    let question = if question_text.trim().is_empty() {
-        "(No question text provided)"
-    } else {
-        question_text.trim()
-    };
+fn persist_telegram_chat_ids(conn: &Connection, chat_ids: &[String]) -> Result<(), String> {
+    let serialized_ids = serde_json::to_string(chat_ids)
+        .map_err(|e| format!("Failed to serialize chat IDs: {}", e))?;
+    let primary_chat_id = chat_ids
+        .first()
+        .map(|value| value.as_str())
+        .unwrap_or_default();
 
-    let message = format!(
-        "New CEFR speaking response\nStudent: {}\nSection: {}\nQuestion ID: {}\nQuestion: {}\nDuration: {}s",
-        student_name, part_label, question_id, question, duration
-    );
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![TELEGRAM_CHAT_IDS_SETTING_KEY, serialized_ids],
+    )
+    .map_err(|e| e.to_string())?;
 
-    send_telegram_message(
-        &client,
-        &bot_token,
-        chat_id,
-        &truncate_for_telegram(&message, 4096),
+    conn.execute(
+        "INSERT INTO app_settings (key, value) VALUES (?, ?)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![TELEGRAM_CHAT_ID_SETTING_KEY, primary_chat_id],
     )
-    .await?;
+    .map_err(|e| e.to_string())?;
 
-    if let Some(resolved_image_path) = resolve_image_path(app_dir, image_path) {
-        if resolved_image_path.exists() {
-            let image_caption =
-                format!("Prompt image for {} (Question {})", part_label, question_id);
-            send_telegram_photo(
-                &client,
-                &bot_token,
-                chat_id,
-                &resolved_image_path,
-                &truncate_for_telegram(&image_caption, 1024),
-            )
-            .await?;
-        } else {
-            println!(
-                "Warning: image file configured for question {} but not found at {:?}",
-                question_id, resolved_image_path
+    Ok(())
+}
+
+fn load_telegram_chat_ids(conn: &Connection) -> Result<Vec<String>, String> {
+    if let Some(chat_ids) = stored_telegram_chat_ids(conn)? {
+        if chat_ids.is_empty() {
+            return Err(
+                "Telegram chat IDs are not configured. Add at least one in Admin Dashboard."
+                    .to_string(),
             );
         }
+        return Ok(chat_ids);
     }
 
-    let mp3_caption = format!(
-        "Answer recording (MP3) | {} | Question {} | Student {}",
-        part_label, question_id, student_name
-    );
-    let fallback_caption = format!(
-        "Answer recording (original format) | {} | Question {} | Student {}",
-        part_label, question_id, student_name
-    );
-
-    match convert_audio_to_mp3(audio_file_path) {
-        Ok(mp3_file_path) => {
-            let send_result = send_telegram_document(
-                &client,
-                &bot_token,
-                chat_id,
-                &mp3_file_path,
-                &truncate_for_telegram(&mp3_caption, 1024),
-            )
-            .await;
+    let env_chat_ids = env_telegram_chat_ids();
+    if !env_chat_ids.is_empty() {
+        return Ok(env_chat_ids);
+    }
 
-            if let Err(error) = fs::remove_file(&mp3_file_path) {
-                if error.kind() != std::io::ErrorKind::NotFound {
-                    println!(
-                        "Warning: failed to delete temporary MP3 file {:?}: {}",
-                        mp3_file_path, error
-                    );
-                }
-            }
+    Err("Telegram chat IDs are not configured. Add at least one in Admin Dashboard.".to_string())
+}
 
-            send_result?;
+fn truncate_for_telegram(value: &str, max_chars: usize) -> String {
+    let mut iter = value.chars();
+    let mut truncated: String = iter.by_ref().take(max_chars).collect();
+    if iter.next().is_some() {
+        if max_chars > 3 {
+            truncated.truncate(max_chars.saturating_sub(3));
+            truncated.push_str("...");
         }
-        Err(error) => {
-            println!(
-                "Warning: MP3 conversion failed. Sending original recording instead. Reason: {}",
-                error
+    }
+    truncated
+}
+
+/// The prompt image `image_id` names, for Telegram deliveries.
+fn resolve_image_path(app_dir: &Path, image_id: &str) -> Result<PathBuf, String> {
+    assets::resolve(&[app_dir.join("images")], image_id.trim())
+}
+
+/// The folders an asset ID under `folder` is looked up in, in order. Question
+/// audio can also name one of the instruction sounds bundled with the app,
+/// which end up in different places in development and installed builds.
+fn asset_roots(app_handle: &AppHandle, app_dir: &Path, folder: &str) -> Vec<PathBuf> {
+    let mut roots = vec![app_dir.join(folder)];
+
+    if folder == "audio" {
+        if let Ok(resource_dir) = app_handle.path().resource_dir() {
+            roots.push(resource_dir.join("audio"));
+            roots.push(resource_dir.join("instruction_sounds"));
+            roots.push(resource_dir.join("_up_").join("instruction_sounds"));
+            roots.push(
+                resource_dir
+                    .join("_up_")
+                    .join("src")
+                    .join("instruction_sounds"),
             );
-            send_telegram_document(
-                &client,
-                &bot_token,
-                chat_id,
-                audio_file_path,
-                &truncate_for_telegram(&fallback_caption, 1024),
-            )
-            .await?;
         }
+
+        roots.push(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("..")
+                .join("src")
+                .join("instruction_sounds"),
+        );
     }
 
-    Ok(())
+    roots
 }
 
-#[tauri::command]
-pub async fn get_telegram_chat_id(app_handle: AppHandle) -> Result<String, String> {
-    let conn = init_db(&app_handle)?;
-    if let Some(chat_ids) = stored_telegram_chat_ids(&conn)? {
-        return Ok(chat_ids.into_iter().next().unwrap_or_default());
+/// Where an export puts asset files: embedded as base64 in the legacy JSON
+/// formats, or as raw entries of a `.cefrpkg` package.
+enum AssetSink<'a> {
+    Embedded,
+    Package(&'a mut PackageWriter),
+}
+
+impl AssetSink<'_> {
+    fn export_version(&self) -> i32 {
+        match self {
+            AssetSink::Embedded => LEGACY_EXPORT_VERSION,
+            AssetSink::Package(_) => PACKAGE_EXPORT_VERSION,
+        }
     }
-    Ok(env_telegram_chat_ids()
-        .into_iter()
-        .next()
-        .unwrap_or_default())
 }
 
-#[tauri::command]
-pub async fn set_telegram_chat_id(app_handle: AppHandle, chat_id: String) -> Result<(), String> {
-    let trimmed = chat_id.trim();
+fn read_exported_asset(
+    app_handle: &AppHandle,
+    app_dir: &Path,
+    folder: &str,
+    asset_path: &str,
+    sink: &mut AssetSink,
+) -> Result<Option<ExportedAsset>, String> {
+    let trimmed = asset_path.trim();
     if trimmed.is_empty() {
-        return Err("Telegram chat ID cannot be empty".to_string());
+        return Ok(None);
     }
 
-    let conn = init_db(&app_handle)?;
-    persist_telegram_chat_ids(&conn, &[trimmed.to_string()])?;
+    let data = assets::read(&asset_roots(app_handle, app_dir, folder), trimmed)
+        .map_err(|e| format!("Could not export {} file: {}", folder, e))?;
+
+    let filename = trimmed.to_string();
+    let sha256 = sha256_hex(&data);
+
+    let asset = match sink {
+        AssetSink::Embedded => ExportedAsset {
+            filename,
+            data_base64: general_purpose::STANDARD.encode(data),
+            sha256,
+            entry: None,
+        },
+        AssetSink::Package(writer) => ExportedAsset {
+            entry: Some(writer.add_asset(folder, &sha256, &filename, &data)?),
+            filename,
+            data_base64: String::new(),
+            sha256,
+        },
+    };
 
-    Ok(())
+    Ok(Some(asset))
 }
 
-#[tauri::command]
-pub async fn get_telegram_chat_ids(app_handle: AppHandle) -> Result<Vec<String>, String> {
-    let conn = init_db(&app_handle)?;
-    if let Some(chat_ids) = stored_telegram_chat_ids(&conn)? {
-        return Ok(chat_ids);
-    }
-    Ok(env_telegram_chat_ids())
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
 }
 
-#[tauri::command]
-pub async fn set_telegram_chat_ids(
-    app_handle: AppHandle,
-    chat_ids: Vec<String>,
-) -> Result<Vec<String>, String> {
-    let normalized_chat_ids = normalize_telegram_chat_ids(chat_ids);
-    let conn = init_db(&app_handle)?;
-    persist_telegram_chat_ids(&conn, &normalized_chat_ids)?;
+fn new_content_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
 
-    Ok(normalized_chat_ids)
+/// Where the files of an export being imported come from.
+enum ExportAssets {
+    Embedded,
+    Package(PackageReader),
 }
 
-#[tauri::command]
-pub async fn create_attempt(app_handle: AppHandle, student_name: String) -> Result<i64, String> {
-    let conn = init_db(&app_handle)?;
+/// An imported asset after it has been read from the export and checked.
+struct DecodedAsset {
+    filename: String,
+    data: Vec<u8>,
+    sha256: String,
+}
+
+fn decode_exported_asset(
+    assets: &mut ExportAssets,
+    folder: &str,
+    asset: Option<&ExportedAsset>,
+) -> Result<Option<DecodedAsset>, String> {
+    let Some(asset) = asset else {
+        return Ok(None);
+    };
+
+    let data = match (&asset.entry, assets) {
+        (Some(entry), ExportAssets::Package(reader)) => reader.read_asset(entry)?,
+        (Some(_), ExportAssets::Embedded) => {
+            return Err(format!(
+                "Imported {} file {} is stored in a package, but this file is not a package",
+                folder, asset.filename
+            ));
+        }
+        (None, _) => general_purpose::STANDARD
+            .decode(&asset.data_base64)
+            .map_err(|e| {
+                format!(
+                    "Failed to decode imported {} file {}: {}",
+                    folder, asset.filename, e
+                )
+            })?,
+    };
+    let sha256 = sha256_hex(&data);
+
+    // Exports written before checksums existed have no hash to compare.
+    if !asset.sha256.is_empty() && !asset.sha256.eq_ignore_ascii_case(&sha256) {
+        return Err(format!(
+            "Imported {} file {} is damaged: its checksum does not match the export",
+            folder, asset.filename
+        ));
+    }
+
+    Ok(Some(DecodedAsset {
+        filename: asset.filename.clone(),
+        data,
+        sha256,
+    }))
+}
 
+fn record_asset_hash(
+    conn: &Connection,
+    folder: &str,
+    filename: &str,
+    sha256: &str,
+) -> Result<(), String> {
     conn.execute(
-        "INSERT INTO attempts (student_name) VALUES (?)",
-        params![student_name],
+        "INSERT OR REPLACE INTO asset_hashes (folder, filename, sha256) VALUES (?, ?, ?)",
+        params![folder, filename, sha256],
     )
     .map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-    Ok(conn.last_insert_rowid())
+/// Holds the asset files of one import until its database transaction is
+/// ready to commit. Decoded files are written under `import-staging/{id}` and
+/// only moved into `audio/` and `images/` by `commit`, so a failed import
+/// leaves neither rows nor files behind. Dropping the staging without a
+/// successful commit removes everything it wrote.
+struct AssetStaging {
+    app_dir: PathBuf,
+    staging_dir: PathBuf,
+    staged: Vec<(String, String)>,
+    staged_hashes: HashMap<(String, String), String>,
+    promoted: Vec<PathBuf>,
 }
 
-#[tauri::command]
-pub async fn get_random_questions(
-    app_handle: AppHandle,
-    part: i32,
-    count: i32,
-    exclude_ids: Vec<i64>,
-    sub_part: Option<i32>,
-) -> Result<Vec<Question>, String> {
-    let conn = init_db(&app_handle)?;
-
-    let map_question = |row: &rusqlite::Row| {
-        Ok(Question {
-            id: row.get(0)?,
-            part: row.get(1)?,
-            sub_part: row.get(2)?,
-            audio_path: row.get(3)?,
-            image_path: row.get(4)?,
-            text: row.get(5)?,
-            pack_id: row.get(6)?,
-            pack_order: row.get(7)?,
-            response_time: row.get(8)?,
-            active: row.get(9)?,
+impl AssetStaging {
+    fn new(app_dir: &Path) -> Result<Self, String> {
+        let id: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+        let staging_dir = app_dir.join(IMPORT_STAGING_DIR).join(id);
+        fs::create_dir_all(&staging_dir)
+            .map_err(|e| format!("Failed to create import staging folder: {}", e))?;
+
+        Ok(AssetStaging {
+            app_dir: app_dir.to_path_buf(),
+            staging_dir,
+            staged: Vec::new(),
+            staged_hashes: HashMap::new(),
+            promoted: Vec::new(),
         })
-    };
+    }
 
-    if part == 1 {
-        if let Some(sub_part_value) = sub_part {
-            if sub_part_value == 1 || sub_part_value == 2 {
-                let pack_id: Option<String> = conn
-                    .query_row(
-                        "SELECT pack_id FROM questions
-                         WHERE part = ? AND sub_part = ? AND active = 1 AND pack_id <> ''
-                         GROUP BY pack_id
-                         ORDER BY RANDOM()
-                         LIMIT 1",
-                        params![part, sub_part_value],
-                        |row| row.get(0),
-                    )
-                    .optional()
-                    .map_err(|e| e.to_string())?;
-
-                if let Some(pack_id) = pack_id {
-                    let mut stmt = conn.prepare(
-                        "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active
-                         FROM questions
-                         WHERE part = ? AND sub_part = ? AND active = 1 AND pack_id = ?
-                         ORDER BY pack_order ASC, id ASC",
-                    ).map_err(|e| e.to_string())?;
-
-                    let questions = stmt
-                        .query_map(params![part, sub_part_value, pack_id], map_question)
-                        .map_err(|e| e.to_string())?
-                        .collect::<Result<Vec<_>, _>>()
-                        .map_err(|e| e.to_string())?;
-
-                    if !questions.is_empty() {
-                        return Ok(questions);
-                    }
-                }
-            }
+    /// Returns the filename `asset` will have under `folder` once the import
+    /// commits. A file with the same content that is already stored, or
+    /// already staged by this import, is reused instead of written again.
+    fn stage(
+        &mut self,
+        conn: &Connection,
+        folder: &str,
+        asset: Option<&DecodedAsset>,
+    ) -> Result<String, String> {
+        let Some(asset) = asset else {
+            return Ok(String::new());
+        };
+
+        let hash_key = (folder.to_string(), asset.sha256.clone());
+        if let Some(filename) = self.staged_hashes.get(&hash_key) {
+            return Ok(filename.clone());
         }
-    }
 
-    // Build the query
-    let exclude_clause = if exclude_ids.is_empty() {
-        "".to_string()
-    } else {
-        let ids_str = exclude_ids
-            .iter()
-            .map(|id| id.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
-        format!("AND id NOT IN ({})", ids_str)
-    };
+        let target_dir = self.app_dir.join(folder);
+        if let Some(asset_id) = reuse_stored_asset(conn, &target_dir, folder, &asset.sha256)? {
+            return Ok(asset_id);
+        }
 
-    let sub_part_clause = match sub_part {
-        Some(_) => "AND sub_part = ?".to_string(),
-        None => "".to_string(),
-    };
+        let staged_dir = self.staging_dir.join(folder);
+        fs::create_dir_all(&staged_dir).map_err(|e| e.to_string())?;
 
-    let sql = format!(
-        "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active
-         FROM questions
-         WHERE part = ? AND active = 1 {} {}
-         ORDER BY RANDOM()
-         LIMIT ?",
-        sub_part_clause, exclude_clause
-    );
+        let asset_id = assets::content_id(&asset.sha256, &asset.filename);
+        fs::write(staged_dir.join(&asset_id), &asset.data).map_err(|e| {
+            format!(
+                "Failed to write imported {} file {}: {}",
+                folder, asset.filename, e
+            )
+        })?;
+        record_asset_hash(conn, folder, &asset_id, &asset.sha256)?;
 
-    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        self.staged.push((folder.to_string(), asset_id.clone()));
+        self.staged_hashes.insert(hash_key, asset_id.clone());
+        Ok(asset_id)
+    }
 
-    let question_iter = match sub_part {
-        Some(value) => stmt.query_map(params![part, value, count], map_question),
-        None => stmt.query_map(params![part, count], map_question),
-    };
+    /// Moves the staged files into place and commits `tx`. If either step
+    /// fails the transaction rolls back and the moved files are removed again.
+    fn commit(mut self, tx: Transaction) -> Result<(), String> {
+        let staged = std::mem::take(&mut self.staged);
+        for (folder, filename) in &staged {
+            let target_dir = self.app_dir.join(folder);
+            fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
+
+            // Another import or upload stored the same content meanwhile.
+            let target = target_dir.join(filename);
+            if target.exists() {
+                continue;
+            }
 
-    let questions = question_iter
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+            move_file(&self.staging_dir.join(folder).join(filename), &target)?;
+            self.promoted.push(target);
+        }
 
-    Ok(questions)
+        tx.commit()
+            .map_err(|e| format!("Failed to save import: {}", e))?;
+        self.promoted.clear();
+
+        for (folder, filename) in &staged {
+            if folder == "images" {
+                prepare_thumbnail(&self.app_dir, filename);
+            }
+        }
+        Ok(())
+    }
 }
 
-#[tauri::command]
-pub async fn save_response(
-    app_handle: AppHandle,
-    attempt_id: i64,
-    question_id: i64,
-    audio_data: Vec<u8>,
-    duration: i32,
-) -> Result<String, String> {
-    // Get student name for folder structure
-    let conn = init_db(&app_handle)?;
-    let student_name: String = conn
-        .query_row(
-            "SELECT student_name FROM attempts WHERE id = ?",
-            params![attempt_id],
-            |row| row.get(0),
-        )
+impl Drop for AssetStaging {
+    fn drop(&mut self) {
+        for path in &self.promoted {
+            let _ = fs::remove_file(path);
+        }
+        let _ = fs::remove_dir_all(&self.staging_dir);
+    }
+}
+
+/// Finds the stored file under `folder` whose content hashes to `sha256`,
+/// whatever extension it was saved with.
+fn stored_content_asset(
+    conn: &Connection,
+    target_dir: &Path,
+    folder: &str,
+    sha256: &str,
+) -> Result<Option<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT filename FROM asset_hashes WHERE folder = ? AND sha256 = ?")
         .map_err(|e| e.to_string())?;
-    let (question_part, question_sub_part, question_text, question_image_path): (
-        i32,
-        i32,
-        String,
-        String,
-    ) = conn
-        .query_row(
-            "SELECT part, sub_part, text, image_path FROM questions WHERE id = ?",
-            params![question_id],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
-        )
+    let filenames = stmt
+        .query_map(params![folder, sha256], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
 
-    // Create directory structure: responses/{date}/{student_name}/
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
-    let safe_name = student_name
-        .replace(" ", "_")
-        .replace("/", "_")
-        .replace("\\", "_")
-        .replace(":", "_");
+    Ok(filenames.into_iter().find(|filename| {
+        assets::validate_asset_id(filename).is_ok() && target_dir.join(filename).is_file()
+    }))
+}
 
-    let dir = app_dir.join("responses").join(&date).join(&safe_name);
+/// `stored_content_asset` for content about to be referenced again. The
+/// file's modification time is reset so `collect_unused_assets` does not take
+/// it before the new reference to it is saved.
+fn reuse_stored_asset(
+    conn: &Connection,
+    target_dir: &Path,
+    folder: &str,
+    sha256: &str,
+) -> Result<Option<String>, String> {
+    let Some(asset_id) = stored_content_asset(conn, target_dir, folder, sha256)? else {
+        return Ok(None);
+    };
 
-    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = target_dir.join(&asset_id);
+    fs::File::options()
+        .append(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(std::time::SystemTime::now()))
+        .map_err(|e| format!("Failed to update {:?}: {}", path, e))?;
+    Ok(Some(asset_id))
+}
 
-    // Save audio file
-    let filename = format!("q{}.webm", question_id);
-    let filepath = dir.join(&filename);
-    let filepath_str = filepath.to_string_lossy().to_string();
+/// Saves `data` under `folder` in the content-addressed store and returns its
+/// asset ID. Content that is already stored is reused. New files are written
+/// under the import staging folder first and then moved into place, so a
+/// crash never leaves a partial file under a content ID.
+fn store_asset(
+    conn: &Connection,
+    app_dir: &Path,
+    folder: &str,
+    filename: &str,
+    data: &[u8],
+) -> Result<String, String> {
+    let sha256 = sha256_hex(data);
+    let target_dir = app_dir.join(folder);
+    if let Some(asset_id) = reuse_stored_asset(conn, &target_dir, folder, &sha256)? {
+        return Ok(asset_id);
+    }
 
-    fs::write(&filepath, audio_data).map_err(|e| e.to_string())?;
+    let asset_id = assets::content_id(&sha256, filename);
+    let staging_dir = app_dir.join(IMPORT_STAGING_DIR);
+    fs::create_dir_all(&staging_dir).map_err(|e| e.to_string())?;
+    fs::create_dir_all(&target_dir).map_err(|e| e.to_string())?;
 
-    // Save to database
-    conn.execute(
-        "INSERT INTO responses (attempt_id, question_id, audio_path, duration)
-         VALUES (?, ?, ?, ?)",
-        params![attempt_id, question_id, filepath_str, duration],
-    )
-    .map_err(|e| e.to_string())?;
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(12)
+        .map(char::from)
+        .collect();
+    let partial = staging_dir.join(format!("upload-{}", suffix));
+    fs::write(&partial, data)
+        .map_err(|e| format!("Failed to write {} file {}: {}", folder, filename, e))?;
+    if let Err(error) = move_file(&partial, &target_dir.join(&asset_id)) {
+        let _ = fs::remove_file(&partial);
+        return Err(error);
+    }
 
-    let target_chat_ids = load_telegram_chat_ids(&conn)?;
-    let mut send_errors = Vec::new();
+    record_asset_hash(conn, folder, &asset_id, &sha256)?;
+    Ok(asset_id)
+}
 
-    for target_chat_id in target_chat_ids {
-        if let Err(error) = send_response_to_telegram(
-            &app_dir,
-            &target_chat_id,
-            &student_name,
-            question_id,
-            question_part,
-            question_sub_part,
-            &question_text,
-            &question_image_path,
-            duration,
-            &filepath,
-        )
-        .await
-        {
-            send_errors.push(format!("{} ({})", target_chat_id, error));
+/// Removes staging folders left behind by imports and uploads that were
+/// interrupted before they could clean up, e.g. because the app was closed.
+pub fn remove_stale_import_staging(app_dir: &Path) {
+    let staging_root = app_dir.join(IMPORT_STAGING_DIR);
+    if staging_root.exists() {
+        if let Err(error) = fs::remove_dir_all(&staging_root) {
+            println!(
+                "Warning: failed to remove stale import staging folder {:?}: {}",
+                staging_root, error
+            );
         }
     }
+}
 
-    if !send_errors.is_empty() {
-        return Err(format!(
-            "Response saved locally but failed to send to Telegram for {} chat(s): {}",
-            send_errors.len(),
-            send_errors.join(" | ")
+/// Every rule an exported question breaks, so a preview can report them all
+/// at once. Imports stop at the first one. `incoming` holds the sections of
+/// an exam format the import would set up.
+fn exported_question_problems(
+    conn: &Connection,
+    question: &ExportedQuestion,
+    incoming: &[BlueprintSection],
+) -> Result<Vec<String>, String> {
+    let mut problems = Vec::new();
+
+    if question.response_time <= 0 {
+        problems.push(format!(
+            "Question {} has an invalid response time",
+            question.source_id
         ));
     }
 
-    Ok(filepath_str)
-}
+    let rules = match section_rules(conn, question.part, question.sub_part, incoming) {
+        Ok(rules) => rules,
+        Err(problem) => {
+            problems.push(format!("Question {}: {}", question.source_id, problem));
+            return Ok(problems);
+        }
+    };
 
-#[tauri::command]
-pub async fn finish_attempt(app_handle: AppHandle, attempt_id: i64) -> Result<(), String> {
-    let conn = init_db(&app_handle)?;
+    if rules.image_required && question.image_file.is_none() {
+        problems.push(format!(
+            "Question {} is {} and needs an image file",
+            question.source_id, rules.label
+        ));
+    }
 
-    conn.execute(
-        "UPDATE attempts SET finished_at = CURRENT_TIMESTAMP WHERE id = ?",
-        params![attempt_id],
-    )
-    .map_err(|e| e.to_string())?;
+    if rules.pack_required {
+        if question.pack_id.trim().is_empty() {
+            problems.push(format!(
+                "Question {} needs a test pack ID",
+                question.source_id
+            ));
+        }
+        if question.pack_order <= 0 {
+            problems.push(format!(
+                "Question {} needs a pack order",
+                question.source_id
+            ));
+        }
+    }
 
-    Ok(())
+    if let Err(problem) = rules.prep_time(question.prep_time) {
+        problems.push(format!("Question {}: {}", question.source_id, problem));
+    }
+
+    Ok(problems)
 }
 
-#[tauri::command]
-pub async fn get_attempts(app_handle: AppHandle) -> Result<Vec<Attempt>, String> {
-    let conn = init_db(&app_handle)?;
+fn validate_exported_question(
+    conn: &Connection,
+    question: &ExportedQuestion,
+    incoming: &[BlueprintSection],
+) -> Result<(), String> {
+    match exported_question_problems(conn, question, incoming)?
+        .into_iter()
+        .next()
+    {
+        Some(problem) => Err(problem),
+        None => Ok(()),
+    }
+}
 
-    let mut stmt = conn.prepare(
-        "SELECT id, student_name, started_at, finished_at FROM attempts ORDER BY started_at DESC"
-    ).map_err(|e| e.to_string())?;
+fn validate_exported_full_test_sections(
+    blueprint: &BlueprintInput,
+    questions: &[ExportedQuestion],
+) -> Result<(), String> {
+    let sections_used = questions
+        .iter()
+        .map(|question| (question.part, question.sub_part))
+        .collect::<Vec<_>>();
+    match full_test_section_problems(&blueprint.name, &blueprint.sections, &sections_used)
+        .into_iter()
+        .next()
+    {
+        Some(problem) => Err(problem),
+        None => Ok(()),
+    }
+}
 
-    let attempts = stmt
-        .query_map([], |row| {
-            Ok(Attempt {
-                id: row.get(0)?,
-                student_name: row.get(1)?,
-                started_at: row.get(2)?,
-                finished_at: row.get(3)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+/// The exam format an imported full test is checked against and attached
+/// to. `id` is `None` when the import has to set the format up first.
+struct ImportBlueprint {
+    id: Option<i64>,
+    definition: BlueprintInput,
+}
 
-    Ok(attempts)
+/// Picks the format for an imported full test: an active one with the
+/// exported name, else the exported definition, else the default format for
+/// exports made before tests carried one.
+fn import_blueprint(
+    conn: &Connection,
+    exported: Option<&BlueprintInput>,
+) -> Result<ImportBlueprint, String> {
+    let existing_id = match exported {
+        Some(exported) => conn
+            .query_row(
+                "SELECT id FROM blueprints WHERE name = ? AND active = 1 ORDER BY id LIMIT 1",
+                params![exported.name.trim()],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?,
+        None => Some(default_blueprint_id(conn)?),
+    };
+
+    match (existing_id, exported) {
+        (Some(blueprint_id), _) => {
+            let blueprint = load_blueprint(conn, blueprint_id)?;
+            Ok(ImportBlueprint {
+                id: Some(blueprint_id),
+                definition: BlueprintInput {
+                    name: blueprint.name,
+                    description: blueprint.description,
+                    sections: blueprint.sections,
+                },
+            })
+        }
+        (None, Some(exported)) => Ok(ImportBlueprint {
+            id: None,
+            definition: validated_blueprint(exported.clone())?,
+        }),
+        (None, None) => unreachable!("the default format is looked up above"),
+    }
 }
 
-#[tauri::command]
-pub async fn save_audio_file(
-    app_handle: AppHandle,
-    filename: String,
-    audio_data: Vec<u8>,
-) -> Result<String, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+/// Problems with a full test as a whole. Rules for individual questions are
+/// checked by `exported_question_problems`.
+fn full_test_export_problems(
+    conn: &Connection,
+    import_file: &FullTestExportFile,
+) -> Result<Vec<String>, String> {
+    let mut problems = Vec::new();
 
-    fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+    if import_file.format != "cefr-speaking-full-test" {
+        problems.push("This file is not a CEFR speaking full test export".to_string());
+    }
 
-    let audio_dir = app_dir.join("audio");
-    fs::create_dir_all(&audio_dir).map_err(|e| e.to_string())?;
+    if import_file.name.trim().is_empty() {
+        problems.push("The selected full test export does not have a test name".to_string());
+    }
 
-    let safe_filename = unique_filename(&audio_dir, &filename)?;
-    let audio_file_path = audio_dir.join(&safe_filename);
-    println!(
-        "Saving audio file to: {:?}, size: {} bytes",
-        audio_file_path,
-        audio_data.len()
-    );
+    match import_blueprint(conn, import_file.blueprint.as_ref()) {
+        Err(problem) => problems.push(problem),
+        Ok(_) if import_file.questions.is_empty() => problems
+            .push("The selected full test export does not contain any questions".to_string()),
+        Ok(blueprint) => {
+            if let Err(problem) =
+                validate_exported_full_test_sections(&blueprint.definition, &import_file.questions)
+            {
+                problems.push(problem);
+            }
+        }
+    }
 
-    fs::write(&audio_file_path, &audio_data).map_err(|e| {
-        let err_msg = format!("Failed to write audio file: {}", e);
-        println!("Error: {}", err_msg);
-        err_msg
-    })?;
+    let mut source_ids = HashSet::new();
+    for question in &import_file.questions {
+        if !source_ids.insert(question.source_id) {
+            problems.push(format!(
+                "Question {} appears more than once in the full test export",
+                question.source_id
+            ));
+        }
+    }
 
-    println!("Audio file saved successfully: {}", safe_filename);
-    Ok(safe_filename)
+    Ok(problems)
 }
 
-#[tauri::command]
-pub async fn get_audio_file(app_handle: AppHandle, filename: String) -> Result<Vec<u8>, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    let mut candidates = Vec::new();
-    candidates.push(app_dir.join("audio").join(&filename));
-
-    if let Ok(resource_dir) = app_handle.path().resource_dir() {
-        candidates.push(resource_dir.join(&filename));
-        candidates.push(resource_dir.join("audio").join(&filename));
-        candidates.push(resource_dir.join("instruction_sounds").join(&filename));
-        candidates.push(
-            resource_dir
-                .join("_up_")
-                .join("instruction_sounds")
-                .join(&filename),
-        );
-        candidates.push(
-            resource_dir
-                .join("_up_")
-                .join("src")
-                .join("instruction_sounds")
-                .join(&filename),
-        );
+fn validate_full_test_export_file(
+    conn: &Connection,
+    import_file: &FullTestExportFile,
+) -> Result<ImportBlueprint, String> {
+    if let Some(problem) = full_test_export_problems(conn, import_file)?
+        .into_iter()
+        .next()
+    {
+        return Err(problem);
     }
 
-    let dev_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-        .join("..")
-        .join("src")
-        .join("instruction_sounds")
-        .join(&filename);
-    candidates.push(dev_path);
-
-    for path in candidates {
-        if !path.exists() {
-            continue;
-        }
-        println!("Reading audio file from: {:?}", path);
-        let data = fs::read(&path).map_err(|e| {
-            let err_msg = format!("Failed to read audio file {}: {}", filename, e);
-            println!("Error: {}", err_msg);
-            err_msg
-        })?;
-        println!("Audio file read successfully: {} bytes", data.len());
-        return Ok(data);
+    let blueprint = import_blueprint(conn, import_file.blueprint.as_ref())?;
+    let incoming = incoming_sections(&blueprint);
+    for question in &import_file.questions {
+        validate_exported_question(conn, question, incoming)?;
     }
 
-    let err_msg = format!("Failed to read audio file {}: file not found", filename);
-    println!("Error: {}", err_msg);
-    Err(err_msg)
+    Ok(blueprint)
 }
 
-#[tauri::command]
-pub async fn add_question(
-    app_handle: AppHandle,
-    part: i32,
-    sub_part: Option<i32>,
-    response_time: i32,
-    audio_path: Option<String>,
-    image_path: Option<String>,
-    text: Option<String>,
-    pack_id: Option<String>,
-    pack_order: Option<i32>,
-) -> Result<i64, String> {
-    let conn = init_db(&app_handle)?;
+/// The sections an import would add along with a new exam format.
+fn incoming_sections(blueprint: &ImportBlueprint) -> &[BlueprintSection] {
+    match blueprint.id {
+        Some(_) => &[],
+        None => &blueprint.definition.sections,
+    }
+}
 
-    // Use empty string if no audio path provided
-    let audio = audio_path.unwrap_or_default();
-    let image = image_path.unwrap_or_default();
-    let question_text = if part == 3 {
-        String::new()
-    } else {
-        text.unwrap_or_default()
-    };
-    let sub_part_value = sub_part.unwrap_or(0);
-    let pack_id_value = pack_id.unwrap_or_default();
-    let pack_order_value = pack_order.unwrap_or(0);
+/// State shared by every question and full test in one import: staged
+/// assets, the chosen strategy and what has been created or matched so far.
+struct ImportSession {
+    staging: AssetStaging,
+    assets: ExportAssets,
+    strategy: ImportStrategy,
+    question_ids: HashMap<String, i64>,
+    questions: ImportCounts,
+    tests: ImportCounts,
+}
 
-    if part == 1 && sub_part_value == 2 && image.is_empty() {
-        return Err("Image is required for Part 1.2 questions".to_string());
+impl ImportSession {
+    fn new(app_dir: &Path, strategy: ImportStrategy, assets: ExportAssets) -> Result<Self, String> {
+        Ok(ImportSession {
+            staging: AssetStaging::new(app_dir)?,
+            assets,
+            strategy,
+            question_ids: HashMap::new(),
+            questions: ImportCounts::default(),
+            tests: ImportCounts::default(),
+        })
     }
 
-    if part == 3 && image.is_empty() {
-        return Err("Image is required for Part 3 questions".to_string());
+    fn commit(self, tx: Transaction) -> Result<(ImportCounts, ImportCounts), String> {
+        self.staging.commit(tx)?;
+        Ok((self.tests, self.questions))
     }
+}
 
-    if part == 1 && (sub_part_value == 1 || sub_part_value == 2) {
-        if pack_id_value.trim().is_empty() {
-            return Err("Test pack ID is required for Part 1.1 and Part 1.2 questions".to_string());
+/// Content ID for a question from an export written before IDs existed.
+/// It is derived from the question's content so importing the same old file
+/// twice still matches the first import.
+fn derived_question_uuid(
+    question: &ExportedQuestion,
+    audio_sha256: Option<&str>,
+    image_sha256: Option<&str>,
+) -> String {
+    let fingerprint = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}\n{}\n{}",
+        question.part,
+        question.sub_part,
+        question.text.trim(),
+        question.pack_id.trim(),
+        question.pack_order,
+        question.response_time,
+        audio_sha256.unwrap_or_default(),
+        image_sha256.unwrap_or_default(),
+    );
+    derived_uuid(&fingerprint)
+}
+
+fn derived_uuid(fingerprint: &str) -> String {
+    let digest = Sha256::digest(fingerprint.as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes)
+        .into_uuid()
+        .to_string()
+}
+
+fn content_id_exists(conn: &Connection, table: &str, uuid: &str) -> Result<bool, String> {
+    conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {} WHERE uuid = ?)", table),
+        params![uuid],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Puts back the files of a question the import keeps as it is, where its
+/// own are gone from disk. The imported copies are used for those.
+fn restore_question_assets(
+    conn: &Connection,
+    session: &mut ImportSession,
+    question_id: i64,
+    audio: Option<&DecodedAsset>,
+    image: Option<&DecodedAsset>,
+) -> Result<(), String> {
+    let (audio_path, image_path) = conn
+        .query_row(
+            "SELECT COALESCE(audio_path, ''), COALESCE(image_path, '') FROM questions WHERE id = ?",
+            params![question_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut changed = false;
+    for (column, folder, current, asset) in [
+        ("audio_path", "audio", audio_path, audio),
+        ("image_path", "images", image_path, image),
+    ] {
+        let current = current.trim();
+        let root = session.staging.app_dir.join(folder);
+        if asset.is_none() || current.is_empty() || assets::resolve(&[root], current).is_ok() {
+            continue;
         }
-        if pack_order_value <= 0 {
-            return Err(
-                "Question order is required for Part 1.1 and Part 1.2 questions".to_string(),
-            );
+
+        let asset_id = session.staging.stage(conn, folder, asset)?;
+        if asset_id != current {
+            conn.execute(
+                &format!("UPDATE questions SET {column} = ? WHERE id = ?"),
+                params![asset_id, question_id],
+            )
+            .map_err(|e| e.to_string())?;
+            changed = true;
         }
     }
 
-    conn.execute(
-        "INSERT INTO questions (part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1)",
-        params![part, sub_part_value, audio, image, question_text, pack_id_value, pack_order_value, response_time],
-    ).map_err(|e| e.to_string())?;
-
-    Ok(conn.last_insert_rowid())
+    if changed {
+        record_question_revision(conn, question_id)?;
+    }
+    Ok(())
 }
 
-#[tauri::command]
-pub async fn get_questions(app_handle: AppHandle) -> Result<Vec<Question>, String> {
-    let conn = init_db(&app_handle)?;
+/// Adds, reuses or updates one exported question according to the session
+/// strategy and returns its ID in this bank along with its content ID.
+fn import_exported_question(
+    conn: &Connection,
+    session: &mut ImportSession,
+    question: ExportedQuestion,
+    incoming: &[BlueprintSection],
+) -> Result<(i64, String), String> {
+    validate_exported_question(conn, &question, incoming)?;
+    let prep_time = section_rules(conn, question.part, question.sub_part, incoming)?
+        .prep_time(question.prep_time)?;
+
+    let audio_asset =
+        decode_exported_asset(&mut session.assets, "audio", question.audio_file.as_ref())?;
+    let image_asset =
+        decode_exported_asset(&mut session.assets, "images", question.image_file.as_ref())?;
+    let uuid = if question.uuid.trim().is_empty() {
+        derived_question_uuid(
+            &question,
+            audio_asset.as_ref().map(|asset| asset.sha256.as_str()),
+            image_asset.as_ref().map(|asset| asset.sha256.as_str()),
+        )
+    } else {
+        question.uuid.trim().to_string()
+    };
 
-    let mut stmt = conn.prepare(
-        "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active
-         FROM questions WHERE active = 1 ORDER BY part, sub_part, id"
-    ).map_err(|e| e.to_string())?;
+    // The same question can appear in several tests of one export.
+    if let Some(question_id) = session.question_ids.get(&uuid) {
+        return Ok((*question_id, uuid));
+    }
 
-    let questions = stmt
-        .query_map([], |row| {
-            Ok(Question {
-                id: row.get(0)?,
-                part: row.get(1)?,
-                sub_part: row.get(2)?,
-                audio_path: row.get(3)?,
+    let existing_id = if session.strategy == ImportStrategy::Duplicate {
+        None
+    } else {
+        conn.query_row(
+            "SELECT id FROM questions WHERE uuid = ?",
+            params![uuid],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+    };
+
+    let question_id = match existing_id {
+        Some(question_id) if session.strategy == ImportStrategy::Skip => {
+            // A question deleted here but still shipped by another centre
+            // comes back rather than failing the test it belongs to.
+            conn.execute(
+                "UPDATE questions SET active = 1 WHERE id = ?",
+                params![question_id],
+            )
+            .map_err(|e| e.to_string())?;
+            restore_question_assets(
+                conn,
+                session,
+                question_id,
+                audio_asset.as_ref(),
+                image_asset.as_ref(),
+            )?;
+            session.questions.reused += 1;
+            question_id
+        }
+        Some(question_id) => {
+            let audio = session.staging.stage(conn, "audio", audio_asset.as_ref())?;
+            let image = session
+                .staging
+                .stage(conn, "images", image_asset.as_ref())?;
+            let question_text = if question.part == 3 {
+                String::new()
+            } else {
+                question.text
+            };
+
+            conn.execute(
+                "UPDATE questions
+                 SET part = ?, sub_part = ?, audio_path = ?, image_path = ?, text = ?,
+                     pack_id = ?, pack_order = ?, response_time = ?, prep_time = ?, active = 1
+                 WHERE id = ?",
+                params![
+                    question.part,
+                    question.sub_part,
+                    audio,
+                    image,
+                    question_text,
+                    question.pack_id.trim(),
+                    question.pack_order,
+                    question.response_time,
+                    prep_time,
+                    question_id
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+            record_question_revision(conn, question_id)?;
+            session.questions.updated += 1;
+            question_id
+        }
+        None => {
+            let stored_uuid = if content_id_exists(conn, "questions", &uuid)? {
+                new_content_id()
+            } else {
+                uuid.clone()
+            };
+            let audio = session.staging.stage(conn, "audio", audio_asset.as_ref())?;
+            let image = session
+                .staging
+                .stage(conn, "images", image_asset.as_ref())?;
+            let question_text = if question.part == 3 {
+                String::new()
+            } else {
+                question.text
+            };
+
+            conn.execute(
+                "INSERT INTO questions (uuid, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, prep_time, active)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1)",
+                params![
+                    stored_uuid,
+                    question.part,
+                    question.sub_part,
+                    audio,
+                    image,
+                    question_text,
+                    question.pack_id.trim(),
+                    question.pack_order,
+                    question.response_time,
+                    prep_time
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+            let question_id = conn.last_insert_rowid();
+            record_question_revision(conn, question_id)?;
+            session.questions.created += 1;
+            question_id
+        }
+    };
+
+    session.question_ids.insert(uuid.clone(), question_id);
+    Ok((question_id, uuid))
+}
+
+/// The MIME type of a file, from its content rather than its name.
+fn guess_mime(data: &[u8]) -> &'static str {
+    media::sniff(data).map_or("application/octet-stream", |format| format.mime)
+}
+
+fn convert_audio_to_mp3(source_path: &Path) -> Result<PathBuf, String> {
+    if !source_path.exists() {
+        return Err(format!(
+            "Cannot convert to MP3 because source file does not exist: {:?}",
+            source_path
+        ));
+    }
+
+    let ffmpeg_available = Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+
+    if !ffmpeg_available {
+        return Err(
+            "ffmpeg is not installed or not available in PATH. Install ffmpeg to send MP3 files."
+                .to_string(),
+        );
+    }
+
+    let stem = source_path
+        .file_stem()
+        .and_then(|value| value.to_str())
+        .unwrap_or("response");
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect();
+    let output_path = std::env::temp_dir().join(format!("{}_{}_telegram.mp3", stem, suffix));
+
+    let status = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-i")
+        .arg(source_path)
+        .arg("-vn")
+        .arg("-codec:a")
+        .arg("libmp3lame")
+        .arg("-q:a")
+        .arg("2")
+        .arg(&output_path)
+        .status()
+        .map_err(|e| format!("Failed to run ffmpeg for MP3 conversion: {}", e))?;
+
+    if !status.success() {
+        return Err("ffmpeg failed to convert recording to MP3".to_string());
+    }
+
+    if !output_path.exists() {
+        return Err("MP3 conversion finished but output file was not created".to_string());
+    }
+
+    Ok(output_path)
+}
+
+async fn read_telegram_result(
+    response: reqwest::Response,
+    endpoint: &str,
+) -> Result<Option<serde_json::Value>, TelegramError> {
+    let status = response.status();
+    let body = response
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read Telegram {} response: {}", endpoint, e))?;
+
+    let parsed = serde_json::from_str::<TelegramApiResponse>(&body);
+
+    if !status.is_success() {
+        let retry_after = parsed
+            .ok()
+            .and_then(|parsed| parsed.parameters)
+            .and_then(|parameters| parameters.retry_after);
+        return Err(TelegramError {
+            message: format!(
+                "Telegram {} failed with HTTP {}: {}",
+                endpoint, status, body
+            ),
+            retry_after,
+        });
+    }
+
+    let parsed = parsed.map_err(|e| {
+        format!(
+            "Failed to parse Telegram {} response JSON: {} (body: {})",
+            endpoint, e, body
+        )
+    })?;
+
+    if !parsed.ok {
+        return Err(TelegramError {
+            message: format!(
+                "Telegram {} returned error: {}",
+                endpoint,
+                parsed
+                    .description
+                    .unwrap_or_else(|| "Unknown Telegram error".to_string())
+            ),
+            retry_after: parsed
+                .parameters
+                .and_then(|parameters| parameters.retry_after),
+        });
+    }
+
+    Ok(parsed.result)
+}
+
+async fn parse_telegram_response(
+    response: reqwest::Response,
+    endpoint: &str,
+) -> Result<(), TelegramError> {
+    read_telegram_result(response, endpoint).await.map(|_| ())
+}
+
+async fn fetch_telegram_bot_info(api: &TelegramBotApi) -> Result<TelegramBotInfo, TelegramError> {
+    let response = api
+        .client
+        .get(api.method_url("getMe"))
+        .send()
+        .await
+        .map_err(|e| format!("Failed to call Telegram getMe: {}", e))?;
+
+    let result = read_telegram_result(response, "getMe")
+        .await?
+        .ok_or_else(|| "Telegram getMe returned no bot details".to_string())?;
+
+    Ok(serde_json::from_value(result)
+        .map_err(|e| format!("Failed to parse Telegram getMe result: {}", e))?)
+}
+
+async fn send_telegram_message(
+    api: &TelegramBotApi,
+    chat_id: &str,
+    text: &str,
+) -> Result<(), TelegramError> {
+    let response = api
+        .client
+        .post(api.method_url("sendMessage"))
+        .form(&[("chat_id", chat_id), ("text", text)])
+        .send()
+        .await
+        .map_err(|e| format!("Failed to call Telegram sendMessage: {}", e))?;
+
+    parse_telegram_response(response, "sendMessage").await
+}
+
+async fn send_telegram_photo(
+    api: &TelegramBotApi,
+    chat_id: &str,
+    image_path: &Path,
+    caption: &str,
+) -> Result<(), TelegramError> {
+    let image_data = fs::read(image_path)
+        .map_err(|e| format!("Failed to read image file for Telegram: {}", e))?;
+    let image_name = image_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("prompt_image.bin")
+        .to_string();
+    let mime = guess_mime(&image_data);
+
+    let image_part = Part::bytes(image_data)
+        .file_name(image_name)
+        .mime_str(mime)
+        .map_err(|e| format!("Failed to set Telegram image MIME type: {}", e))?;
+
+    let mut form = Form::new()
+        .text("chat_id", chat_id.to_string())
+        .part("photo", image_part);
+
+    if !caption.trim().is_empty() {
+        form = form.text("caption", caption.to_string());
+    }
+
+    let response = api
+        .client
+        .post(api.method_url("sendPhoto"))
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to call Telegram sendPhoto: {}", e))?;
+
+    parse_telegram_response(response, "sendPhoto").await
+}
+
+async fn send_telegram_document(
+    api: &TelegramBotApi,
+    chat_id: &str,
+    file_path: &Path,
+    caption: &str,
+) -> Result<(), TelegramError> {
+    let file_data =
+        fs::read(file_path).map_err(|e| format!("Failed to read recording for Telegram: {}", e))?;
+    let file_name = file_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("response.webm")
+        .to_string();
+    let mime = guess_mime(&file_data);
+
+    let document_part = Part::bytes(file_data)
+        .file_name(file_name)
+        .mime_str(mime)
+        .map_err(|e| format!("Failed to set Telegram document MIME type: {}", e))?;
+
+    let mut form = Form::new()
+        .text("chat_id", chat_id.to_string())
+        .part("document", document_part);
+
+    if !caption.trim().is_empty() {
+        form = form.text("caption", caption.to_string());
+    }
+
+    let response = api
+        .client
+        .post(api.method_url("sendDocument"))
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to call Telegram sendDocument: {}", e))?;
+
+    parse_telegram_response(response, "sendDocument").await
+}
+
+/// Sends the parts of a delivery that `job.steps_sent` says are still
+/// missing, counting each one as it goes through.
+async fn send_response_to_telegram(
+    api: &TelegramBotApi,
+    app_dir: &Path,
+    job: &mut TelegramDeliveryJob,
+) -> Result<(), TelegramError> {
+    while job.steps_sent < DELIVERY_STEPS {
+        send_telegram_delivery_step(api, app_dir, job, job.steps_sent).await?;
+        job.steps_sent += 1;
+    }
+    Ok(())
+}
+
+/// Sends one part of a delivery: the summary message, the prompt image or
+/// the recording. A prompt image that cannot be found is skipped.
+async fn send_telegram_delivery_step(
+    api: &TelegramBotApi,
+    app_dir: &Path,
+    job: &TelegramDeliveryJob,
+    step: i32,
+) -> Result<(), TelegramError> {
+    let chat_id = job.chat_id.as_str();
+    let student_name = job.student_name.as_str();
+    let question_id = job.question_id;
+    let part_label = job.section_label.as_str();
+
+    match step {
+        DELIVERY_STEP_MESSAGE => {
+            let question = if job.question_text.trim().is_empty() {
+                "(No question text provided)"
+            } else {
+                job.question_text.trim()
+            };
+
+            let mut message = format!(
+                "New CEFR speaking response\nStudent: {}\nSection: {}\nQuestion ID: {}\nQuestion: {}",
+                student_name, part_label, question_id, question
+            );
+            if job.prep_time > 0 {
+                message.push_str(&format!("\nPreparation: {}s", job.prep_time));
+            }
+            message.push_str(&format!("\nDuration: {}s", job.duration));
+
+            send_telegram_message(api, chat_id, &truncate_for_telegram(&message, 4096)).await
+        }
+        DELIVERY_STEP_PHOTO => {
+            if job.image_path.trim().is_empty() {
+                return Ok(());
+            }
+            match resolve_image_path(app_dir, &job.image_path) {
+                Ok(resolved_image_path) => {
+                    let image_caption =
+                        format!("Prompt image for {} (Question {})", part_label, question_id);
+                    send_telegram_photo(
+                        api,
+                        chat_id,
+                        &resolved_image_path,
+                        &truncate_for_telegram(&image_caption, 1024),
+                    )
+                    .await
+                }
+                Err(error) => {
+                    println!(
+                        "Warning: image file configured for question {} cannot be sent: {}",
+                        question_id, error
+                    );
+                    Ok(())
+                }
+            }
+        }
+        DELIVERY_STEP_DOCUMENT => {
+            let audio_file_path = Path::new(&job.audio_path);
+            let mp3_caption = format!(
+                "Answer recording (MP3) | {} | Question {} | Student {}",
+                part_label, question_id, student_name
+            );
+            let fallback_caption = format!(
+                "Answer recording (original format) | {} | Question {} | Student {}",
+                part_label, question_id, student_name
+            );
+
+            match convert_audio_to_mp3(audio_file_path) {
+                Ok(mp3_file_path) => {
+                    let send_result = send_telegram_document(
+                        api,
+                        chat_id,
+                        &mp3_file_path,
+                        &truncate_for_telegram(&mp3_caption, 1024),
+                    )
+                    .await;
+
+                    if let Err(error) = fs::remove_file(&mp3_file_path) {
+                        if error.kind() != std::io::ErrorKind::NotFound {
+                            println!(
+                                "Warning: failed to delete temporary MP3 file {:?}: {}",
+                                mp3_file_path, error
+                            );
+                        }
+                    }
+
+                    send_result
+                }
+                Err(error) => {
+                    println!(
+                        "Warning: MP3 conversion failed. Sending original recording instead. Reason: {}",
+                        error
+                    );
+                    send_telegram_document(
+                        api,
+                        chat_id,
+                        audio_file_path,
+                        &truncate_for_telegram(&fallback_caption, 1024),
+                    )
+                    .await
+                }
+            }
+        }
+        _ => Err(TelegramError::from(format!(
+            "Unknown delivery step {}",
+            step
+        ))),
+    }
+}
+
+struct TelegramDeliveryJob {
+    id: i64,
+    chat_id: String,
+    attempts: i32,
+    student_name: String,
+    question_id: i64,
+    section_label: String,
+    question_text: String,
+    image_path: String,
+    prep_time: i32,
+    duration: i32,
+    audio_path: String,
+    steps_sent: i32,
+}
+
+fn telegram_retry_delay_secs(attempts: i32) -> i64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    DELIVERY_BASE_RETRY_SECS
+        .saturating_mul(2_i64.pow(exponent))
+        .min(DELIVERY_MAX_RETRY_SECS)
+}
+
+fn enqueue_telegram_deliveries(
+    conn: &Connection,
+    response_id: i64,
+    chat_ids: &[String],
+) -> Result<usize, String> {
+    let mut queued = 0;
+    for chat_id in chat_ids {
+        queued += conn
+            .execute(
+                "INSERT OR IGNORE INTO telegram_deliveries (response_id, chat_id, status)
+                 VALUES (?, ?, ?)",
+                params![response_id, chat_id, DELIVERY_STATUS_PENDING],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(queued)
+}
+
+fn reset_interrupted_telegram_deliveries(conn: &Connection) -> Result<(), String> {
+    conn.execute(
+        "UPDATE telegram_deliveries SET status = ? WHERE status = ?",
+        params![DELIVERY_STATUS_PENDING, DELIVERY_STATUS_SENDING],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn claim_next_telegram_delivery(conn: &Connection) -> Result<Option<TelegramDeliveryJob>, String> {
+    loop {
+        let delivery_id: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM telegram_deliveries
+                 WHERE status = ? AND next_attempt_at <= CURRENT_TIMESTAMP
+                 ORDER BY next_attempt_at ASC, id ASC
+                 LIMIT 1",
+                params![DELIVERY_STATUS_PENDING],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+
+        let Some(delivery_id) = delivery_id else {
+            return Ok(None);
+        };
+
+        let claimed = conn
+            .execute(
+                "UPDATE telegram_deliveries SET status = ? WHERE id = ? AND status = ?",
+                params![
+                    DELIVERY_STATUS_SENDING,
+                    delivery_id,
+                    DELIVERY_STATUS_PENDING
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        if claimed == 0 {
+            continue;
+        }
+
+        let job = conn
+            .query_row(
+                "SELECT telegram_deliveries.id,
+                        telegram_deliveries.chat_id,
+                        telegram_deliveries.attempts,
+                        attempts.student_name,
+                        responses.question_id,
+                        COALESCE(question_revisions.part, questions.part),
+                        COALESCE(question_revisions.sub_part, questions.sub_part),
+                        COALESCE(question_revisions.text, questions.text),
+                        COALESCE(question_revisions.image_path, questions.image_path),
+                        responses.duration,
+                        responses.audio_path,
+                        attempts.blueprint_id,
+                        COALESCE(question_revisions.prep_time, questions.prep_time),
+                        telegram_deliveries.steps_sent
+                 FROM telegram_deliveries
+                 JOIN responses ON telegram_deliveries.response_id = responses.id
+                 JOIN attempts ON responses.attempt_id = attempts.id
+                 JOIN questions ON responses.question_id = questions.id
+                 LEFT JOIN question_revisions
+                     ON responses.question_revision_id = question_revisions.id
+                 WHERE telegram_deliveries.id = ?",
+                params![delivery_id],
+                |row| {
+                    let section: (i32, i32, Option<i64>) = (row.get(5)?, row.get(6)?, row.get(11)?);
+                    let job = TelegramDeliveryJob {
+                        id: row.get(0)?,
+                        chat_id: row.get(1)?,
+                        attempts: row.get(2)?,
+                        student_name: row.get(3)?,
+                        question_id: row.get(4)?,
+                        section_label: String::new(),
+                        question_text: row.get(7)?,
+                        image_path: row.get(8)?,
+                        prep_time: row.get(12)?,
+                        duration: row.get(9)?,
+                        audio_path: row.get(10)?,
+                        steps_sent: row.get(13)?,
+                    };
+                    Ok((job, section))
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+
+        match job {
+            Some((mut job, (part, sub_part, blueprint_id))) => {
+                job.section_label = section_label(conn, blueprint_id, part, sub_part)?;
+                return Ok(Some(job));
+            }
+            None => {
+                conn.execute(
+                    "UPDATE telegram_deliveries SET status = ?, last_error = ? WHERE id = ?",
+                    params![
+                        DELIVERY_STATUS_FAILED,
+                        "The response for this delivery no longer exists",
+                        delivery_id
+                    ],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+}
+
+fn record_telegram_delivery_result(
+    conn: &Connection,
+    job: &TelegramDeliveryJob,
+    result: Result<(), TelegramError>,
+) -> Result<(), String> {
+    let error = match result {
+        Ok(()) => {
+            conn.execute(
+                "UPDATE telegram_deliveries
+                 SET status = ?, attempts = attempts + 1, steps_sent = ?, last_error = '',
+                     sent_at = CURRENT_TIMESTAMP
+                 WHERE id = ?",
+                params![DELIVERY_STATUS_SENT, job.steps_sent, job.id],
+            )
+            .map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        Err(error) => error,
+    };
+
+    // Being rate limited is Telegram asking us to wait, not a failed
+    // delivery, so it does not use up an attempt.
+    let attempts = match error.retry_after {
+        Some(_) => job.attempts,
+        None => job.attempts + 1,
+    };
+    if attempts >= DELIVERY_MAX_ATTEMPTS {
+        conn.execute(
+            "UPDATE telegram_deliveries
+             SET status = ?, attempts = ?, steps_sent = ?, last_error = ?
+             WHERE id = ?",
+            params![
+                DELIVERY_STATUS_FAILED,
+                attempts,
+                job.steps_sent,
+                error.message,
+                job.id
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    // Telegram's retry_after is authoritative when it rate-limits us.
+    let delay_secs = match error.retry_after {
+        Some(retry_after) => retry_after.max(1) as i64,
+        None => telegram_retry_delay_secs(attempts),
+    };
+    conn.execute(
+        "UPDATE telegram_deliveries
+         SET status = ?, attempts = ?, steps_sent = ?, last_error = ?,
+             next_attempt_at = datetime('now', '+' || ? || ' seconds')
+         WHERE id = ?",
+        params![
+            DELIVERY_STATUS_PENDING,
+            attempts,
+            job.steps_sent,
+            error.message,
+            delay_secs,
+            job.id
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+async fn process_due_telegram_deliveries(db: &Db, app_dir: &Path) -> Result<(), String> {
+    // Without a token every send would fail, so leave deliveries pending
+    // until an admin configures one.
+    let Some((bot_token, _)) = load_telegram_bot_token(app_dir)? else {
+        return Ok(());
+    };
+    let api_base = db.run(|conn| load_telegram_api_base(conn)).await?;
+    let api = TelegramBotApi::new(&api_base, &bot_token)?;
+
+    loop {
+        let Some(mut job) = db.run(|conn| claim_next_telegram_delivery(conn)).await? else {
+            return Ok(());
+        };
+
+        let result = send_response_to_telegram(&api, app_dir, &mut job).await;
+
+        if let Err(error) = &result {
+            println!(
+                "Warning: Telegram delivery {} to {} failed: {}",
+                job.id, job.chat_id, error
+            );
+        }
+
+        db.run(move |conn| record_telegram_delivery_result(conn, &job, result))
+            .await?;
+    }
+}
+
+/// Starts the background task that drains `telegram_deliveries`. Deliveries
+/// left in `sending` by a previous run are put back in the queue first.
+pub fn start_telegram_delivery_worker(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let db = app_handle.state::<Db>().inner().clone();
+        let app_dir = match app_data_dir(&app_handle) {
+            Ok(app_dir) => app_dir,
+            Err(error) => {
+                println!(
+                    "Warning: Telegram delivery worker could not resolve app data directory: {}",
+                    error
+                );
+                return;
+            }
+        };
+
+        if let Err(error) = db
+            .run(|conn| reset_interrupted_telegram_deliveries(conn))
+            .await
+        {
+            println!(
+                "Warning: failed to reset interrupted Telegram deliveries: {}",
+                error
+            );
+        }
+
+        loop {
+            if let Err(error) = process_due_telegram_deliveries(&db, &app_dir).await {
+                println!("Warning: Telegram delivery worker error: {}", error);
+            }
+
+            let worker = app_handle.state::<TelegramDeliveryWorker>();
+            let _ = tokio::time::timeout(DELIVERY_POLL_INTERVAL, worker.wake.notified()).await;
+        }
+    });
+}
+
+fn telegram_delivery_from_row(row: &rusqlite::Row) -> rusqlite::Result<TelegramDelivery> {
+    Ok(TelegramDelivery {
+        id: row.get(0)?,
+        response_id: row.get(1)?,
+        attempt_id: row.get(2)?,
+        student_name: row.get(3)?,
+        question_id: row.get(4)?,
+        chat_id: row.get(5)?,
+        status: row.get(6)?,
+        attempts: row.get(7)?,
+        last_error: row.get(8)?,
+        next_attempt_at: row.get(9)?,
+        created_at: row.get(10)?,
+        sent_at: row.get(11)?,
+    })
+}
+
+#[tauri::command]
+pub async fn get_database_diagnostics(db: State<'_, Db>) -> Result<DatabaseDiagnostics, String> {
+    let database_path = db.path().to_path_buf();
+    db.run(move |conn| {
+        let database_size_bytes = fs::metadata(&database_path)
+            .map(|metadata| metadata.len())
+            .unwrap_or(0);
+
+        Ok(DatabaseDiagnostics {
+            database_path: database_path.to_string_lossy().to_string(),
+            database_size_bytes,
+            schema_version: migrations::schema_version(conn)?,
+            latest_schema_version: migrations::latest_schema_version(),
+            migrations: migrations::applied_migrations(conn)?,
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_telegram_chat_id(db: State<'_, Db>) -> Result<String, String> {
+    db.run(move |conn| {
+        if let Some(chat_ids) = stored_telegram_chat_ids(conn)? {
+            return Ok(chat_ids.into_iter().next().unwrap_or_default());
+        }
+        Ok(env_telegram_chat_ids()
+            .into_iter()
+            .next()
+            .unwrap_or_default())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_telegram_chat_id(db: State<'_, Db>, chat_id: String) -> Result<(), String> {
+    let trimmed = chat_id.trim().to_string();
+    if trimmed.is_empty() {
+        return Err("Telegram chat ID cannot be empty".to_string());
+    }
+
+    db.run(move |conn| {
+        persist_telegram_chat_ids(conn, &[trimmed])?;
+
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_telegram_chat_ids(db: State<'_, Db>) -> Result<Vec<String>, String> {
+    db.run(move |conn| {
+        if let Some(chat_ids) = stored_telegram_chat_ids(conn)? {
+            return Ok(chat_ids);
+        }
+        Ok(env_telegram_chat_ids())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_telegram_chat_ids(
+    db: State<'_, Db>,
+    chat_ids: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let normalized_chat_ids = normalize_telegram_chat_ids(chat_ids);
+    db.run(move |conn| {
+        persist_telegram_chat_ids(conn, &normalized_chat_ids)?;
+
+        Ok(normalized_chat_ids)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_telegram_bot_token_status(
+    app_handle: AppHandle,
+) -> Result<TelegramBotTokenStatus, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+
+    Ok(match load_telegram_bot_token(&app_dir)? {
+        Some((token, source)) => TelegramBotTokenStatus {
+            configured: true,
+            source: source.to_string(),
+            masked_token: mask_telegram_bot_token(&token),
+        },
+        None => TelegramBotTokenStatus {
+            configured: false,
+            source: "none".to_string(),
+            masked_token: String::new(),
+        },
+    })
+}
+
+#[tauri::command]
+pub async fn set_telegram_bot_token(
+    app_handle: AppHandle,
+    worker: State<'_, TelegramDeliveryWorker>,
+    token: String,
+) -> Result<TelegramBotTokenStatus, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    let trimmed = token.trim();
+
+    if trimmed.is_empty() {
+        persist_telegram_bot_token(&app_dir, None)?;
+    } else {
+        validate_telegram_bot_token_format(trimmed)?;
+        persist_telegram_bot_token(&app_dir, Some(trimmed))?;
+        worker.wake.notify_one();
+    }
+
+    get_telegram_bot_token_status(app_handle).await
+}
+
+#[tauri::command]
+pub async fn validate_telegram_bot_token(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    token: Option<String>,
+) -> Result<TelegramBotInfo, String> {
+    let candidate = token
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty());
+
+    let bot_token = match candidate {
+        Some(value) => value,
+        None => {
+            let app_dir = app_data_dir(&app_handle)?;
+            load_telegram_bot_token(&app_dir)?
+                .map(|(value, _)| value)
+                .ok_or_else(|| "Telegram bot token is not configured".to_string())?
+        }
+    };
+    validate_telegram_bot_token_format(&bot_token)?;
+
+    let api_base = db.run(|conn| load_telegram_api_base(conn)).await?;
+    let api = TelegramBotApi::new(&api_base, &bot_token)?;
+    fetch_telegram_bot_info(&api)
+        .await
+        .map_err(|error| error.to_string())
+}
+
+#[tauri::command]
+pub async fn get_telegram_api_base(db: State<'_, Db>) -> Result<String, String> {
+    db.run(move |conn| load_telegram_api_base(conn)).await
+}
+
+#[tauri::command]
+pub async fn set_telegram_api_base(
+    db: State<'_, Db>,
+    worker: State<'_, TelegramDeliveryWorker>,
+    api_base: String,
+) -> Result<String, String> {
+    let saved = db
+        .run(move |conn| {
+            if api_base.trim().is_empty() {
+                persist_app_setting(conn, TELEGRAM_API_BASE_SETTING_KEY, None)?;
+            } else {
+                let normalized = normalize_telegram_api_base(&api_base)?;
+                persist_app_setting(conn, TELEGRAM_API_BASE_SETTING_KEY, Some(&normalized))?;
+            }
+
+            load_telegram_api_base(conn)
+        })
+        .await?;
+
+    worker.wake.notify_one();
+    Ok(saved)
+}
+
+#[tauri::command]
+pub async fn get_telegram_deliveries(
+    db: State<'_, Db>,
+    status: Option<String>,
+) -> Result<Vec<TelegramDelivery>, String> {
+    db.run(move |conn| {
+        let status_filter = status
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty());
+
+        let status_clause = match status_filter {
+            Some(_) => "WHERE telegram_deliveries.status = ?",
+            None => "",
+        };
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT telegram_deliveries.id,
+                        telegram_deliveries.response_id,
+                        responses.attempt_id,
+                        attempts.student_name,
+                        responses.question_id,
+                        telegram_deliveries.chat_id,
+                        telegram_deliveries.status,
+                        telegram_deliveries.attempts,
+                        telegram_deliveries.last_error,
+                        telegram_deliveries.next_attempt_at,
+                        telegram_deliveries.created_at,
+                        telegram_deliveries.sent_at
+                 FROM telegram_deliveries
+                 JOIN responses ON telegram_deliveries.response_id = responses.id
+                 JOIN attempts ON responses.attempt_id = attempts.id
+                 {}
+                 ORDER BY telegram_deliveries.created_at DESC, telegram_deliveries.id DESC",
+                status_clause
+            ))
+            .map_err(|e| e.to_string())?;
+
+        let delivery_rows = match &status_filter {
+            Some(value) => stmt.query_map(params![value], telegram_delivery_from_row),
+            None => stmt.query_map([], telegram_delivery_from_row),
+        };
+
+        let deliveries = delivery_rows
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        Ok(deliveries)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn retry_telegram_delivery(
+    db: State<'_, Db>,
+    worker: State<'_, TelegramDeliveryWorker>,
+    delivery_id: i64,
+) -> Result<(), String> {
+    db.run(move |conn| {
+        let updated = conn
+            .execute(
+                "UPDATE telegram_deliveries
+                 SET status = ?, attempts = 0, next_attempt_at = CURRENT_TIMESTAMP
+                 WHERE id = ? AND status IN (?, ?, ?)",
+                params![
+                    DELIVERY_STATUS_PENDING,
+                    delivery_id,
+                    DELIVERY_STATUS_PENDING,
+                    DELIVERY_STATUS_FAILED,
+                    DELIVERY_STATUS_CANCELLED
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+
+        if updated == 0 {
+            return Err(format!(
+                "Telegram delivery {} does not exist or cannot be retried right now",
+                delivery_id
+            ));
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    worker.wake.notify_one();
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn retry_failed_telegram_deliveries(
+    db: State<'_, Db>,
+    worker: State<'_, TelegramDeliveryWorker>,
+) -> Result<usize, String> {
+    let updated = db
+        .run(move |conn| {
+            conn.execute(
+                "UPDATE telegram_deliveries
+                 SET status = ?, attempts = 0, next_attempt_at = CURRENT_TIMESTAMP
+                 WHERE status = ?",
+                params![DELIVERY_STATUS_PENDING, DELIVERY_STATUS_FAILED],
+            )
+            .map_err(|e| e.to_string())
+        })
+        .await?;
+
+    if updated > 0 {
+        worker.wake.notify_one();
+    }
+    Ok(updated)
+}
+
+#[tauri::command]
+pub async fn cancel_telegram_delivery(db: State<'_, Db>, delivery_id: i64) -> Result<(), String> {
+    db.run(move |conn| {
+        let updated = conn
+            .execute(
+                "UPDATE telegram_deliveries SET status = ? WHERE id = ? AND status IN (?, ?)",
+                params![
+                    DELIVERY_STATUS_CANCELLED,
+                    delivery_id,
+                    DELIVERY_STATUS_PENDING,
+                    DELIVERY_STATUS_FAILED
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+
+        if updated == 0 {
+            return Err(format!(
+                "Telegram delivery {} does not exist or has already been sent",
+                delivery_id
+            ));
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn create_attempt(
+    db: State<'_, Db>,
+    student_name: String,
+    mode: Option<String>,
+    full_test_id: Option<i64>,
+    blueprint_id: Option<i64>,
+    seed: Option<i64>,
+) -> Result<i64, String> {
+    let mode = match mode.as_deref().map(str::trim) {
+        Some(mode) if !mode.is_empty() => mode.to_string(),
+        _ if full_test_id.is_some() => ATTEMPT_MODE_FULL_TEST.to_string(),
+        _ => ATTEMPT_MODE_RANDOM.to_string(),
+    };
+    match (mode.as_str(), full_test_id) {
+        (ATTEMPT_MODE_RANDOM, None) | (ATTEMPT_MODE_FULL_TEST, Some(_)) => {}
+        (ATTEMPT_MODE_RANDOM, Some(_)) => {
+            return Err("A random exam cannot be started from a full test".to_string());
+        }
+        (ATTEMPT_MODE_FULL_TEST, None) => {
+            return Err("Choose the full test this exam is taken from".to_string());
+        }
+        _ => return Err(format!("Unknown exam mode: {}", mode)),
+    }
+    // A random exam is always drawn from a seed; one given here is a paper
+    // code shared with other candidates.
+    let (selection_seed, shared_seed) = match (mode.as_str(), seed) {
+        (ATTEMPT_MODE_FULL_TEST, Some(_)) => {
+            return Err("A full test is fixed and cannot take a paper code".to_string());
+        }
+        (ATTEMPT_MODE_FULL_TEST, None) => (None, false),
+        (_, Some(seed)) => (Some(validated_selection_seed(seed)?), true),
+        (_, None) => (Some(random_selection_seed()), false),
+    };
+
+    db.run(move |conn| {
+        // A full test is always taken in the format it was laid out for.
+        let blueprint_id = match full_test_id {
+            Some(full_test_id) => {
+                full_test_name(conn, full_test_id)?;
+                let test_blueprint_id = full_test_blueprint_id(conn, full_test_id)?;
+                if blueprint_id.is_some_and(|id| id != test_blueprint_id) {
+                    return Err(
+                        "This full test is laid out for a different exam format".to_string()
+                    );
+                }
+                test_blueprint_id
+            }
+            None => resolve_blueprint_id(conn, blueprint_id)?,
+        };
+
+        conn.execute(
+            "INSERT INTO attempts
+                 (student_name, mode, full_test_id, blueprint_id, selection_seed, shared_seed)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                student_name,
+                mode,
+                full_test_id,
+                blueprint_id,
+                selection_seed,
+                shared_seed
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(conn.last_insert_rowid())
+    })
+    .await
+}
+
+/// Records that a question is on screen for the candidate. Each question is
+/// served once per attempt; serving it again keeps its first position and
+/// time. Returns the question's position in the attempt.
+#[tauri::command]
+pub async fn serve_question(
+    db: State<'_, Db>,
+    attempt_id: i64,
+    question_id: i64,
+) -> Result<i32, String> {
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+        mark_attempt_in_progress(&tx, attempt_id)?;
+        let (position, _) = record_served_question(&tx, attempt_id, question_id)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(position)
+    })
+    .await
+}
+
+/// Appends a question to the attempt's served list unless it is already
+/// there. Returns its position and the revision the candidate was shown.
+fn record_served_question(
+    conn: &Connection,
+    attempt_id: i64,
+    question_id: i64,
+) -> Result<(i32, i64), String> {
+    let existing: Option<(i32, Option<i64>)> = conn
+        .query_row(
+            "SELECT position, question_revision_id FROM attempt_questions
+             WHERE attempt_id = ? AND question_id = ?",
+            params![attempt_id, question_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    match existing {
+        Some((position, Some(question_revision_id))) => {
+            return Ok((position, question_revision_id));
+        }
+        Some((position, None)) => {
+            return Ok((position, record_question_revision(conn, question_id)?));
+        }
+        None => {}
+    }
+
+    let question_revision_id = record_question_revision(conn, question_id)?;
+    let position: i32 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(position), 0) + 1 FROM attempt_questions WHERE attempt_id = ?",
+            params![attempt_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO attempt_questions (attempt_id, position, question_id, question_revision_id)
+         VALUES (?, ?, ?, ?)",
+        params![attempt_id, position, question_id, question_revision_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok((position, question_revision_id))
+}
+
+/// Lists the questions served in an attempt, with the wording shown and how
+/// many responses were saved for each.
+#[tauri::command]
+pub async fn get_attempt_questions(
+    db: State<'_, Db>,
+    attempt_id: i64,
+) -> Result<Vec<ServedQuestion>, String> {
+    db.run(move |conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT attempt_questions.position,
+                        attempt_questions.question_id,
+                        attempt_questions.question_revision_id,
+                        COALESCE(question_revisions.part, questions.part),
+                        COALESCE(question_revisions.sub_part, questions.sub_part),
+                        COALESCE(question_revisions.text, questions.text),
+                        COALESCE(attempt_questions.served_at, ''),
+                        (SELECT COUNT(*) FROM responses
+                         WHERE responses.attempt_id = attempt_questions.attempt_id
+                           AND responses.question_id = attempt_questions.question_id)
+                 FROM attempt_questions
+                 JOIN questions ON attempt_questions.question_id = questions.id
+                 LEFT JOIN question_revisions
+                     ON attempt_questions.question_revision_id = question_revisions.id
+                 WHERE attempt_questions.attempt_id = ?
+                 ORDER BY attempt_questions.position",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let served = stmt
+            .query_map(params![attempt_id], |row| {
+                Ok(ServedQuestion {
+                    position: row.get(0)?,
+                    question_id: row.get(1)?,
+                    question_revision_id: row.get(2)?,
+                    part: row.get(3)?,
+                    sub_part: row.get(4)?,
+                    question_text: row.get(5)?,
+                    served_at: row.get(6)?,
+                    response_count: row.get(7)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        Ok(served)
+    })
+    .await
+}
+
+/// Draws questions for one section. When `attempt_id` is given, packs and
+/// questions its candidate has not seen come first, then the ones they saw
+/// longest ago, so a small bank still fills the section. Ties go to what has
+/// been shown least across all candidates, then to the attempt's seed.
+///
+/// A `seed` passed here, or a paper code the attempt was started with, draws
+/// from the seed and the bank alone: everyone with the same seed gets the
+/// same questions.
+///
+/// What an attempt draws is kept with it. Asking again for a section it has
+/// already drawn, when it is resumed or looked into later, gives back the
+/// same questions in the same order, less `exclude_ids`, whatever the bank
+/// and other candidates have done since.
+#[tauri::command]
+pub async fn get_random_questions(
+    db: State<'_, Db>,
+    draw: QuestionDraw,
+) -> Result<Vec<Question>, String> {
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let section_sub_part = draw.sub_part.unwrap_or(0);
+
+        let questions = match draw.attempt_id {
+            Some(attempt_id) => match attempt_draw(&tx, attempt_id, draw.part, section_sub_part)? {
+                Some(drawn) => drawn
+                    .into_iter()
+                    .filter(|question| !draw.exclude_ids.contains(&question.id))
+                    .take(draw.count.max(0) as usize)
+                    .collect(),
+                None => {
+                    let questions = draw_questions(&tx, &draw)?;
+                    record_attempt_draw(&tx, attempt_id, draw.part, section_sub_part, &questions)?;
+                    questions
+                }
+            },
+            None => draw_questions(&tx, &draw)?,
+        };
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(questions)
+    })
+    .await
+}
+
+/// The questions an attempt drew for a section, in the order they were
+/// drawn, or `None` when it has not drawn that section yet.
+fn attempt_draw(
+    conn: &Connection,
+    attempt_id: i64,
+    part: i32,
+    sub_part: i32,
+) -> Result<Option<Vec<Question>>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT questions.id, questions.part, questions.sub_part, questions.audio_path,
+                    questions.image_path, questions.text, questions.pack_id, questions.pack_order,
+                    questions.response_time, questions.active, questions.prep_time
+             FROM attempt_draws
+             JOIN questions ON attempt_draws.question_id = questions.id
+             WHERE attempt_draws.attempt_id = ?
+               AND attempt_draws.part = ?
+               AND attempt_draws.sub_part = ?
+             ORDER BY attempt_draws.position",
+        )
+        .map_err(|e| e.to_string())?;
+    let questions = stmt
+        .query_map(params![attempt_id, part, sub_part], question_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok((!questions.is_empty()).then_some(questions))
+}
+
+fn record_attempt_draw(
+    conn: &Connection,
+    attempt_id: i64,
+    part: i32,
+    sub_part: i32,
+    questions: &[Question],
+) -> Result<(), String> {
+    for (position, question) in questions.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO attempt_draws (attempt_id, part, sub_part, position, question_id)
+             VALUES (?, ?, ?, ?, ?)",
+            params![attempt_id, part, sub_part, position as i64 + 1, question.id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn draw_questions(conn: &Connection, draw: &QuestionDraw) -> Result<Vec<Question>, String> {
+    let QuestionDraw {
+        part,
+        sub_part,
+        count,
+        ref exclude_ids,
+        blueprint_id,
+        attempt_id,
+        seed,
+    } = *draw;
+    let blueprint_id = resolve_blueprint_id(conn, blueprint_id)?;
+    let section_sub_part = sub_part.unwrap_or(0);
+    let section = blueprint_sections(conn, blueprint_id)?
+        .into_iter()
+        .find(|section| section.part == part && section.sub_part == section_sub_part)
+        .ok_or_else(|| {
+            format!(
+                "{} is not part of this exam format",
+                fallback_section_label(part, section_sub_part)
+            )
+        })?;
+
+    let (seed, history) = match seed {
+        Some(seed) => (validated_selection_seed(seed)?, false),
+        None => match attempt_id
+            .map(|attempt_id| attempt_selection_seed(conn, attempt_id))
+            .transpose()?
+            .flatten()
+        {
+            Some((seed, shared)) => (seed, !shared),
+            None => (random_selection_seed(), true),
+        },
+    };
+    let history_attempt_id = attempt_id.filter(|_| history);
+    let lookback_days = load_exposure_lookback_days(conn)?;
+    let scope = format!("{}.{}", part, section_sub_part);
+
+    if section.selection == SECTION_SELECTION_PACK {
+        let sql = format!(
+            "{}
+             SELECT questions.pack_id,
+                    MAX(seen.last_seen),
+                    COUNT(DISTINCT exposures.attempt_id)
+             FROM questions
+             LEFT JOIN seen ON seen.question_id = questions.id
+             LEFT JOIN exposures ON exposures.question_id = questions.id
+             WHERE questions.part = ?4 AND questions.sub_part = ?5 AND questions.active = 1
+               AND questions.pack_id <> ''
+             GROUP BY questions.pack_id",
+            EXPOSURE_CTES
+        );
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let packs = stmt
+            .query_map(
+                params![
+                    history_attempt_id,
+                    lookback_days,
+                    history,
+                    part,
+                    section_sub_part
+                ],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, Option<String>>(1)?,
+                        row.get::<_, i64>(2)?,
+                    ))
+                },
+            )
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let pack_id = packs
+            .into_iter()
+            .min_by_key(|(pack_id, last_seen, uses)| {
+                (last_seen.clone(), *uses, seeded_rank(seed, &scope, pack_id))
+            })
+            .map(|(pack_id, _, _)| pack_id);
+
+        if let Some(pack_id) = pack_id {
+            let mut stmt = conn.prepare(
+                "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active, prep_time
+                 FROM questions
+                 WHERE part = ? AND sub_part = ? AND active = 1 AND pack_id = ?
+                 ORDER BY pack_order ASC, id ASC",
+            ).map_err(|e| e.to_string())?;
+
+            let questions = stmt
+                .query_map(params![part, section_sub_part, pack_id], question_from_row)
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+
+            if !questions.is_empty() {
+                return Ok(questions);
+            }
+        }
+    }
+
+    // Build the query
+    let exclude_clause = if exclude_ids.is_empty() {
+        "".to_string()
+    } else {
+        let ids_str = exclude_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("AND questions.id NOT IN ({})", ids_str)
+    };
+
+    let sql = format!(
+        "{}
+         SELECT questions.id, questions.part, questions.sub_part, questions.audio_path,
+                questions.image_path, questions.text, questions.pack_id, questions.pack_order,
+                questions.response_time, questions.active, questions.prep_time,
+                COALESCE(questions.uuid, CAST(questions.id AS TEXT)),
+                seen.last_seen,
+                COALESCE(usage.uses, 0)
+         FROM questions
+         LEFT JOIN seen ON seen.question_id = questions.id
+         LEFT JOIN (
+             SELECT question_id, COUNT(*) AS uses FROM exposures GROUP BY question_id
+         ) AS usage ON usage.question_id = questions.id
+         WHERE questions.part = ?4 AND questions.active = 1
+           AND (?5 IS NULL OR questions.sub_part = ?5) {}",
+        EXPOSURE_CTES, exclude_clause
+    );
+
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let mut candidates = stmt
+        .query_map(
+            params![history_attempt_id, lookback_days, history, part, sub_part],
+            |row| {
+                Ok((
+                    question_from_row(row)?,
+                    row.get::<_, String>(11)?,
+                    row.get::<_, Option<String>>(12)?,
+                    row.get::<_, i64>(13)?,
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    candidates.sort_by_cached_key(|(_, uuid, last_seen, uses)| {
+        (last_seen.clone(), *uses, seeded_rank(seed, &scope, uuid))
+    });
+    let questions = candidates
+        .into_iter()
+        .take(count.max(0) as usize)
+        .map(|(question, _, _, _)| question)
+        .collect();
+
+    Ok(questions)
+}
+
+/// How many days back a candidate's earlier attempts count when choosing
+/// questions they have not seen. `0` ignores their history.
+#[tauri::command]
+pub async fn get_exposure_lookback_days(db: State<'_, Db>) -> Result<i64, String> {
+    db.run(move |conn| load_exposure_lookback_days(conn)).await
+}
+
+#[tauri::command]
+pub async fn set_exposure_lookback_days(db: State<'_, Db>, days: i64) -> Result<i64, String> {
+    if !(0..=MAX_EXPOSURE_LOOKBACK_DAYS).contains(&days) {
+        return Err(format!(
+            "Lookback must be between 0 and {} days",
+            MAX_EXPOSURE_LOOKBACK_DAYS
+        ));
+    }
+
+    db.run(move |conn| {
+        persist_app_setting(
+            conn,
+            EXPOSURE_LOOKBACK_DAYS_SETTING_KEY,
+            Some(&days.to_string()),
+        )?;
+        load_exposure_lookback_days(conn)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn save_response(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    worker: State<'_, TelegramDeliveryWorker>,
+    attempt_id: i64,
+    question_id: i64,
+    audio_data: Vec<u8>,
+    duration: i32,
+) -> Result<String, String> {
+    if audio_data.is_empty() {
+        return Err("The recording is empty".to_string());
+    }
+    if duration < 0 {
+        return Err("Response duration cannot be negative".to_string());
+    }
+
+    let app_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    // Get student name and start time for folder structure
+    let (filepath_str, queued) = db
+        .run(move |conn| {
+            // The status check and the insert share one transaction, and the
+            // recording only takes its real name once they have gone through,
+            // so a rejected or failed save leaves neither the row nor the file.
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            let (student_name, started_at): (String, String) = tx
+                .query_row(
+                    "SELECT student_name, started_at FROM attempts WHERE id = ?",
+                    params![attempt_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(|e| e.to_string())?;
+
+            // Create directory structure: responses/{date}/attempt_{id}_{student_name}/
+            let dir = attempt_response_dir(&app_dir, attempt_id, &started_at, &student_name);
+
+            fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+            // Save audio file; a re-recorded question gets a suffixed name instead of
+            // overwriting the earlier take.
+            let filename = unique_filename(&dir, &format!("q{}.webm", question_id))?;
+            let filepath = dir.join(&filename);
+            let filepath_str = filepath.to_string_lossy().to_string();
+            let partial_path = dir.join(format!("{}.partial", filename));
+
+            let written = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&partial_path)
+                .and_then(|mut file| std::io::Write::write_all(&mut file, &audio_data));
+            if let Err(error) = written {
+                let _ = fs::remove_file(&partial_path);
+                return Err(error.to_string());
+            }
+
+            let saved = insert_response(
+                &tx,
+                attempt_id,
+                question_id,
+                &audio_data,
+                duration,
+                &filepath_str,
+            );
+            let queued = match saved {
+                Ok(queued) => queued,
+                Err(error) => {
+                    let _ = fs::remove_file(&partial_path);
+                    return Err(error);
+                }
+            };
+            if let Err(error) = fs::rename(&partial_path, &filepath) {
+                let _ = fs::remove_file(&partial_path);
+                return Err(error.to_string());
+            }
+            if let Err(error) = tx.commit() {
+                let _ = fs::remove_file(&filepath);
+                return Err(error.to_string());
+            }
+
+            Ok((filepath_str, queued))
+        })
+        .await?;
+
+    if queued > 0 {
+        worker.wake.notify_one();
+    }
+    Ok(filepath_str)
+}
+
+/// Records a response whose recording is saved at `filepath`, once the
+/// attempt is known to take answers, and queues it for Telegram. Returns how
+/// many deliveries were queued.
+fn insert_response(
+    conn: &Connection,
+    attempt_id: i64,
+    question_id: i64,
+    audio_data: &[u8],
+    duration: i32,
+    filepath: &str,
+) -> Result<usize, String> {
+    mark_attempt_in_progress(conn, attempt_id)?;
+
+    // Save to database, pinned to the wording the candidate was shown. A
+    // question answered without being served first still joins the list.
+    let (_, question_revision_id) = record_served_question(conn, attempt_id, question_id)?;
+    let (response_time, prep_time, served_at): (i32, i32, Option<String>) = conn
+        .query_row(
+            "SELECT question_revisions.response_time,
+                    question_revisions.prep_time,
+                    attempt_questions.served_at
+             FROM question_revisions
+             LEFT JOIN attempt_questions
+               ON attempt_questions.attempt_id = ?
+              AND attempt_questions.question_id = question_revisions.question_id
+             WHERE question_revisions.id = ?",
+            params![attempt_id, question_revision_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| e.to_string())?;
+    // `served_at` is SQLite's CURRENT_TIMESTAMP, in UTC.
+    let served_at = served_at
+        .and_then(|value| chrono::NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S").ok());
+    let timing = check_response_timing(
+        audio_data,
+        duration,
+        response_time,
+        prep_time,
+        served_at,
+        chrono::Utc::now().naive_utc(),
+    );
+    conn.execute(
+        "INSERT INTO responses (attempt_id, question_id, question_revision_id, audio_path, duration,
+                                claimed_duration, audio_duration_ms, timing_flags)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        params![
+            attempt_id,
+            question_id,
+            question_revision_id,
+            filepath,
+            timing.duration,
+            duration,
+            timing.audio_duration_ms,
+            timing.flags.join(",")
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let response_id = conn.last_insert_rowid();
+
+    // Telegram delivery happens in the background worker so a slow or offline
+    // network never blocks the exam flow.
+    let queued = match load_telegram_chat_ids(conn) {
+        Ok(target_chat_ids) => enqueue_telegram_deliveries(conn, response_id, &target_chat_ids)?,
+        Err(error) => {
+            println!(
+                "Warning: response {} was not queued for Telegram: {}",
+                response_id, error
+            );
+            0
+        }
+    };
+    Ok(queued)
+}
+
+struct ResponseTiming {
+    /// Whole seconds of audio, or the claimed duration if the audio could
+    /// not be measured.
+    duration: i32,
+    audio_duration_ms: Option<i64>,
+    flags: Vec<&'static str>,
+}
+
+/// Measures a recording and checks it against the duration reported by the
+/// exam screen and the question's response time, and checks that it was
+/// answered within the question's preparation and response time of being
+/// served. Problems are flagged for the examiner instead of rejecting the
+/// response, which would lose the answer.
+fn check_response_timing(
+    audio: &[u8],
+    claimed: i32,
+    response_time: i32,
+    prep_time: i32,
+    served_at: Option<chrono::NaiveDateTime>,
+    answered_at: chrono::NaiveDateTime,
+) -> ResponseTiming {
+    let mut flags = Vec::new();
+    let audio_duration_ms = match webm::duration_ms(audio) {
+        Ok(duration_ms) => Some(duration_ms as i64),
+        Err(error) => {
+            println!("Warning: could not measure recording: {}", error);
+            flags.push(TIMING_FLAG_UNREADABLE_AUDIO);
+            None
+        }
+    };
+
+    let claimed_ms = i64::from(claimed) * 1000;
+    if let Some(audio_ms) = audio_duration_ms {
+        if (audio_ms - claimed_ms).abs() > DURATION_MISMATCH_TOLERANCE_SECONDS * 1000 {
+            flags.push(TIMING_FLAG_DURATION_MISMATCH);
+        }
+    }
+    let limit_ms = (i64::from(response_time) + RESPONSE_TIME_GRACE_SECONDS) * 1000;
+    if audio_duration_ms.unwrap_or(claimed_ms).max(claimed_ms) > limit_ms {
+        flags.push(TIMING_FLAG_OVER_TIME_LIMIT);
+    }
+    if let Some(served_at) = served_at {
+        let allowed_secs = i64::from(prep_time)
+            + i64::from(response_time)
+            + RESPONSE_TIME_GRACE_SECONDS
+            + SERVED_TIME_ALLOWANCE_SECONDS;
+        if (answered_at - served_at).num_seconds() > allowed_secs {
+            flags.push(TIMING_FLAG_ANSWERED_LATE);
+        }
+    }
+
+    ResponseTiming {
+        duration: audio_duration_ms
+            .map(|audio_ms| ((audio_ms + 500) / 1000).min(i64::from(i32::MAX)) as i32)
+            .unwrap_or(claimed),
+        audio_duration_ms,
+        flags,
+    }
+}
+
+/// The states an attempt may move to from `status`. Finished and abandoned
+/// attempts can still be voided; a voided attempt is final.
+fn attempt_status_transitions(status: &str) -> &'static [&'static str] {
+    match status {
+        ATTEMPT_STATUS_CREATED => &[
+            ATTEMPT_STATUS_IN_PROGRESS,
+            ATTEMPT_STATUS_PAUSED,
+            ATTEMPT_STATUS_FINISHED,
+            ATTEMPT_STATUS_ABANDONED,
+            ATTEMPT_STATUS_VOIDED,
+        ],
+        ATTEMPT_STATUS_IN_PROGRESS => &[
+            ATTEMPT_STATUS_PAUSED,
+            ATTEMPT_STATUS_FINISHED,
+            ATTEMPT_STATUS_ABANDONED,
+            ATTEMPT_STATUS_VOIDED,
+        ],
+        ATTEMPT_STATUS_PAUSED => &[
+            ATTEMPT_STATUS_IN_PROGRESS,
+            ATTEMPT_STATUS_ABANDONED,
+            ATTEMPT_STATUS_VOIDED,
+        ],
+        ATTEMPT_STATUS_FINISHED | ATTEMPT_STATUS_ABANDONED => &[ATTEMPT_STATUS_VOIDED],
+        _ => &[],
+    }
+}
+
+fn attempt_status(conn: &Connection, attempt_id: i64) -> Result<String, String> {
+    conn.query_row(
+        "SELECT status FROM attempts WHERE id = ?",
+        params![attempt_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Attempt {} was not found", attempt_id))
+}
+
+/// Explains why an attempt in `status` cannot take the requested action.
+fn attempt_status_error(attempt_id: i64, status: &str, action: &str) -> String {
+    match status {
+        ATTEMPT_STATUS_PAUSED => format!("Attempt {} is paused; resume it first", attempt_id),
+        ATTEMPT_STATUS_FINISHED => format!("Attempt {} is already finished", attempt_id),
+        ATTEMPT_STATUS_ABANDONED => format!("Attempt {} was abandoned", attempt_id),
+        ATTEMPT_STATUS_VOIDED => format!("Attempt {} was voided", attempt_id),
+        _ => format!(
+            "Attempt {} is {} and cannot be {}",
+            attempt_id,
+            status.replace('_', " "),
+            action
+        ),
+    }
+}
+
+/// Moves an attempt to `to`, refusing moves the lifecycle does not allow.
+/// Finishing also stamps `finished_at`.
+fn transition_attempt(conn: &Connection, attempt_id: i64, to: &str) -> Result<(), String> {
+    let from = attempt_status(conn, attempt_id)?;
+    if !attempt_status_transitions(&from).contains(&to) {
+        let action = match to {
+            ATTEMPT_STATUS_IN_PROGRESS => "resumed",
+            other => other,
+        };
+        return Err(attempt_status_error(attempt_id, &from, action));
+    }
+
+    let sql = if to == ATTEMPT_STATUS_FINISHED {
+        "UPDATE attempts SET status = ?, finished_at = CURRENT_TIMESTAMP WHERE id = ?"
+    } else {
+        "UPDATE attempts SET status = ? WHERE id = ?"
+    };
+    conn.execute(sql, params![to, attempt_id])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Questions can only be served and answered while the attempt is running.
+/// The first one moves a new attempt into progress; a paused attempt has to
+/// be resumed before the candidate carries on.
+fn mark_attempt_in_progress(conn: &Connection, attempt_id: i64) -> Result<(), String> {
+    match attempt_status(conn, attempt_id)?.as_str() {
+        ATTEMPT_STATUS_IN_PROGRESS => Ok(()),
+        ATTEMPT_STATUS_CREATED => transition_attempt(conn, attempt_id, ATTEMPT_STATUS_IN_PROGRESS),
+        status => Err(attempt_status_error(attempt_id, status, "answered")),
+    }
+}
+
+async fn change_attempt_status(db: &Db, attempt_id: i64, to: &'static str) -> Result<(), String> {
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        transition_attempt(&tx, attempt_id, to)?;
+        tx.commit().map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn finish_attempt(db: State<'_, Db>, attempt_id: i64) -> Result<(), String> {
+    change_attempt_status(&db, attempt_id, ATTEMPT_STATUS_FINISHED).await
+}
+
+/// Stops an attempt part-way so it can be resumed later.
+#[tauri::command]
+pub async fn pause_attempt(db: State<'_, Db>, attempt_id: i64) -> Result<(), String> {
+    change_attempt_status(&db, attempt_id, ATTEMPT_STATUS_PAUSED).await
+}
+
+/// Closes an attempt that will not be completed. Its responses are kept.
+#[tauri::command]
+pub async fn abandon_attempt(db: State<'_, Db>, attempt_id: i64) -> Result<(), String> {
+    change_attempt_status(&db, attempt_id, ATTEMPT_STATUS_ABANDONED).await
+}
+
+/// Marks an attempt as not counting, for example after an irregularity.
+/// Its responses are kept for reference.
+#[tauri::command]
+pub async fn void_attempt(db: State<'_, Db>, attempt_id: i64) -> Result<(), String> {
+    change_attempt_status(&db, attempt_id, ATTEMPT_STATUS_VOIDED).await
+}
+
+/// Picks an interrupted attempt back up, whether it was paused or the app
+/// closed mid-exam, and returns what is left for the candidate to answer.
+#[tauri::command]
+pub async fn resume_attempt(db: State<'_, Db>, attempt_id: i64) -> Result<ResumedAttempt, String> {
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+        match attempt_status(&tx, attempt_id)?.as_str() {
+            ATTEMPT_STATUS_CREATED | ATTEMPT_STATUS_IN_PROGRESS => {}
+            ATTEMPT_STATUS_PAUSED => {
+                transition_attempt(&tx, attempt_id, ATTEMPT_STATUS_IN_PROGRESS)?;
+            }
+            status => return Err(attempt_status_error(attempt_id, status, "resumed")),
+        }
+
+        let (student_name, mode, full_test_id, blueprint_id): (
+            String,
+            Option<String>,
+            Option<i64>,
+            Option<i64>,
+        ) = tx
+            .query_row(
+                "SELECT student_name, mode, full_test_id, blueprint_id FROM attempts WHERE id = ?",
+                params![attempt_id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .map_err(|e| e.to_string())?;
+        let mode = mode.unwrap_or_else(|| ATTEMPT_MODE_RANDOM.to_string());
+        let blueprint_id = resolve_blueprint_id(&tx, blueprint_id)?;
+
+        let answered = attempt_questions_by_answered(&tx, attempt_id, true)?;
+        let remaining = match full_test_id {
+            Some(full_test_id) if mode == ATTEMPT_MODE_FULL_TEST => {
+                let answered_ids: HashSet<i64> =
+                    answered.iter().map(|question| question.id).collect();
+                let remaining_ids: Vec<i64> = full_test_question_ids(&tx, full_test_id)?
+                    .into_iter()
+                    .filter(|id| !answered_ids.contains(id))
+                    .collect();
+                questions_by_ids(&tx, &remaining_ids)?
+            }
+            _ => attempt_questions_by_answered(&tx, attempt_id, false)?,
+        };
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(ResumedAttempt {
+            attempt_id,
+            student_name,
+            mode,
+            full_test_id,
+            blueprint_id,
+            answered,
+            remaining,
+        })
+    })
+    .await
+}
+
+/// The served questions of an attempt that have, or have not yet, got a
+/// saved response, in serving order.
+fn attempt_questions_by_answered(
+    conn: &Connection,
+    attempt_id: i64,
+    answered: bool,
+) -> Result<Vec<Question>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT questions.id, questions.part, questions.sub_part, questions.audio_path,
+                    questions.image_path, questions.text, questions.pack_id,
+                    questions.pack_order, questions.response_time, questions.active,
+                    questions.prep_time
+             FROM attempt_questions
+             JOIN questions ON attempt_questions.question_id = questions.id
+             WHERE attempt_questions.attempt_id = ?1
+               AND EXISTS (
+                   SELECT 1 FROM responses
+                   WHERE responses.attempt_id = attempt_questions.attempt_id
+                     AND responses.question_id = attempt_questions.question_id
+               ) = ?2
+             ORDER BY attempt_questions.position",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let questions = stmt
+        .query_map(params![attempt_id, answered], question_from_row)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(questions)
+}
+
+/// Loads questions by id, keeping the order of `ids`.
+fn questions_by_ids(conn: &Connection, ids: &[i64]) -> Result<Vec<Question>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order,
+                    response_time, active, prep_time
+             FROM questions WHERE id = ?",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut questions = Vec::with_capacity(ids.len());
+    for id in ids {
+        let question = stmt
+            .query_row(params![id], question_from_row)
+            .map_err(|e| e.to_string())?;
+        questions.push(question);
+    }
+    Ok(questions)
+}
+
+#[tauri::command]
+pub async fn get_attempts(db: State<'_, Db>) -> Result<Vec<Attempt>, String> {
+    db.run(move |conn| load_attempts(conn, "")).await
+}
+
+/// Attempts that were paused or cut short and can still be picked up.
+#[tauri::command]
+pub async fn get_resumable_attempts(db: State<'_, Db>) -> Result<Vec<Attempt>, String> {
+    db.run(move |conn| {
+        load_attempts(
+            conn,
+            "WHERE attempts.status IN ('created', 'in_progress', 'paused')",
+        )
+    })
+    .await
+}
+
+/// Lists attempts, newest first. `filter` is an optional `WHERE` clause over
+/// the `attempts` table.
+fn load_attempts(conn: &Connection, filter: &str) -> Result<Vec<Attempt>, String> {
+    let mut stmt = conn
+            .prepare(&format!(
+                "SELECT attempts.id,
+                        attempts.student_name,
+                        attempts.started_at,
+                        attempts.finished_at,
+                        scores.total,
+                        scores.count,
+                        attempts.mode,
+                        attempts.full_test_id,
+                        full_tests.name,
+                        (SELECT COUNT(*) FROM attempt_questions
+                         WHERE attempt_questions.attempt_id = attempts.id),
+                        (SELECT COUNT(*) FROM attempt_questions
+                         WHERE attempt_questions.attempt_id = attempts.id
+                           AND NOT EXISTS (
+                               SELECT 1 FROM responses
+                               WHERE responses.attempt_id = attempt_questions.attempt_id
+                                 AND responses.question_id = attempt_questions.question_id
+                           )),
+                        attempts.status,
+                        attempts.blueprint_id,
+                        blueprints.name,
+                        attempts.selection_seed,
+                        attempts.shared_seed
+                 FROM attempts
+                 LEFT JOIN full_tests ON attempts.full_test_id = full_tests.id
+                 LEFT JOIN blueprints ON attempts.blueprint_id = blueprints.id
+                 LEFT JOIN (
+                     SELECT responses.attempt_id AS attempt_id,
+                            SUM(COALESCE(ratings.fluency, 0) + COALESCE(ratings.lexical, 0)
+                                + COALESCE(ratings.grammar, 0) + COALESCE(ratings.pronunciation, 0)) AS total,
+                            SUM((ratings.fluency IS NOT NULL) + (ratings.lexical IS NOT NULL)
+                                + (ratings.grammar IS NOT NULL) + (ratings.pronunciation IS NOT NULL)) AS count
+                     FROM ratings
+                     JOIN responses ON ratings.response_id = responses.id
+                     GROUP BY responses.attempt_id
+                 ) AS scores ON scores.attempt_id = attempts.id
+                 {}
+                 ORDER BY attempts.started_at DESC",
+                filter
+            ))
+            .map_err(|e| e.to_string())?;
+
+    let attempts = stmt
+        .query_map([], |row| {
+            let total: Option<i64> = row.get(4)?;
+            let count: Option<i64> = row.get(5)?;
+            Ok(Attempt {
+                id: row.get(0)?,
+                student_name: row.get(1)?,
+                started_at: row.get(2)?,
+                finished_at: row.get(3)?,
+                overall_score: band_score(total.unwrap_or(0), count.unwrap_or(0)),
+                status: row.get(11)?,
+                mode: row.get(6)?,
+                full_test_id: row.get(7)?,
+                full_test_name: row.get(8)?,
+                blueprint_id: row.get(12)?,
+                blueprint_name: row.get(13)?,
+                selection_seed: row.get(14)?,
+                shared_seed: row.get(15)?,
+                served_count: row.get(9)?,
+                unanswered_count: row.get(10)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(attempts)
+}
+
+/// Runs `prepare` on an uploaded file away from the async runtime. Returns
+/// `filename` with the extension of the format the file ended up in, and the
+/// prepared file.
+async fn prepare_upload(
+    filename: String,
+    data: Vec<u8>,
+    prepare: fn(&[u8]) -> Result<media::Prepared, String>,
+) -> Result<(String, media::Prepared), String> {
+    let prepared = tauri::async_runtime::spawn_blocking(move || prepare(&data))
+        .await
+        .map_err(|e| format!("Failed to check the uploaded file: {}", e))?
+        .map_err(|error| {
+            let error = format!("{} was not saved: {}", filename, error);
+            println!("Error: {}", error);
+            error
+        })?;
+
+    let filename = Path::new(&filename)
+        .with_extension(prepared.format.extension)
+        .to_string_lossy()
+        .into_owned();
+    Ok((filename, prepared))
+}
+
+/// Stores uploaded question audio and returns its asset ID. The file is
+/// checked first, see `media::prepare_audio`, and the gain it plays at is
+/// kept with it.
+#[tauri::command]
+pub async fn save_audio_file(
+    app_handle: AppHandle,
+    db: State<'_, Db>,
+    filename: String,
+    audio_data: Vec<u8>,
+) -> Result<String, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    println!(
+        "Saving audio file {}, size: {} bytes",
+        filename,
+        audio_data.len()
+    );
+
+    let (filename, prepared) = prepare_upload(filename, audio_data, media::prepare_audio).await?;
+    let asset_id = db
+        .run(move |conn| {
+            let asset_id = store_asset(conn, &app_dir, "audio", &filename, &prepared.data)?;
+            conn.execute(
+                "UPDATE asset_hashes SET gain_db = ? WHERE folder = 'audio' AND filename = ?",
+                params![prepared.gain_db, asset_id],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(asset_id)
+        })
+        .await
+        .map_err(|error| {
+            println!("Error: {}", error);
+            error
+        })?;
+
+    println!("Audio file saved successfully: {}", asset_id);
+    Ok(asset_id)
+}
+
+/// Reads question audio or a bundled instruction sound by asset ID.
+#[tauri::command]
+pub async fn get_audio_file(app_handle: AppHandle, asset_id: String) -> Result<Vec<u8>, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    let roots = asset_roots(&app_handle, &app_dir, "audio");
+
+    assets::read(&roots, &asset_id).map_err(|e| {
+        let err_msg = format!("Failed to read audio file: {}", e);
+        println!("Error: {}", err_msg);
+        err_msg
+    })
+}
+
+/// The gain, in dB, a stored question audio file is played at, see
+/// `media::playback_gain`. Files whose gain is not worked out yet, and files
+/// the bank did not store, like bundled instruction sounds, play as they are.
+fn stored_audio_gain(conn: &Connection, asset_id: &str) -> Result<f64, String> {
+    let gain: Option<Option<f64>> = conn
+        .query_row(
+            "SELECT gain_db FROM asset_hashes WHERE folder = 'audio' AND filename = ?",
+            params![asset_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(gain.flatten().unwrap_or(0.0))
+}
+
+/// Works out the playback gain of stored question audio that has none yet,
+/// such as files kept from before gains were stored or brought in by an
+/// import. Each file is decoded outside the database lock. A file that
+/// cannot be decoded plays as it is; one that cannot be read is tried again
+/// next time.
+async fn measure_missing_audio_gains(db: &Db, app_dir: &Path) -> Result<(), String> {
+    let asset_ids = db
+        .run(|conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT filename FROM asset_hashes WHERE folder = 'audio' AND gain_db IS NULL",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map([], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())
+        })
+        .await?;
+
+    for asset_id in asset_ids {
+        let root = app_dir.join("audio");
+        let read_id = asset_id.clone();
+        let data =
+            match tauri::async_runtime::spawn_blocking(move || assets::read(&[root], &read_id))
+                .await
+                .map_err(|e| e.to_string())?
+            {
+                Ok(data) => data,
+                Err(error) => {
+                    println!("Warning: could not read audio {}: {}", asset_id, error);
+                    continue;
+                }
+            };
+        let gain = tauri::async_runtime::spawn_blocking(move || media::playback_gain(&data))
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap_or_else(|error| {
+                println!("Warning: could not measure audio {}: {}", asset_id, error);
+                0.0
+            });
+
+        db.run(move |conn| {
+            conn.execute(
+                "UPDATE asset_hashes SET gain_db = ?
+                 WHERE folder = 'audio' AND filename = ? AND gain_db IS NULL",
+                params![gain, asset_id],
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(())
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Starts measuring, in the background, the gain of question audio that has
+/// none yet, see `measure_missing_audio_gains`.
+pub fn start_audio_gain_measurement(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let db = app_handle.state::<Db>().inner().clone();
+        let result = match app_data_dir(&app_handle) {
+            Ok(app_dir) => measure_missing_audio_gains(&db, &app_dir).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            println!("Warning: failed to measure question audio: {}", error);
+        }
+    });
+}
+
+/// The gain, in dB, to play question audio at so every prompt sounds equally
+/// loud.
+#[tauri::command]
+pub async fn get_audio_gain(db: State<'_, Db>, asset_id: String) -> Result<f64, String> {
+    db.run(move |conn| stored_audio_gain(conn, &asset_id))
+        .await
+        .map_err(|error| {
+            let err_msg = format!("Failed to read audio gain: {}", error);
+            println!("Error: {}", err_msg);
+            err_msg
+        })
+}
+
+/// `QuestionInput` after defaults are filled in and the section rules checked.
+struct QuestionFields {
+    part: i32,
+    sub_part: i32,
+    audio_path: String,
+    image_path: String,
+    text: String,
+    pack_id: String,
+    pack_order: i32,
+    response_time: i32,
+    prep_time: i32,
+}
+
+fn validated_question_fields(
+    conn: &Connection,
+    input: QuestionInput,
+) -> Result<QuestionFields, String> {
+    // Use empty string if no audio path provided
+    let audio = input.audio_path.unwrap_or_default().trim().to_string();
+    let image = input.image_path.unwrap_or_default().trim().to_string();
+    for (kind, id) in [("audio", &audio), ("image", &image)] {
+        if !id.is_empty() {
+            assets::validate_asset_id(id).map_err(|e| format!("Question {}: {}", kind, e))?;
+        }
+    }
+    let question_text = if input.part == 3 {
+        String::new()
+    } else {
+        input.text.unwrap_or_default()
+    };
+    let sub_part_value = input.sub_part.unwrap_or(0);
+    let pack_id_value = input.pack_id.unwrap_or_default();
+    let pack_order_value = input.pack_order.unwrap_or(0);
+
+    let rules = section_rules(conn, input.part, sub_part_value, &[])?;
+    if rules.image_required && image.is_empty() {
+        return Err(format!("Image is required for {} questions", rules.label));
+    }
+
+    if rules.pack_required {
+        if pack_id_value.trim().is_empty() {
+            return Err(format!(
+                "Test pack ID is required for {} questions",
+                rules.label
+            ));
+        }
+        if pack_order_value <= 0 {
+            return Err(format!(
+                "Question order is required for {} questions",
+                rules.label
+            ));
+        }
+    }
+
+    let prep_time = rules.prep_time(input.prep_time)?;
+
+    Ok(QuestionFields {
+        part: input.part,
+        sub_part: sub_part_value,
+        audio_path: audio,
+        image_path: image,
+        text: question_text,
+        pack_id: pack_id_value,
+        pack_order: pack_order_value,
+        response_time: input.response_time,
+        prep_time,
+    })
+}
+
+/// Saves the current state of a question as a new revision, unless it is
+/// unchanged since the latest one. Returns the ID of the revision that
+/// matches the question as it is now.
+fn record_question_revision(conn: &Connection, question_id: i64) -> Result<i64, String> {
+    let latest = conn
+        .query_row(
+            "SELECT question_revisions.id,
+                    question_revisions.revision,
+                    question_revisions.part IS questions.part
+                        AND question_revisions.sub_part IS questions.sub_part
+                        AND question_revisions.audio_path IS questions.audio_path
+                        AND question_revisions.image_path IS questions.image_path
+                        AND question_revisions.text IS questions.text
+                        AND question_revisions.pack_id IS questions.pack_id
+                        AND question_revisions.pack_order IS questions.pack_order
+                        AND question_revisions.response_time IS questions.response_time
+                        AND question_revisions.prep_time IS questions.prep_time
+             FROM question_revisions
+             JOIN questions ON question_revisions.question_id = questions.id
+             WHERE question_revisions.question_id = ?
+             ORDER BY question_revisions.revision DESC
+             LIMIT 1",
+            params![question_id],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i32>(1)?,
+                    row.get::<_, bool>(2)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+
+    let next_revision = match latest {
+        Some((revision_id, _, true)) => return Ok(revision_id),
+        Some((_, revision, false)) => revision + 1,
+        None => 1,
+    };
+
+    let inserted = conn
+        .execute(
+            "INSERT INTO question_revisions
+                (question_id, revision, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, prep_time)
+             SELECT id, ?, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, prep_time
+             FROM questions WHERE id = ?",
+            params![next_revision, question_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if inserted == 0 {
+        return Err(format!("Question {} was not found", question_id));
+    }
+
+    Ok(conn.last_insert_rowid())
+}
+
+#[tauri::command]
+pub async fn add_question(
+    db: State<'_, Db>,
+    part: i32,
+    sub_part: Option<i32>,
+    response_time: i32,
+    prep_time: Option<i32>,
+    audio_path: Option<String>,
+    image_path: Option<String>,
+    text: Option<String>,
+    pack_id: Option<String>,
+    pack_order: Option<i32>,
+) -> Result<i64, String> {
+    let input = QuestionInput {
+        part,
+        sub_part,
+        response_time,
+        prep_time,
+        audio_path,
+        image_path,
+        text,
+        pack_id,
+        pack_order,
+    };
+
+    db.run(move |conn| {
+        let fields = validated_question_fields(conn, input)?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "INSERT INTO questions (uuid, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, prep_time, active)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1)",
+            params![
+                new_content_id(),
+                fields.part,
+                fields.sub_part,
+                fields.audio_path,
+                fields.image_path,
+                fields.text,
+                fields.pack_id,
+                fields.pack_order,
+                fields.response_time,
+                fields.prep_time
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let question_id = tx.last_insert_rowid();
+
+        record_question_revision(&tx, question_id)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(question_id)
+    })
+    .await
+}
+
+/// Edits a question in place, keeping its ID so full tests and earlier
+/// responses stay linked to it. The previous wording stays available in the
+/// question's revision history. Returns the question's revision number.
+#[tauri::command]
+pub async fn update_question(
+    db: State<'_, Db>,
+    question_id: i64,
+    question: QuestionInput,
+) -> Result<i32, String> {
+    db.run(move |conn| {
+        let fields = validated_question_fields(conn, question)?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let updated = tx
+            .execute(
+                "UPDATE questions
+                 SET part = ?, sub_part = ?, audio_path = ?, image_path = ?, text = ?,
+                     pack_id = ?, pack_order = ?, response_time = ?, prep_time = ?
+                 WHERE id = ?",
+                params![
+                    fields.part,
+                    fields.sub_part,
+                    fields.audio_path,
+                    fields.image_path,
+                    fields.text,
+                    fields.pack_id,
+                    fields.pack_order,
+                    fields.response_time,
+                    fields.prep_time,
+                    question_id
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err(format!("Question {} was not found", question_id));
+        }
+
+        for (full_test_id, name) in question_full_tests(&tx, question_id)? {
+            let blueprint_id = full_test_blueprint_id(&tx, full_test_id)?;
+            let question_ids = full_test_question_ids(&tx, full_test_id)?;
+            validate_full_test_questions(&tx, blueprint_id, &question_ids).map_err(|error| {
+                format!(
+                    "Full test \"{}\" uses this question and would no longer fit its format: {}. Remove the question from that test first.",
+                    name, error
+                )
+            })?;
+        }
+
+        let revision_id = record_question_revision(&tx, question_id)?;
+        let revision: i32 = tx
+            .query_row(
+                "SELECT revision FROM question_revisions WHERE id = ?",
+                params![revision_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+
+        tx.commit().map_err(|e| e.to_string())?;
+        println!("Question {} saved as revision {}", question_id, revision);
+        Ok(revision)
+    })
+    .await
+}
+
+/// Lists every revision of a question, newest first.
+#[tauri::command]
+pub async fn get_question_revisions(
+    db: State<'_, Db>,
+    question_id: i64,
+) -> Result<Vec<QuestionRevision>, String> {
+    db.run(move |conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT question_revisions.id,
+                        question_revisions.question_id,
+                        question_revisions.revision,
+                        question_revisions.part,
+                        question_revisions.sub_part,
+                        question_revisions.audio_path,
+                        question_revisions.image_path,
+                        question_revisions.text,
+                        question_revisions.pack_id,
+                        question_revisions.pack_order,
+                        question_revisions.response_time,
+                        question_revisions.prep_time,
+                        COALESCE(question_revisions.created_at, ''),
+                        COUNT(responses.id)
+                 FROM question_revisions
+                 LEFT JOIN responses ON responses.question_revision_id = question_revisions.id
+                 WHERE question_revisions.question_id = ?
+                 GROUP BY question_revisions.id
+                 ORDER BY question_revisions.revision DESC",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let revisions = stmt
+            .query_map(params![question_id], |row| {
+                Ok(QuestionRevision {
+                    id: row.get(0)?,
+                    question_id: row.get(1)?,
+                    revision: row.get(2)?,
+                    part: row.get(3)?,
+                    sub_part: row.get(4)?,
+                    audio_path: row.get(5)?,
+                    image_path: row.get(6)?,
+                    text: row.get(7)?,
+                    pack_id: row.get(8)?,
+                    pack_order: row.get(9)?,
+                    response_time: row.get(10)?,
+                    prep_time: row.get(11)?,
+                    created_at: row.get(12)?,
+                    response_count: row.get(13)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        Ok(revisions)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_questions(db: State<'_, Db>) -> Result<Vec<Question>, String> {
+    db.run(move |conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active, prep_time
+             FROM questions WHERE active = 1 ORDER BY part, sub_part, id"
+        ).map_err(|e| e.to_string())?;
+
+        let questions = stmt
+            .query_map([], |row| {
+                Ok(Question {
+                    id: row.get(0)?,
+                    part: row.get(1)?,
+                    sub_part: row.get(2)?,
+                    audio_path: row.get(3)?,
+                    image_path: row.get(4)?,
+                    text: row.get(5)?,
+                    pack_id: row.get(6)?,
+                    pack_order: row.get(7)?,
+                    response_time: row.get(8)?,
+                    active: row.get(9)?,
+                    prep_time: row.get(10)?,
+                })
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        Ok(questions)
+    })
+    .await
+}
+
+/// Trims a blueprint from the admin form or an export and checks its
+/// sections. Sections without a label get the plain part name.
+fn validated_blueprint(input: BlueprintInput) -> Result<BlueprintInput, String> {
+    let name = input.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Exam format name cannot be empty".to_string());
+    }
+    if input.sections.is_empty() {
+        return Err(format!("\"{}\" needs at least one section", name));
+    }
+
+    let mut seen = HashSet::new();
+    let mut sections = Vec::with_capacity(input.sections.len());
+    for section in input.sections {
+        let fallback = fallback_section_label(section.part, section.sub_part);
+        if section.part < 1 || section.sub_part < 0 {
+            return Err(format!("{} is not a valid section", fallback));
+        }
+        if !seen.insert((section.part, section.sub_part)) {
+            return Err(format!("{} appears more than once", fallback));
+        }
+        if section.question_count < 1 {
+            return Err(format!("{} needs at least one question", fallback));
+        }
+        if section.selection != SECTION_SELECTION_PACK
+            && section.selection != SECTION_SELECTION_RANDOM
+        {
+            return Err(format!(
+                "{} has an unknown question selection: {}",
+                fallback, section.selection
+            ));
+        }
+        if !(0..=MAX_SECTION_PREP_TIME_SECONDS).contains(&section.prep_time) {
+            return Err(format!(
+                "{} preparation time must be between 0 and {} seconds",
+                fallback, MAX_SECTION_PREP_TIME_SECONDS
+            ));
+        }
+
+        // Instruction audio is bundled with the app, so only an asset ID is
+        // allowed.
+        let instruction_audio = section.instruction_audio.trim().to_string();
+        if !instruction_audio.is_empty() && assets::validate_asset_id(&instruction_audio).is_err() {
+            return Err(format!(
+                "{} instruction audio must be a plain file name",
+                fallback
+            ));
+        }
+
+        let label = section.label.trim().to_string();
+        sections.push(BlueprintSection {
+            label: if label.is_empty() { fallback } else { label },
+            instruction_audio,
+            instruction_text: section.instruction_text.trim().to_string(),
+            ..section
+        });
+    }
+
+    Ok(BlueprintInput {
+        name,
+        description: input.description.trim().to_string(),
+        sections,
+    })
+}
+
+fn ensure_blueprint_name_available(
+    conn: &Connection,
+    name: &str,
+    blueprint_id: Option<i64>,
+) -> Result<(), String> {
+    let taken: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM blueprints WHERE name = ? AND active = 1 AND id IS NOT ?)",
+            params![name, blueprint_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    if taken {
+        return Err(format!("An exam format named \"{}\" already exists", name));
+    }
+    Ok(())
+}
+
+fn write_blueprint_sections(
+    conn: &Connection,
+    blueprint_id: i64,
+    sections: &[BlueprintSection],
+) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM blueprint_sections WHERE blueprint_id = ?",
+        params![blueprint_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    for (index, section) in sections.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO blueprint_sections
+                (blueprint_id, position, part, sub_part, label, question_count, selection,
+                 prep_time, image_required, instruction_audio, instruction_text)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                blueprint_id,
+                index as i64 + 1,
+                section.part,
+                section.sub_part,
+                section.label,
+                section.question_count,
+                section.selection,
+                section.prep_time,
+                section.image_required,
+                section.instruction_audio,
+                section.instruction_text
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn insert_blueprint(conn: &Connection, blueprint: &BlueprintInput) -> Result<i64, String> {
+    let blueprint = validated_blueprint(blueprint.clone())?;
+    ensure_blueprint_name_available(conn, &blueprint.name, None)?;
+
+    conn.execute(
+        "INSERT INTO blueprints (name, description, is_default, active) VALUES (?, ?, 0, 1)",
+        params![blueprint.name, blueprint.description],
+    )
+    .map_err(|e| e.to_string())?;
+    let blueprint_id = conn.last_insert_rowid();
+    write_blueprint_sections(conn, blueprint_id, &blueprint.sections)?;
+
+    Ok(blueprint_id)
+}
+
+/// Active full tests laid out for a blueprint, as `(id, name)`.
+fn blueprint_full_tests(
+    conn: &Connection,
+    blueprint_id: i64,
+) -> Result<Vec<(i64, String)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, name FROM full_tests WHERE blueprint_id = ? AND active = 1 ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let tests = stmt
+        .query_map(params![blueprint_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tests)
+}
+
+/// The active full tests that include a question.
+fn question_full_tests(conn: &Connection, question_id: i64) -> Result<Vec<(i64, String)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT DISTINCT full_tests.id, full_tests.name
+             FROM full_test_questions
+             JOIN full_tests ON full_test_questions.full_test_id = full_tests.id
+             WHERE full_test_questions.question_id = ? AND full_tests.active = 1
+             ORDER BY full_tests.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let tests = stmt
+        .query_map(params![question_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(tests)
+}
+
+/// Lists the active exam formats, the default one first.
+#[tauri::command]
+pub async fn get_blueprints(db: State<'_, Db>) -> Result<Vec<Blueprint>, String> {
+    db.run(move |conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT id FROM blueprints WHERE active = 1 ORDER BY is_default DESC, name ASC, id ASC",
+            )
+            .map_err(|e| e.to_string())?;
+        let ids = stmt
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        ids.into_iter()
+            .map(|blueprint_id| load_blueprint(conn, blueprint_id))
+            .collect()
+    })
+    .await
+}
+
+/// Loads one exam format, or the default one when no ID is given.
+#[tauri::command]
+pub async fn get_blueprint(
+    db: State<'_, Db>,
+    blueprint_id: Option<i64>,
+) -> Result<Blueprint, String> {
+    db.run(move |conn| {
+        let blueprint_id = resolve_blueprint_id(conn, blueprint_id)?;
+        load_blueprint(conn, blueprint_id)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn create_blueprint(db: State<'_, Db>, blueprint: BlueprintInput) -> Result<i64, String> {
+    db.run(move |conn| insert_blueprint(conn, &blueprint)).await
+}
+
+/// Saves a new definition for an exam format. Full tests laid out for it
+/// must still fit, otherwise nothing is changed.
+#[tauri::command]
+pub async fn update_blueprint(
+    db: State<'_, Db>,
+    blueprint_id: i64,
+    blueprint: BlueprintInput,
+) -> Result<(), String> {
+    let blueprint = validated_blueprint(blueprint)?;
+
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        blueprint_name(&tx, blueprint_id)?;
+        ensure_blueprint_name_available(&tx, &blueprint.name, Some(blueprint_id))?;
+
+        tx.execute(
+            "UPDATE blueprints SET name = ?, description = ? WHERE id = ?",
+            params![blueprint.name, blueprint.description, blueprint_id],
+        )
+        .map_err(|e| e.to_string())?;
+        write_blueprint_sections(&tx, blueprint_id, &blueprint.sections)?;
+
+        for (full_test_id, name) in blueprint_full_tests(&tx, blueprint_id)? {
+            let question_ids = full_test_question_ids(&tx, full_test_id)?;
+            validate_full_test_questions(&tx, blueprint_id, &question_ids).map_err(|error| {
+                format!("\"{}\" would no longer fit this format: {}", name, error)
+            })?;
+        }
+
+        tx.commit().map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Makes an exam format the one used for random exams and for full tests
+/// that do not name a format.
+#[tauri::command]
+pub async fn set_default_blueprint(db: State<'_, Db>, blueprint_id: i64) -> Result<(), String> {
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        blueprint_name(&tx, blueprint_id)?;
+        tx.execute(
+            "UPDATE blueprints SET is_default = (id = ?)",
+            params![blueprint_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Retires an exam format. Attempts taken in it keep their section labels;
+/// attempts that can still be resumed must be closed first.
+#[tauri::command]
+pub async fn delete_blueprint(db: State<'_, Db>, blueprint_id: i64) -> Result<(), String> {
+    db.run(move |conn| {
+        let name = blueprint_name(conn, blueprint_id)?;
+        if default_blueprint_id(conn)? == blueprint_id {
+            return Err(format!(
+                "\"{}\" is the default exam format. Make another format the default first.",
+                name
+            ));
+        }
+
+        let full_tests = blueprint_full_tests(conn, blueprint_id)?;
+        if !full_tests.is_empty() {
+            return Err(format!(
+                "\"{}\" is used by {} full test(s). Delete them first.",
+                name,
+                full_tests.len()
+            ));
+        }
+
+        let unfinished: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM attempts WHERE blueprint_id = ? AND status IN (?, ?, ?)",
+                params![
+                    blueprint_id,
+                    ATTEMPT_STATUS_CREATED,
+                    ATTEMPT_STATUS_IN_PROGRESS,
+                    ATTEMPT_STATUS_PAUSED
+                ],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if unfinished > 0 {
+            return Err(format!(
+                "\"{}\" is used by {} unfinished attempt(s). Finish or abandon them first.",
+                name, unfinished
+            ));
+        }
+
+        conn.execute(
+            "UPDATE blueprints SET active = 0, is_default = 0 WHERE id = ?",
+            params![blueprint_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn create_full_test(
+    db: State<'_, Db>,
+    name: String,
+    question_ids: Vec<i64>,
+    blueprint_id: Option<i64>,
+) -> Result<i64, String> {
+    let trimmed_name = name.trim().to_string();
+    if trimmed_name.is_empty() {
+        return Err("Full test name cannot be empty".to_string());
+    }
+
+    db.run(move |conn| {
+        let blueprint_id = resolve_blueprint_id(conn, blueprint_id)?;
+        validate_full_test_questions(conn, blueprint_id, &question_ids)?;
+
+        conn.execute(
+            "INSERT INTO full_tests (uuid, name, blueprint_id, active) VALUES (?, ?, ?, 1)",
+            params![new_content_id(), trimmed_name, blueprint_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let full_test_id = conn.last_insert_rowid();
+        insert_full_test_questions(conn, full_test_id, &question_ids)?;
+
+        Ok(full_test_id)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_full_tests(db: State<'_, Db>) -> Result<Vec<FullTest>, String> {
+    db.run(move |conn| {
+        let mut stmt = conn
+            .prepare(
+                "SELECT full_tests.id,
+                        full_tests.name,
+                        full_tests.created_at,
+                        full_tests.blueprint_id,
+                        COALESCE(blueprints.name, '')
+                 FROM full_tests
+                 LEFT JOIN blueprints ON full_tests.blueprint_id = blueprints.id
+                 WHERE full_tests.active = 1
+                 ORDER BY full_tests.created_at DESC, full_tests.id DESC",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let test_rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<i64>>(3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut tests = Vec::new();
+        for row in test_rows {
+            let (id, name, created_at, blueprint_id, blueprint_name) =
+                row.map_err(|e| e.to_string())?;
+            let mut question_stmt = conn
+                .prepare(&format!(
+                    "SELECT questions.id,
+                            questions.part,
+                            questions.sub_part,
+                            questions.audio_path,
+                            questions.image_path,
+                            questions.text,
+                            questions.pack_id,
+                            questions.pack_order,
+                            questions.response_time,
+                            questions.active,
+                            questions.prep_time
+                     FROM full_test_questions
+                     JOIN questions ON full_test_questions.question_id = questions.id
+                     WHERE full_test_questions.full_test_id = ? AND questions.active = 1
+                     {}",
+                    FULL_TEST_QUESTION_ORDER
+                ))
+                .map_err(|e| e.to_string())?;
+
+            let questions = question_stmt
+                .query_map(params![id], question_from_row)
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+
+            tests.push(FullTest {
+                id,
+                name,
+                created_at,
+                blueprint_id,
+                blueprint_name,
+                attempt_count: full_test_attempt_count(conn, id)?,
+                questions,
+            });
+        }
+
+        Ok(tests)
+    })
+    .await
+}
+
+fn full_test_attempt_count(conn: &Connection, full_test_id: i64) -> Result<i64, String> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM attempts WHERE full_test_id = ?",
+        params![full_test_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn full_test_name(conn: &Connection, full_test_id: i64) -> Result<String, String> {
+    conn.query_row(
+        "SELECT name FROM full_tests WHERE id = ? AND active = 1",
+        params![full_test_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Full test {} not found or inactive", full_test_id))
+}
+
+/// The exam format a full test is laid out for; tests saved before formats
+/// existed use the default one.
+fn full_test_blueprint_id(conn: &Connection, full_test_id: i64) -> Result<i64, String> {
+    let blueprint_id: Option<i64> = conn
+        .query_row(
+            "SELECT blueprint_id FROM full_tests WHERE id = ?",
+            params![full_test_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Full test {} not found", full_test_id))?;
+    resolve_blueprint_id(conn, blueprint_id)
+}
+
+/// The active questions of a full test in exam order.
+fn full_test_question_ids(conn: &Connection, full_test_id: i64) -> Result<Vec<i64>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT questions.id
+             FROM full_test_questions
+             JOIN questions ON full_test_questions.question_id = questions.id
+             WHERE full_test_questions.full_test_id = ? AND questions.active = 1
+             {}",
+            FULL_TEST_QUESTION_ORDER
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let ids = stmt
+        .query_map(params![full_test_id], |row| row.get::<_, i64>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+/// Replaces the question list of a full test. Candidates who took a test
+/// must still be able to see the form they were given, so once a test has
+/// been used its questions can no longer change; it has to be duplicated as
+/// a new version instead.
+fn replace_full_test_questions(
+    conn: &Connection,
+    full_test_id: i64,
+    question_ids: &[i64],
+) -> Result<(), String> {
+    // Sections are always served in the blueprint's order, so only the order
+    // within each section is kept.
+    let blueprint_id = full_test_blueprint_id(conn, full_test_id)?;
+    let mut questions = validate_full_test_questions(conn, blueprint_id, question_ids)?;
+    let section_positions: HashMap<(i32, i32), usize> = blueprint_sections(conn, blueprint_id)?
+        .into_iter()
+        .enumerate()
+        .map(|(position, section)| ((section.part, section.sub_part), position))
+        .collect();
+    questions.sort_by_key(|question| section_positions.get(&(question.part, question.sub_part)));
+    let ordered_ids = questions
+        .iter()
+        .map(|question| question.id)
+        .collect::<Vec<_>>();
+
+    if full_test_question_ids(conn, full_test_id)? == ordered_ids {
+        return Ok(());
+    }
+
+    let attempt_count = full_test_attempt_count(conn, full_test_id)?;
+    if attempt_count > 0 {
+        let name = full_test_name(conn, full_test_id)?;
+        return Err(format!(
+            "\"{}\" has been used in {} attempt(s), so its questions cannot be changed. Duplicate it as a new version and edit that instead.",
+            name, attempt_count
+        ));
+    }
+
+    conn.execute(
+        "DELETE FROM full_test_questions WHERE full_test_id = ?",
+        params![full_test_id],
+    )
+    .map_err(|e| e.to_string())?;
+    insert_full_test_questions(conn, full_test_id, &ordered_ids)
+}
+
+/// Runs `edit` on the current question list of an active full test and
+/// saves the result.
+async fn edit_full_test_questions(
+    db: &Db,
+    full_test_id: i64,
+    edit: impl FnOnce(&mut Vec<i64>) -> Result<(), String> + Send + 'static,
+) -> Result<(), String> {
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        full_test_name(&tx, full_test_id)?;
+
+        let mut question_ids = full_test_question_ids(&tx, full_test_id)?;
+        edit(&mut question_ids)?;
+        replace_full_test_questions(&tx, full_test_id, &question_ids)?;
+
+        tx.commit().map_err(|e| e.to_string())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn rename_full_test(
+    db: State<'_, Db>,
+    full_test_id: i64,
+    name: String,
+) -> Result<(), String> {
+    let trimmed_name = name.trim().to_string();
+    if trimmed_name.is_empty() {
+        return Err("Full test name cannot be empty".to_string());
+    }
+
+    db.run(move |conn| {
+        let updated = conn
+            .execute(
+                "UPDATE full_tests SET name = ? WHERE id = ? AND active = 1",
+                params![trimmed_name, full_test_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Err(format!("Full test {} not found or inactive", full_test_id));
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+/// Adds a question to the end of its section in a full test.
+#[tauri::command]
+pub async fn add_full_test_question(
+    db: State<'_, Db>,
+    full_test_id: i64,
+    question_id: i64,
+) -> Result<(), String> {
+    edit_full_test_questions(&db, full_test_id, move |question_ids| {
+        if question_ids.contains(&question_id) {
+            return Err(format!(
+                "Question {} is already in this full test",
+                question_id
+            ));
+        }
+        question_ids.push(question_id);
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn remove_full_test_question(
+    db: State<'_, Db>,
+    full_test_id: i64,
+    question_id: i64,
+) -> Result<(), String> {
+    edit_full_test_questions(&db, full_test_id, move |question_ids| {
+        let before = question_ids.len();
+        question_ids.retain(|id| *id != question_id);
+        if question_ids.len() == before {
+            return Err(format!("Question {} is not in this full test", question_id));
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Saves a new order for the questions of a full test. `question_ids` must
+/// hold exactly the questions already in the test; within each section they
+/// are served in this order.
+#[tauri::command]
+pub async fn reorder_full_test_questions(
+    db: State<'_, Db>,
+    full_test_id: i64,
+    question_ids: Vec<i64>,
+) -> Result<(), String> {
+    edit_full_test_questions(&db, full_test_id, move |current_ids| {
+        let mut expected = current_ids.clone();
+        let mut requested = question_ids.clone();
+        expected.sort_unstable();
+        requested.sort_unstable();
+        if expected != requested {
+            return Err(
+                "The new order must contain exactly the questions already in the full test"
+                    .to_string(),
+            );
+        }
+
+        *current_ids = question_ids;
+        Ok(())
+    })
+    .await
+}
+
+/// Copies a full test and its question order into a new test, for example
+/// to start a new version of a test that candidates have already taken.
+#[tauri::command]
+pub async fn clone_full_test(
+    db: State<'_, Db>,
+    full_test_id: i64,
+    name: Option<String>,
+) -> Result<i64, String> {
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let source_name = full_test_name(&tx, full_test_id)?;
+        let clone_name = name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| format!("{} (copy)", source_name));
+
+        let question_ids = full_test_question_ids(&tx, full_test_id)?;
+        let blueprint_id = full_test_blueprint_id(&tx, full_test_id)?;
+        validate_full_test_questions(&tx, blueprint_id, &question_ids)?;
+
+        tx.execute(
+            "INSERT INTO full_tests (uuid, name, blueprint_id, active) VALUES (?, ?, ?, 1)",
+            params![new_content_id(), clone_name, blueprint_id],
+        )
+        .map_err(|e| e.to_string())?;
+        let clone_id = tx.last_insert_rowid();
+        insert_full_test_questions(&tx, clone_id, &question_ids)?;
+
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(clone_id)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_full_test(db: State<'_, Db>, full_test_id: i64) -> Result<(), String> {
+    db.run(move |conn| {
+        conn.execute(
+            "UPDATE full_tests SET active = 0 WHERE id = ?",
+            params![full_test_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
+}
+
+fn export_question(
+    conn: &Connection,
+    app_handle: &AppHandle,
+    app_dir: &Path,
+    question: Question,
+    sink: &mut AssetSink,
+) -> Result<ExportedQuestion, String> {
+    let uuid: Option<String> = conn
+        .query_row(
+            "SELECT uuid FROM questions WHERE id = ?",
+            params![question.id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(ExportedQuestion {
+        source_id: question.id,
+        uuid: uuid.unwrap_or_default(),
+        part: question.part,
+        sub_part: question.sub_part,
+        audio_file: read_exported_asset(app_handle, app_dir, "audio", &question.audio_path, sink)?,
+        image_file: read_exported_asset(app_handle, app_dir, "images", &question.image_path, sink)?,
+        audio_path: question.audio_path,
+        image_path: question.image_path,
+        text: question.text,
+        pack_id: question.pack_id,
+        pack_order: question.pack_order,
+        response_time: question.response_time,
+        prep_time: Some(question.prep_time),
+    })
+}
+
+fn build_full_test_export(
+    conn: &Connection,
+    app_handle: &AppHandle,
+    app_dir: &Path,
+    full_test_id: i64,
+    exported_at: &str,
+    sink: &mut AssetSink,
+) -> Result<FullTestExportFile, String> {
+    let (source_id, uuid, name, created_at, blueprint_id): (
+        i64,
+        Option<String>,
+        String,
+        String,
+        Option<i64>,
+    ) = conn
+        .query_row(
+            "SELECT id, uuid, name, created_at, blueprint_id
+             FROM full_tests
+             WHERE id = ? AND active = 1",
+            params![full_test_id],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Full test {} not found or inactive", full_test_id))?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT questions.id,
+                    questions.part,
+                    questions.sub_part,
+                    questions.audio_path,
+                    questions.image_path,
+                    questions.text,
+                    questions.pack_id,
+                    questions.pack_order,
+                    questions.response_time,
+                    questions.active,
+                    questions.prep_time
+             FROM full_test_questions
+             JOIN questions ON full_test_questions.question_id = questions.id
+             WHERE full_test_questions.full_test_id = ? AND questions.active = 1
+             {}",
+            FULL_TEST_QUESTION_ORDER
+        ))
+        .map_err(|e| e.to_string())?;
+
+    let question_rows = stmt
+        .query_map(params![full_test_id], question_from_row)
+        .map_err(|e| e.to_string())?;
+
+    let mut exported_questions = Vec::new();
+    for row in question_rows {
+        let question = row.map_err(|e| e.to_string())?;
+        exported_questions.push(export_question(conn, app_handle, app_dir, question, sink)?);
+    }
+
+    if exported_questions.is_empty() {
+        return Err(format!(
+            "Full test \"{}\" does not contain any active questions",
+            name
+        ));
+    }
+    let blueprint = load_blueprint(conn, resolve_blueprint_id(conn, blueprint_id)?)?;
+    let blueprint = BlueprintInput {
+        name: blueprint.name,
+        description: blueprint.description,
+        sections: blueprint.sections,
+    };
+    validate_exported_full_test_sections(&blueprint, &exported_questions)?;
+
+    Ok(FullTestExportFile {
+        format: "cefr-speaking-full-test".to_string(),
+        version: sink.export_version(),
+        exported_at: exported_at.to_string(),
+        source_id,
+        uuid: uuid.unwrap_or_default(),
+        name,
+        created_at,
+        blueprint: Some(blueprint),
+        questions: exported_questions,
+    })
+}
+
+fn import_full_test_payload(
+    conn: &Connection,
+    session: &mut ImportSession,
+    import_file: FullTestExportFile,
+) -> Result<i64, String> {
+    let blueprint = validate_full_test_export_file(conn, &import_file)?;
+    let trimmed_name = import_file.name.trim().to_string();
+    let incoming = incoming_sections(&blueprint).to_vec();
+    let blueprint_id = match blueprint.id {
+        Some(blueprint_id) => blueprint_id,
+        None => insert_blueprint(conn, &blueprint.definition)?,
+    };
+
+    let mut imported_question_ids = Vec::new();
+    let mut question_uuids = Vec::new();
+    for question in import_file.questions {
+        let (question_id, uuid) = import_exported_question(conn, session, question, &incoming)?;
+        imported_question_ids.push(question_id);
+        question_uuids.push(uuid);
+    }
+
+    let uuid = if import_file.uuid.trim().is_empty() {
+        derived_uuid(&format!("{}\n{}", trimmed_name, question_uuids.join("\n")))
+    } else {
+        import_file.uuid.trim().to_string()
+    };
+
+    let existing_id = if session.strategy == ImportStrategy::Duplicate {
+        None
+    } else {
+        conn.query_row(
+            "SELECT id FROM full_tests WHERE uuid = ?",
+            params![uuid],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?
+    };
+
+    if let Some(full_test_id) = existing_id {
+        if session.strategy == ImportStrategy::Skip {
+            conn.execute(
+                "UPDATE full_tests SET active = 1 WHERE id = ?",
+                params![full_test_id],
+            )
+            .map_err(|e| e.to_string())?;
+            session.tests.reused += 1;
+            return Ok(full_test_id);
+        }
+
+        validate_full_test_questions(conn, blueprint_id, &imported_question_ids)?;
+        conn.execute(
+            "UPDATE full_tests SET name = ?, blueprint_id = ?, active = 1 WHERE id = ?",
+            params![trimmed_name, blueprint_id, full_test_id],
+        )
+        .map_err(|e| e.to_string())?;
+        replace_full_test_questions(conn, full_test_id, &imported_question_ids)?;
+        session.tests.updated += 1;
+        return Ok(full_test_id);
+    }
+
+    validate_full_test_questions(conn, blueprint_id, &imported_question_ids)?;
+
+    let stored_uuid = if content_id_exists(conn, "full_tests", &uuid)? {
+        new_content_id()
+    } else {
+        uuid
+    };
+    conn.execute(
+        "INSERT INTO full_tests (uuid, name, blueprint_id, active) VALUES (?, ?, ?, 1)",
+        params![stored_uuid, trimmed_name, blueprint_id],
+    )
+    .map_err(|e| e.to_string())?;
+
+    let full_test_id = conn.last_insert_rowid();
+    insert_full_test_questions(conn, full_test_id, &imported_question_ids)?;
+    session.tests.created += 1;
+
+    Ok(full_test_id)
+}
+
+fn insert_full_test_questions(
+    conn: &Connection,
+    full_test_id: i64,
+    question_ids: &[i64],
+) -> Result<(), String> {
+    for (index, question_id) in question_ids.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO full_test_questions (full_test_id, question_id, position)
+             VALUES (?, ?, ?)",
+            params![full_test_id, question_id, (index as i32) + 1],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Reads the selected active questions in bank order into a questions export.
+fn build_questions_export(
+    conn: &Connection,
+    app_handle: &AppHandle,
+    app_dir: &Path,
+    question_ids: Vec<i64>,
+    sink: &mut AssetSink,
+) -> Result<QuestionsExportFile, String> {
+    let selected_ids: HashSet<i64> = question_ids.into_iter().collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active, prep_time
+         FROM questions WHERE active = 1 ORDER BY part, sub_part, id"
+    ).map_err(|e| e.to_string())?;
+
+    let questions = stmt
+        .query_map([], |row| {
+            Ok(Question {
+                id: row.get(0)?,
+                part: row.get(1)?,
+                sub_part: row.get(2)?,
+                audio_path: row.get(3)?,
                 image_path: row.get(4)?,
                 text: row.get(5)?,
                 pack_id: row.get(6)?,
                 pack_order: row.get(7)?,
                 response_time: row.get(8)?,
                 active: row.get(9)?,
+                prep_time: row.get(10)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let mut exported_questions = Vec::new();
+    for question in questions {
+        if !selected_ids.contains(&question.id) {
+            continue;
+        }
+
+        exported_questions.push(export_question(conn, app_handle, app_dir, question, sink)?);
+    }
+
+    if exported_questions.is_empty() {
+        return Err("No active questions matched the export selection".to_string());
+    }
+
+    Ok(QuestionsExportFile {
+        format: "cefr-speaking-questions".to_string(),
+        version: sink.export_version(),
+        exported_at: chrono::Local::now().to_rfc3339(),
+        questions: exported_questions,
+    })
+}
+
+/// Drops repeated IDs while keeping the order the tests were chosen in.
+fn unique_full_test_ids(full_test_ids: Vec<i64>) -> Result<Vec<i64>, String> {
+    let mut seen_ids = HashSet::new();
+    let mut ordered_ids = Vec::new();
+    for full_test_id in full_test_ids {
+        if full_test_id <= 0 {
+            return Err("Invalid full test selected for export".to_string());
+        }
+        if seen_ids.insert(full_test_id) {
+            ordered_ids.push(full_test_id);
+        }
+    }
+
+    if ordered_ids.is_empty() {
+        return Err("Choose at least one full test to export".to_string());
+    }
+
+    Ok(ordered_ids)
+}
+
+fn build_full_tests_export(
+    conn: &Connection,
+    app_handle: &AppHandle,
+    app_dir: &Path,
+    full_test_ids: Vec<i64>,
+    sink: &mut AssetSink,
+) -> Result<FullTestsExportFile, String> {
+    let exported_at = chrono::Local::now().to_rfc3339();
+
+    let mut tests = Vec::new();
+    for full_test_id in full_test_ids {
+        tests.push(build_full_test_export(
+            conn,
+            app_handle,
+            app_dir,
+            full_test_id,
+            &exported_at,
+            sink,
+        )?);
+    }
+
+    Ok(FullTestsExportFile {
+        format: "cefr-speaking-full-tests".to_string(),
+        version: sink.export_version(),
+        exported_at,
+        tests,
+    })
+}
+
+fn import_questions_export(
+    conn: &mut Connection,
+    app_dir: &Path,
+    import_file: QuestionsExportFile,
+    strategy: ImportStrategy,
+    assets: ExportAssets,
+) -> Result<ImportQuestionsResult, String> {
+    if import_file.format != "cefr-speaking-questions" {
+        return Err("This file is not a CEFR speaking questions export".to_string());
+    }
+
+    if import_file.questions.is_empty() {
+        return Err("The selected export file does not contain any questions".to_string());
+    }
+
+    fs::create_dir_all(app_dir).map_err(|e| e.to_string())?;
+
+    for question in &import_file.questions {
+        validate_exported_question(conn, question, &[])?;
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut session = ImportSession::new(app_dir, strategy, assets)?;
+
+    for question in import_file.questions {
+        import_exported_question(&tx, &mut session, question, &[])?;
+    }
+
+    let (_, questions) = session.commit(tx)?;
+    Ok(ImportQuestionsResult {
+        imported: questions.total(),
+        questions,
+    })
+}
+
+fn import_full_test_exports(
+    conn: &mut Connection,
+    app_dir: &Path,
+    tests: Vec<FullTestExportFile>,
+    strategy: ImportStrategy,
+    assets: ExportAssets,
+) -> Result<ImportFullTestResult, String> {
+    fs::create_dir_all(app_dir).map_err(|e| e.to_string())?;
+
+    for test in &tests {
+        validate_full_test_export_file(conn, test)?;
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let mut session = ImportSession::new(app_dir, strategy, assets)?;
+
+    let mut last_full_test_id = 0;
+    for test in tests {
+        last_full_test_id = import_full_test_payload(&tx, &mut session, test)?;
+    }
+
+    let (tests, questions) = session.commit(tx)?;
+    Ok(ImportFullTestResult {
+        imported_tests: tests.total(),
+        imported_questions: questions.total(),
+        full_test_id: last_full_test_id,
+        tests,
+        questions,
+    })
+}
+
+#[tauri::command]
+pub async fn export_questions(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    question_ids: Vec<i64>,
+) -> Result<String, String> {
+    if question_ids.is_empty() {
+        return Err("Choose at least one question to export".to_string());
+    }
+
+    db.run(move |conn| {
+        let app_dir = app_data_dir(&app_handle)?;
+        let export_file = build_questions_export(
+            conn,
+            &app_handle,
+            &app_dir,
+            question_ids,
+            &mut AssetSink::Embedded,
+        )?;
+
+        serde_json::to_string_pretty(&export_file)
+            .map_err(|e| format!("Failed to create questions export: {}", e))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn import_questions(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    export_json: String,
+    strategy: Option<ImportStrategy>,
+) -> Result<ImportQuestionsResult, String> {
+    let import_file: QuestionsExportFile = serde_json::from_str(&export_json)
+        .map_err(|e| format!("This file is not a valid CEFR questions export: {}", e))?;
+
+    let gain_handle = app_handle.clone();
+    let result = db
+        .run(move |conn| {
+            let app_dir = app_data_dir(&app_handle)?;
+            import_questions_export(
+                conn,
+                &app_dir,
+                import_file,
+                strategy.unwrap_or_default(),
+                ExportAssets::Embedded,
+            )
+        })
+        .await?;
+    start_audio_gain_measurement(gain_handle);
+    Ok(result)
+}
+
+#[tauri::command]
+pub async fn export_full_test(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    full_test_id: i64,
+) -> Result<String, String> {
+    db.run(move |conn| {
+        let app_dir = app_data_dir(&app_handle)?;
+
+        let exported_at = chrono::Local::now().to_rfc3339();
+        let export_file = build_full_test_export(
+            conn,
+            &app_handle,
+            &app_dir,
+            full_test_id,
+            &exported_at,
+            &mut AssetSink::Embedded,
+        )?;
+
+        serde_json::to_string_pretty(&export_file)
+            .map_err(|e| format!("Failed to create full test export: {}", e))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn export_full_tests(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    full_test_ids: Vec<i64>,
+) -> Result<String, String> {
+    let ordered_ids = unique_full_test_ids(full_test_ids)?;
+
+    db.run(move |conn| {
+        let app_dir = app_data_dir(&app_handle)?;
+        let export_file = build_full_tests_export(
+            conn,
+            &app_handle,
+            &app_dir,
+            ordered_ids,
+            &mut AssetSink::Embedded,
+        )?;
+
+        serde_json::to_string_pretty(&export_file)
+            .map_err(|e| format!("Failed to create full tests export: {}", e))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn import_full_test(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    export_json: String,
+    strategy: Option<ImportStrategy>,
+) -> Result<ImportFullTestResult, String> {
+    let parsed: serde_json::Value = serde_json::from_str(&export_json)
+        .map_err(|e| format!("This file is not a valid CEFR full test export: {}", e))?;
+
+    let gain_handle = app_handle.clone();
+    let result = db
+        .run(move |conn| {
+            let app_dir = app_data_dir(&app_handle)?;
+            let tests = parse_full_test_exports(parsed)?;
+            import_full_test_exports(
+                conn,
+                &app_dir,
+                tests,
+                strategy.unwrap_or_default(),
+                ExportAssets::Embedded,
+            )
+        })
+        .await?;
+    start_audio_gain_measurement(gain_handle);
+    Ok(result)
+}
+
+/// Asks where to save a package. Returns `None` when the dialog is cancelled.
+#[tauri::command]
+pub async fn pick_export_package_path(
+    app_handle: AppHandle,
+    default_file_name: String,
+) -> Result<Option<String>, String> {
+    let default_file_name = if default_file_name.trim().is_empty() {
+        format!("cefr-export.{}", package::PACKAGE_EXTENSION)
+    } else {
+        default_file_name.trim().to_string()
+    };
+
+    let picked = tauri::async_runtime::spawn_blocking(move || {
+        app_handle
+            .dialog()
+            .file()
+            .set_title("Save export package")
+            .set_file_name(&default_file_name)
+            .add_filter("CEFR package", &[package::PACKAGE_EXTENSION])
+            .blocking_save_file()
+    })
+    .await
+    .map_err(|e| format!("Failed to open the save dialog: {}", e))?;
+
+    picked
+        .map(|path| {
+            path.into_path()
+                .map(|path| path.to_string_lossy().into_owned())
+                .map_err(|e| format!("Invalid export path: {}", e))
+        })
+        .transpose()
+}
+
+/// Asks for a package or legacy JSON export to import. Returns `None` when
+/// the dialog is cancelled.
+#[tauri::command]
+pub async fn pick_import_file(app_handle: AppHandle) -> Result<Option<String>, String> {
+    let picked = tauri::async_runtime::spawn_blocking(move || {
+        app_handle
+            .dialog()
+            .file()
+            .set_title("Choose an export to import")
+            .add_filter("CEFR export", &[package::PACKAGE_EXTENSION, "json"])
+            .blocking_pick_file()
+    })
+    .await
+    .map_err(|e| format!("Failed to open the file dialog: {}", e))?;
+
+    picked
+        .map(|path| {
+            path.into_path()
+                .map(|path| path.to_string_lossy().into_owned())
+                .map_err(|e| format!("Invalid import path: {}", e))
+        })
+        .transpose()
+}
+
+fn package_destination(destination_path: &str) -> Result<PathBuf, String> {
+    let destination_path = destination_path.trim();
+    if destination_path.is_empty() {
+        return Err("Choose where to save the export package".to_string());
+    }
+
+    let destination = PathBuf::from(destination_path);
+    if destination.extension().is_none() {
+        return Ok(destination.with_extension(package::PACKAGE_EXTENSION));
+    }
+    Ok(destination)
+}
+
+fn finish_package<T: Serialize>(
+    writer: PackageWriter,
+    destination: &Path,
+    manifest: &T,
+) -> Result<u64, String> {
+    let manifest = serde_json::to_vec_pretty(manifest)
+        .map_err(|e| format!("Failed to create package manifest: {}", e))?;
+    let size_bytes = writer.finish(&manifest)?;
+    println!(
+        "Saved export package {:?} ({} bytes)",
+        destination, size_bytes
+    );
+    Ok(size_bytes)
+}
+
+/// Writes the selected questions to a `.cefrpkg` package at `destination_path`.
+#[tauri::command]
+pub async fn export_questions_package(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    question_ids: Vec<i64>,
+    destination_path: String,
+) -> Result<ExportPackageResult, String> {
+    if question_ids.is_empty() {
+        return Err("Choose at least one question to export".to_string());
+    }
+    let destination = package_destination(&destination_path)?;
+
+    db.run(move |conn| {
+        let app_dir = app_data_dir(&app_handle)?;
+        let mut writer = PackageWriter::create(&destination)?;
+        let export_file = build_questions_export(
+            conn,
+            &app_handle,
+            &app_dir,
+            question_ids,
+            &mut AssetSink::Package(&mut writer),
+        )?;
+        let size_bytes = finish_package(writer, &destination, &export_file)?;
+
+        Ok(ExportPackageResult {
+            path: destination.to_string_lossy().into_owned(),
+            size_bytes,
+            tests: 0,
+            questions: export_file.questions.len(),
+        })
+    })
+    .await
+}
+
+/// Writes the selected full tests to a `.cefrpkg` package at `destination_path`.
+#[tauri::command]
+pub async fn export_full_tests_package(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    full_test_ids: Vec<i64>,
+    destination_path: String,
+) -> Result<ExportPackageResult, String> {
+    let ordered_ids = unique_full_test_ids(full_test_ids)?;
+    let destination = package_destination(&destination_path)?;
+
+    db.run(move |conn| {
+        let app_dir = app_data_dir(&app_handle)?;
+        let mut writer = PackageWriter::create(&destination)?;
+        let export_file = build_full_tests_export(
+            conn,
+            &app_handle,
+            &app_dir,
+            ordered_ids,
+            &mut AssetSink::Package(&mut writer),
+        )?;
+        let size_bytes = finish_package(writer, &destination, &export_file)?;
+
+        Ok(ExportPackageResult {
+            path: destination.to_string_lossy().into_owned(),
+            size_bytes,
+            tests: export_file.tests.len(),
+            questions: export_file
+                .tests
+                .iter()
+                .map(|test| test.questions.len())
+                .sum(),
+        })
+    })
+    .await
+}
+
+/// Opens an export file from disk. Packages yield their manifest and a
+/// reader for the asset entries; anything else is read as a legacy JSON
+/// export with embedded assets.
+fn open_export_file(source_path: &str) -> Result<(String, ExportAssets), String> {
+    let source_path = source_path.trim();
+    if source_path.is_empty() {
+        return Err("Choose an export file to import".to_string());
+    }
+
+    let source = Path::new(source_path);
+    if package::is_package(source)? {
+        let mut reader = PackageReader::open(source)?;
+        let manifest = reader.read_manifest()?;
+        return Ok((manifest, ExportAssets::Package(reader)));
+    }
+
+    let export_json = fs::read_to_string(source)
+        .map_err(|e| format!("Failed to read export file {}: {}", source_path, e))?;
+    Ok((export_json, ExportAssets::Embedded))
+}
+
+/// Imports a `.cefrpkg` package or a legacy JSON export of questions or
+/// full tests from `source_path`.
+#[tauri::command]
+pub async fn import_export_file(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    source_path: String,
+    strategy: Option<ImportStrategy>,
+) -> Result<ImportFullTestResult, String> {
+    let strategy = strategy.unwrap_or_default();
+
+    let gain_handle = app_handle.clone();
+    let result = db
+        .run(move |conn| {
+            let app_dir = app_data_dir(&app_handle)?;
+            let (export_json, assets) = open_export_file(&source_path)?;
+            let parsed: serde_json::Value = serde_json::from_str(&export_json)
+                .map_err(|e| format!("This file is not a valid CEFR export: {}", e))?;
+
+            let is_questions_export = parsed
+                .get("format")
+                .and_then(|value| value.as_str())
+                .is_some_and(|format| format == "cefr-speaking-questions");
+            if !is_questions_export {
+                let tests = parse_full_test_exports(parsed)?;
+                return import_full_test_exports(conn, &app_dir, tests, strategy, assets);
+            }
+
+            let import_file: QuestionsExportFile = serde_json::from_value(parsed)
+                .map_err(|e| format!("This file is not a valid CEFR questions export: {}", e))?;
+            let result = import_questions_export(conn, &app_dir, import_file, strategy, assets)?;
+            Ok(ImportFullTestResult {
+                imported_tests: 0,
+                imported_questions: result.imported,
+                full_test_id: 0,
+                tests: ImportCounts::default(),
+                questions: result.questions,
             })
         })
+        .await?;
+    start_audio_gain_measurement(gain_handle);
+    Ok(result)
+}
+
+/// Reads a `cefr-speaking-full-test` or `cefr-speaking-full-tests` export
+/// into its list of tests.
+fn parse_full_test_exports(parsed: serde_json::Value) -> Result<Vec<FullTestExportFile>, String> {
+    let export_format = parsed
+        .get("format")
+        .and_then(|value| value.as_str())
+        .unwrap_or_default();
+
+    if export_format == "cefr-speaking-full-test" {
+        let import_file: FullTestExportFile = serde_json::from_value(parsed)
+            .map_err(|e| format!("This file is not a valid CEFR full test export: {}", e))?;
+        return Ok(vec![import_file]);
+    }
+
+    if export_format != "cefr-speaking-full-tests" {
+        return Err("This file is not a CEFR speaking full test export".to_string());
+    }
+
+    let import_file: FullTestsExportFile = serde_json::from_value(parsed)
+        .map_err(|e| format!("This file is not a valid CEFR full tests export: {}", e))?;
+
+    if import_file.tests.is_empty() {
+        return Err("The selected full tests export does not contain any tests".to_string());
+    }
+
+    Ok(import_file.tests)
+}
+
+fn planned_import_action(strategy: ImportStrategy, existing_id: Option<i64>) -> &'static str {
+    match (existing_id, strategy) {
+        (Some(_), ImportStrategy::Skip) => "reuse",
+        (Some(_), ImportStrategy::Update) => "update",
+        _ => "create",
+    }
+}
+
+fn count_import_action(counts: &mut ImportCounts, action: &str) {
+    match action {
+        "create" => counts.created += 1,
+        "reuse" => counts.reused += 1,
+        "update" => counts.updated += 1,
+        _ => {}
+    }
+}
+
+/// Checks one asset of an exported question. Returns the report entry and,
+/// for a corrupt asset, the error that would make the import fail.
+fn preview_exported_asset(
+    conn: &Connection,
+    app_dir: &Path,
+    assets: &mut ExportAssets,
+    folder: &str,
+    referenced_path: &str,
+    asset: Option<&ExportedAsset>,
+) -> Result<(Option<ImportPreviewAsset>, Option<String>), String> {
+    let Some(exported) = asset else {
+        if referenced_path.trim().is_empty() {
+            return Ok((None, None));
+        }
+        return Ok((
+            Some(ImportPreviewAsset {
+                folder: folder.to_string(),
+                filename: referenced_path.trim().to_string(),
+                status: "missing".to_string(),
+                sha256: String::new(),
+                size_bytes: 0,
+                stored_filename: None,
+            }),
+            None,
+        ));
+    };
+
+    let decoded = match decode_exported_asset(assets, folder, Some(exported)) {
+        Ok(Some(decoded)) => decoded,
+        Ok(None) => return Ok((None, None)),
+        Err(error) => {
+            return Ok((
+                Some(ImportPreviewAsset {
+                    folder: folder.to_string(),
+                    filename: exported.filename.clone(),
+                    status: "corrupt".to_string(),
+                    sha256: exported.sha256.clone(),
+                    size_bytes: 0,
+                    stored_filename: None,
+                }),
+                Some(error),
+            ));
+        }
+    };
+
+    let stored_filename =
+        stored_content_asset(conn, &app_dir.join(folder), folder, &decoded.sha256)?;
+    let status = if stored_filename.is_some() {
+        "stored"
+    } else {
+        "new"
+    };
+
+    Ok((
+        Some(ImportPreviewAsset {
+            folder: folder.to_string(),
+            filename: decoded.filename,
+            status: status.to_string(),
+            sha256: decoded.sha256,
+            size_bytes: decoded.data.len(),
+            stored_filename,
+        }),
+        None,
+    ))
+}
+
+fn similar_question_ids(
+    conn: &Connection,
+    question: &ExportedQuestion,
+    stored_image: Option<&str>,
+    exclude_id: Option<i64>,
+) -> Result<Vec<i64>, String> {
+    let text = question.text.trim();
+    let image = stored_image.unwrap_or_default();
+    if text.is_empty() && image.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM questions
+             WHERE active = 1 AND part = ? AND sub_part = ?
+               AND ((? <> '' AND TRIM(text) = ?) OR (? <> '' AND image_path = ?))
+             ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map(
+            params![question.part, question.sub_part, text, text, image, image],
+            |row| row.get::<_, i64>(0),
+        )
         .map_err(|e| e.to_string())?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| e.to_string())?;
 
-    Ok(questions)
+    Ok(ids
+        .into_iter()
+        .filter(|id| Some(*id) != exclude_id)
+        .collect())
 }
 
-#[tauri::command]
-pub async fn create_full_test(
-    app_handle: AppHandle,
-    name: String,
-    question_ids: Vec<i64>,
-) -> Result<i64, String> {
-    let trimmed_name = name.trim();
-    if trimmed_name.is_empty() {
-        return Err("Full test name cannot be empty".to_string());
+fn preview_exported_question(
+    conn: &Connection,
+    app_dir: &Path,
+    export_assets: &mut ExportAssets,
+    strategy: ImportStrategy,
+    question: &ExportedQuestion,
+    test: Option<(i64, &[BlueprintSection])>,
+    seen_uuids: &mut HashSet<String>,
+) -> Result<ImportPreviewQuestion, String> {
+    // A full test brings the sections of an exam format it may set up.
+    let (test_source_id, incoming) = match test {
+        Some((source_id, incoming)) => (Some(source_id), incoming),
+        None => (None, &[][..]),
+    };
+    let mut errors = exported_question_problems(conn, question, incoming)?;
+    let mut warnings = Vec::new();
+    let mut assets = Vec::new();
+    let mut asset_hashes = Vec::new();
+
+    for (folder, referenced_path, asset) in [
+        ("audio", &question.audio_path, question.audio_file.as_ref()),
+        ("images", &question.image_path, question.image_file.as_ref()),
+    ] {
+        let (asset_preview, problem) =
+            preview_exported_asset(conn, app_dir, export_assets, folder, referenced_path, asset)?;
+        if let Some(problem) = problem {
+            errors.push(problem);
+        }
+
+        let sha256 = match &asset_preview {
+            Some(asset_preview) if asset_preview.status == "missing" => {
+                warnings.push(format!(
+                    "Question {} refers to {} file {} but the export does not include it",
+                    question.source_id, folder, asset_preview.filename
+                ));
+                None
+            }
+            Some(asset_preview) if !asset_preview.sha256.is_empty() => {
+                Some(asset_preview.sha256.clone())
+            }
+            _ => None,
+        };
+        asset_hashes.push(sha256);
+        assets.extend(asset_preview);
     }
 
-    let conn = init_db(&app_handle)?;
-    validate_full_test_questions(&conn, &question_ids)?;
+    let uuid = if question.uuid.trim().is_empty() {
+        derived_question_uuid(
+            question,
+            asset_hashes[0].as_deref(),
+            asset_hashes[1].as_deref(),
+        )
+    } else {
+        question.uuid.trim().to_string()
+    };
 
-    conn.execute(
-        "INSERT INTO full_tests (name, active) VALUES (?, 1)",
-        params![trimmed_name],
-    )
-    .map_err(|e| e.to_string())?;
+    let existing_question_id = conn
+        .query_row(
+            "SELECT id FROM questions WHERE uuid = ?",
+            params![uuid],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
 
-    let full_test_id = conn.last_insert_rowid();
-    for (index, question_id) in question_ids.iter().enumerate() {
-        conn.execute(
-            "INSERT INTO full_test_questions (full_test_id, question_id, position)
-             VALUES (?, ?, ?)",
-            params![full_test_id, question_id, (index as i32) + 1],
+    let stored_image = assets
+        .iter()
+        .find(|asset| asset.folder == "images")
+        .and_then(|asset| asset.stored_filename.as_deref());
+    let similar_question_ids =
+        similar_question_ids(conn, question, stored_image, existing_question_id)?;
+
+    let action = if seen_uuids.insert(uuid.clone()) {
+        planned_import_action(strategy, existing_question_id)
+    } else {
+        "repeat"
+    };
+
+    Ok(ImportPreviewQuestion {
+        source_id: question.source_id,
+        test_source_id,
+        uuid,
+        section: incoming
+            .iter()
+            .find(|section| section.part == question.part && section.sub_part == question.sub_part)
+            .map(|section| Ok(section.label.clone()))
+            .unwrap_or_else(|| section_label(conn, None, question.part, question.sub_part))?,
+        action: action.to_string(),
+        existing_question_id,
+        similar_question_ids,
+        assets,
+        errors,
+        warnings,
+    })
+}
+
+fn preview_full_test(
+    conn: &Connection,
+    app_dir: &Path,
+    assets: &mut ExportAssets,
+    preview: &mut ImportPreview,
+    test: &FullTestExportFile,
+    seen_uuids: &mut HashSet<String>,
+) -> Result<(), String> {
+    let blueprint = import_blueprint(conn, test.blueprint.as_ref()).ok();
+    let incoming = blueprint
+        .as_ref()
+        .map(incoming_sections)
+        .unwrap_or_default();
+
+    let mut question_uuids = Vec::new();
+    for question in &test.questions {
+        let question_preview = preview_exported_question(
+            conn,
+            app_dir,
+            assets,
+            preview.strategy,
+            question,
+            Some((test.source_id, incoming)),
+            seen_uuids,
+        )?;
+        question_uuids.push(question_preview.uuid.clone());
+        preview.questions.push(question_preview);
+    }
+
+    let name = test.name.trim().to_string();
+    let uuid = if test.uuid.trim().is_empty() {
+        derived_uuid(&format!("{}\n{}", name, question_uuids.join("\n")))
+    } else {
+        test.uuid.trim().to_string()
+    };
+    let existing_full_test_id = conn
+        .query_row(
+            "SELECT id FROM full_tests WHERE uuid = ?",
+            params![uuid],
+            |row| row.get::<_, i64>(0),
         )
+        .optional()
         .map_err(|e| e.to_string())?;
+
+    preview.tests.push(ImportPreviewTest {
+        source_id: test.source_id,
+        uuid,
+        name,
+        action: planned_import_action(preview.strategy, existing_full_test_id).to_string(),
+        existing_full_test_id,
+        question_count: test.questions.len(),
+        blueprint_name: blueprint
+            .as_ref()
+            .map(|blueprint| blueprint.definition.name.clone())
+            .unwrap_or_default(),
+        creates_blueprint: blueprint
+            .as_ref()
+            .is_some_and(|blueprint| blueprint.id.is_none()),
+        errors: full_test_export_problems(conn, test)?,
+    });
+
+    Ok(())
+}
+
+fn build_import_preview(
+    conn: &Connection,
+    app_dir: &Path,
+    export_json: &str,
+    assets: &mut ExportAssets,
+    strategy: ImportStrategy,
+) -> Result<ImportPreview, String> {
+    let mut preview = ImportPreview {
+        format: String::new(),
+        strategy,
+        valid: false,
+        errors: Vec::new(),
+        tests: Vec::new(),
+        questions: Vec::new(),
+        test_counts: ImportCounts::default(),
+        question_counts: ImportCounts::default(),
+    };
+
+    let parsed: serde_json::Value = match serde_json::from_str(export_json) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            preview
+                .errors
+                .push(format!("This file is not a valid CEFR export: {}", error));
+            return Ok(preview);
+        }
+    };
+    preview.format = parsed
+        .get("format")
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    let mut seen_uuids = HashSet::new();
+    if preview.format == "cefr-speaking-questions" {
+        match serde_json::from_value::<QuestionsExportFile>(parsed) {
+            Ok(import_file) => {
+                if import_file.questions.is_empty() {
+                    preview.errors.push(
+                        "The selected export file does not contain any questions".to_string(),
+                    );
+                }
+                for question in &import_file.questions {
+                    let question_preview = preview_exported_question(
+                        conn,
+                        app_dir,
+                        assets,
+                        strategy,
+                        question,
+                        None,
+                        &mut seen_uuids,
+                    )?;
+                    preview.questions.push(question_preview);
+                }
+            }
+            Err(error) => preview.errors.push(format!(
+                "This file is not a valid CEFR questions export: {}",
+                error
+            )),
+        }
+    } else {
+        match parse_full_test_exports(parsed) {
+            Ok(tests) => {
+                for test in &tests {
+                    preview_full_test(conn, app_dir, assets, &mut preview, test, &mut seen_uuids)?;
+                }
+            }
+            Err(error) => preview.errors.push(error),
+        }
     }
 
-    Ok(full_test_id)
+    for test in &preview.tests {
+        count_import_action(&mut preview.test_counts, &test.action);
+    }
+    for question in &preview.questions {
+        count_import_action(&mut preview.question_counts, &question.action);
+    }
+
+    preview.valid = preview.errors.is_empty()
+        && preview.tests.iter().all(|test| test.errors.is_empty())
+        && preview
+            .questions
+            .iter()
+            .all(|question| question.errors.is_empty());
+
+    Ok(preview)
 }
 
+/// Previews an import of `export_json`, or of the package or JSON export at
+/// `source_path` when one is given.
 #[tauri::command]
-pub async fn get_full_tests(app_handle: AppHandle) -> Result<Vec<FullTest>, String> {
-    let conn = init_db(&app_handle)?;
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, name, created_at
-             FROM full_tests
-             WHERE active = 1
-             ORDER BY created_at DESC, id DESC",
+pub async fn preview_import(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    export_json: Option<String>,
+    source_path: Option<String>,
+    strategy: Option<ImportStrategy>,
+) -> Result<ImportPreview, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    let strategy = strategy.unwrap_or_default();
+
+    db.run(move |conn| {
+        let (export_json, mut assets) = match (source_path, export_json) {
+            (Some(source_path), _) => open_export_file(&source_path)?,
+            (None, Some(export_json)) => (export_json, ExportAssets::Embedded),
+            (None, None) => return Err("Choose an export file to preview".to_string()),
+        };
+        build_import_preview(conn, &app_dir, &export_json, &mut assets, strategy)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn delete_question(db: State<'_, Db>, question_id: i64) -> Result<String, String> {
+    db.run(move |conn| {
+        conn.execute(
+            "UPDATE questions SET active = 0 WHERE id = ?",
+            [question_id],
         )
         .map_err(|e| e.to_string())?;
 
-    let test_rows = stmt
-        .query_map([], |row| {
-            Ok((
-                row.get::<_, i64>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-            ))
-        })
-        .map_err(|e| e.to_string())?;
+        println!("Question {} deactivated successfully", question_id);
+        Ok(format!("Question {} deactivated", question_id))
+    })
+    .await
+}
 
-    let mut tests = Vec::new();
-    for row in test_rows {
-        let (id, name, created_at) = row.map_err(|e| e.to_string())?;
-        let mut question_stmt = conn
-            .prepare(
-                "SELECT questions.id,
-                        questions.part,
-                        questions.sub_part,
-                        questions.audio_path,
-                        questions.image_path,
-                        questions.text,
-                        questions.pack_id,
-                        questions.pack_order,
-                        questions.response_time,
-                        questions.active
-                 FROM full_test_questions
-                 JOIN questions ON full_test_questions.question_id = questions.id
-                 WHERE full_test_questions.full_test_id = ? AND questions.active = 1
-                 ORDER BY questions.part ASC,
-                          questions.sub_part ASC,
-                          CASE WHEN questions.pack_order > 0 THEN questions.pack_order ELSE 999999 END ASC,
-                          full_test_questions.position ASC,
-                          questions.id ASC",
-            )
-            .map_err(|e| e.to_string())?;
+const RECORDING_SELECT_SQL: &str = "SELECT responses.id,
+        responses.attempt_id,
+        attempts.student_name,
+        attempts.started_at,
+        responses.question_id,
+        COALESCE(question_revisions.part, questions.part),
+        COALESCE(question_revisions.sub_part, questions.sub_part),
+        COALESCE(question_revisions.text, questions.text),
+        responses.recorded_at,
+        responses.duration,
+        ratings.response_id,
+        ratings.fluency,
+        ratings.lexical,
+        ratings.grammar,
+        ratings.pronunciation,
+        ratings.comment,
+        attempt_questions.served_at,
+        responses.claimed_duration,
+        responses.audio_duration_ms,
+        responses.timing_flags
+ FROM responses
+ JOIN attempts ON responses.attempt_id = attempts.id
+ JOIN questions ON responses.question_id = questions.id
+ LEFT JOIN question_revisions ON responses.question_revision_id = question_revisions.id
+ LEFT JOIN attempt_questions ON attempt_questions.attempt_id = responses.attempt_id
+     AND attempt_questions.question_id = responses.question_id
+ LEFT JOIN ratings ON ratings.response_id = responses.id";
+
+/// Rounds the mean of `count` criterion scores adding up to `total` to the
+/// nearest half band. Quarter marks round up, as in IELTS speaking: a mean of
+/// 6.25 becomes 6.5 and 6.75 becomes 7.0. The rounding is done in integers so
+/// means such as 6.25 are never nudged the wrong way by float error.
+fn band_score(total: i64, count: i64) -> Option<f64> {
+    if count <= 0 {
+        return None;
+    }
+    let half_bands = (4 * total + count) / (2 * count);
+    Some(half_bands as f64 / 2.0)
+}
 
-        let questions = question_stmt
-            .query_map(params![id], question_from_row)
-            .map_err(|e| e.to_string())?
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|e| e.to_string())?;
+fn criterion_average(values: &[Option<i32>]) -> Option<f64> {
+    let scores = values.iter().flatten().collect::<Vec<_>>();
+    if scores.is_empty() {
+        return None;
+    }
+    let total: i64 = scores.iter().map(|value| **value as i64).sum();
+    Some(total as f64 / scores.len() as f64)
+}
 
-        tests.push(FullTest {
-            id,
-            name,
-            created_at,
-            questions,
-        });
+fn rating_from_values(
+    response_id: i64,
+    fluency: Option<i32>,
+    lexical: Option<i32>,
+    grammar: Option<i32>,
+    pronunciation: Option<i32>,
+    comment: Option<String>,
+) -> Rating {
+    let criteria = [fluency, lexical, grammar, pronunciation];
+    let total: i64 = criteria.iter().flatten().map(|value| *value as i64).sum();
+    let count = criteria.iter().flatten().count() as i64;
+
+    Rating {
+        response_id,
+        fluency,
+        lexical,
+        grammar,
+        pronunciation,
+        comment: comment.unwrap_or_default(),
+        score: band_score(total, count),
     }
+}
 
-    Ok(tests)
+fn recording_from_row(row: &rusqlite::Row) -> rusqlite::Result<Recording> {
+    let rated_response_id: Option<i64> = row.get(10)?;
+    let rating = match rated_response_id {
+        Some(response_id) => Some(rating_from_values(
+            response_id,
+            row.get(11)?,
+            row.get(12)?,
+            row.get(13)?,
+            row.get(14)?,
+            row.get(15)?,
+        )),
+        None => None,
+    };
+
+    Ok(Recording {
+        id: row.get(0)?,
+        attempt_id: row.get(1)?,
+        student_name: row.get(2)?,
+        attempt_started_at: row.get(3)?,
+        question_id: row.get(4)?,
+        part: row.get(5)?,
+        sub_part: row.get(6)?,
+        question_text: row.get(7)?,
+        served_at: row.get(16)?,
+        recorded_at: row.get(8)?,
+        duration: row.get(9)?,
+        claimed_duration: row.get(17)?,
+        audio_duration_ms: row.get(18)?,
+        timing_flags: split_timing_flags(&row.get::<_, String>(19)?),
+        rating,
+    })
+}
+
+fn split_timing_flags(flags: &str) -> Vec<String> {
+    flags
+        .split(',')
+        .filter(|flag| !flag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn validate_rating_score(label: &str, value: i32) -> Result<(), String> {
+    if !(1..=9).contains(&value) {
+        return Err(format!("{} score must be between 1 and 9", label));
+    }
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn delete_full_test(app_handle: AppHandle, full_test_id: i64) -> Result<(), String> {
-    let conn = init_db(&app_handle)?;
+pub async fn rate_response(
+    db: State<'_, Db>,
+    response_id: i64,
+    fluency: i32,
+    lexical: i32,
+    grammar: i32,
+    pronunciation: i32,
+    comment: String,
+) -> Result<(), String> {
+    validate_rating_score("Fluency", fluency)?;
+    validate_rating_score("Lexical", lexical)?;
+    validate_rating_score("Grammar", grammar)?;
+    validate_rating_score("Pronunciation", pronunciation)?;
 
-    conn.execute(
-        "UPDATE full_tests SET active = 0 WHERE id = ?",
-        params![full_test_id],
-    )
-    .map_err(|e| e.to_string())?;
+    db.run(move |conn| {
+        let response_exists: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM responses WHERE id = ?",
+                params![response_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if response_exists.is_none() {
+            return Err(format!("Response {} does not exist", response_id));
+        }
 
-    Ok(())
+        conn.execute(
+            "INSERT OR REPLACE INTO ratings (response_id, fluency, lexical, grammar, pronunciation, comment)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![response_id, fluency, lexical, grammar, pronunciation, comment],
+        ).map_err(|e| e.to_string())?;
+
+        Ok(())
+    })
+    .await
 }
 
-fn build_full_test_export(
-    conn: &Connection,
-    app_handle: &AppHandle,
-    app_dir: &Path,
-    full_test_id: i64,
-    exported_at: &str,
-) -> Result<FullTestExportFile, String> {
-    let (source_id, name, created_at): (i64, String, String) = conn
-        .query_row(
-            "SELECT id, name, created_at
-             FROM full_tests
-             WHERE id = ? AND active = 1",
-            params![full_test_id],
-            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+#[tauri::command]
+pub async fn get_response_rating(
+    db: State<'_, Db>,
+    response_id: i64,
+) -> Result<Option<Rating>, String> {
+    db.run(move |conn| {
+        conn.query_row(
+            "SELECT response_id, fluency, lexical, grammar, pronunciation, comment
+             FROM ratings WHERE response_id = ?",
+            params![response_id],
+            |row| {
+                Ok(rating_from_values(
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            },
         )
         .optional()
-        .map_err(|e| e.to_string())?
-        .ok_or_else(|| format!("Full test {} not found or inactive", full_test_id))?;
+        .map_err(|e| e.to_string())
+    })
+    .await
+}
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT questions.id,
-                    questions.part,
-                    questions.sub_part,
-                    questions.audio_path,
-                    questions.image_path,
-                    questions.text,
-                    questions.pack_id,
-                    questions.pack_order,
-                    questions.response_time,
-                    questions.active
-             FROM full_test_questions
-             JOIN questions ON full_test_questions.question_id = questions.id
-             WHERE full_test_questions.full_test_id = ? AND questions.active = 1
-             ORDER BY full_test_questions.position ASC",
-        )
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn get_attempt_ratings(
+    db: State<'_, Db>,
+    attempt_id: i64,
+) -> Result<AttemptRatings, String> {
+    db.run(move |conn| {
+        let student_name: String = conn
+            .query_row(
+                "SELECT student_name FROM attempts WHERE id = ?",
+                params![attempt_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Attempt {} does not exist", attempt_id))?;
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "{} WHERE responses.attempt_id = ? ORDER BY responses.recorded_at ASC, responses.id ASC",
+                RECORDING_SELECT_SQL
+            ))
+            .map_err(|e| e.to_string())?;
+
+        let recordings = stmt
+            .query_map(params![attempt_id], recording_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+
+        let ratings = recordings
+            .iter()
+            .filter_map(|recording| recording.rating.as_ref())
+            .collect::<Vec<_>>();
+        let fluency = ratings
+            .iter()
+            .map(|rating| rating.fluency)
+            .collect::<Vec<_>>();
+        let lexical = ratings
+            .iter()
+            .map(|rating| rating.lexical)
+            .collect::<Vec<_>>();
+        let grammar = ratings
+            .iter()
+            .map(|rating| rating.grammar)
+            .collect::<Vec<_>>();
+        let pronunciation = ratings
+            .iter()
+            .map(|rating| rating.pronunciation)
+            .collect::<Vec<_>>();
+
+        let all_scores = [&fluency, &lexical, &grammar, &pronunciation]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect::<Vec<_>>();
+        let total: i64 = all_scores.iter().map(|value| **value as i64).sum();
+
+        Ok(AttemptRatings {
+            attempt_id,
+            student_name,
+            total_responses: recordings.len(),
+            rated_responses: ratings.len(),
+            fluency: criterion_average(&fluency),
+            lexical: criterion_average(&lexical),
+            grammar: criterion_average(&grammar),
+            pronunciation: criterion_average(&pronunciation),
+            overall_score: band_score(total, all_scores.len() as i64),
+            recordings,
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_unrated_recordings(db: State<'_, Db>) -> Result<Vec<Recording>, String> {
+    db.run(move |conn| {
+        let mut stmt = conn
+            .prepare(&format!(
+                "{} WHERE ratings.response_id IS NULL
+                 ORDER BY attempts.started_at DESC, responses.recorded_at ASC",
+                RECORDING_SELECT_SQL
+            ))
+            .map_err(|e| e.to_string())?;
+
+        let recordings = stmt
+            .query_map([], recording_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
 
-    let question_rows = stmt
-        .query_map(params![full_test_id], question_from_row)
-        .map_err(|e| e.to_string())?;
+        Ok(recordings)
+    })
+    .await
+}
 
-    let mut exported_questions = Vec::new();
-    for row in question_rows {
-        let question = row.map_err(|e| e.to_string())?;
-        exported_questions.push(ExportedQuestion {
-            source_id: question.id,
-            part: question.part,
-            sub_part: question.sub_part,
-            audio_path: question.audio_path.clone(),
-            image_path: question.image_path.clone(),
-            text: question.text.clone(),
-            pack_id: question.pack_id.clone(),
-            pack_order: question.pack_order,
-            response_time: question.response_time,
-            audio_file: read_exported_asset(app_handle, app_dir, "audio", &question.audio_path)?,
-            image_file: read_exported_asset(app_handle, app_dir, "images", &question.image_path)?,
-        });
-    }
+/// Stores an uploaded prompt image and returns its asset ID. Large images
+/// are scaled down and metadata is removed first, see
+/// `media::prepare_image`.
+#[tauri::command]
+pub async fn save_image_file(
+    app_handle: AppHandle,
+    db: State<'_, Db>,
+    filename: String,
+    image_data: Vec<u8>,
+) -> Result<String, String> {
+    let app_dir = app_data_dir(&app_handle)?;
 
-    if exported_questions.is_empty() {
-        return Err(format!(
-            "Full test \"{}\" does not contain any active questions",
-            name
-        ));
-    }
-    validate_exported_full_test_sections(&exported_questions)?;
+    let (filename, prepared) = prepare_upload(filename, image_data, media::prepare_image).await?;
+    let store_dir = app_dir.clone();
+    let asset_id = db
+        .run(move |conn| store_asset(conn, &store_dir, "images", &filename, &prepared.data))
+        .await
+        .map_err(|error| {
+            println!("Error: {}", error);
+            error
+        })?;
 
-    Ok(FullTestExportFile {
-        format: "cefr-speaking-full-test".to_string(),
-        version: 1,
-        exported_at: exported_at.to_string(),
-        source_id,
-        name,
-        created_at,
-        questions: exported_questions,
+    let thumbnail_id = asset_id.clone();
+    let _ =
+        tauri::async_runtime::spawn_blocking(move || prepare_thumbnail(&app_dir, &thumbnail_id))
+            .await;
+    Ok(asset_id)
+}
+
+/// Reads a prompt image by asset ID.
+#[tauri::command]
+pub async fn get_image_file(app_handle: AppHandle, asset_id: String) -> Result<Vec<u8>, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+
+    assets::read(&asset_roots(&app_handle, &app_dir, "images"), &asset_id).map_err(|e| {
+        let err_msg = format!("Failed to read image file: {}", e);
+        println!("Error: {}", err_msg);
+        err_msg
     })
 }
 
-fn import_full_test_payload(
-    conn: &Connection,
+/// The thumbnail of image `asset_id`, stored at `source`, and its MIME type.
+/// It is made when missing and made again when older than the image, so an
+/// image replaced under the same name does not keep its old preview.
+fn ensure_thumbnail(
     app_dir: &Path,
-    import_file: FullTestExportFile,
-) -> Result<ImportFullTestResult, String> {
-    let trimmed_name = validate_full_test_export_file(&import_file)?;
-
-    let mut source_to_imported = HashMap::new();
-    let mut imported_question_ids = Vec::new();
-    for question in import_file.questions {
-        let source_id = question.source_id;
-        let imported_id = insert_exported_question(&conn, &app_dir, question)?;
-        source_to_imported.insert(source_id, imported_id);
-        imported_question_ids.push(imported_id);
+    asset_id: &str,
+    source: &Path,
+) -> Result<(PathBuf, &'static str), String> {
+    let thumbnail_dir = app_dir.join(THUMBNAIL_DIR);
+    let thumbnail_path = |format: media::MediaFormat| {
+        thumbnail_dir.join(format!("{}.{}", asset_id, format.extension))
+    };
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    let source_modified =
+        modified(source).ok_or_else(|| format!("Image {} was not found", asset_id))?;
+    for format in [media::JPEG, media::PNG] {
+        let path = thumbnail_path(format);
+        if modified(&path).is_some_and(|made| made >= source_modified) {
+            return Ok((path, format.mime));
+        }
     }
 
-    validate_full_test_questions(&conn, &imported_question_ids)?;
+    let data = fs::read(source).map_err(|e| format!("Failed to read image {}: {}", asset_id, e))?;
+    let thumbnail = media::thumbnail(&data)?;
+    fs::create_dir_all(&thumbnail_dir).map_err(|e| e.to_string())?;
 
-    conn.execute(
-        "INSERT INTO full_tests (name, active) VALUES (?, 1)",
-        params![trimmed_name],
-    )
-    .map_err(|e| e.to_string())?;
+    // Each call writes under its own name, so two requests for the same
+    // image never write into one file.
+    let path = thumbnail_path(thumbnail.format);
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(12)
+        .map(char::from)
+        .collect();
+    let partial = thumbnail_dir.join(format!("{}.{}.partial", asset_id, suffix));
+    fs::write(&partial, &thumbnail.data)
+        .and_then(|_| fs::rename(&partial, &path))
+        .map_err(|e| {
+            let _ = fs::remove_file(&partial);
+            format!("Failed to save the thumbnail of image {}: {}", asset_id, e)
+        })?;
 
-    let full_test_id = conn.last_insert_rowid();
-    for (index, question_id) in imported_question_ids.iter().enumerate() {
-        conn.execute(
-            "INSERT INTO full_test_questions (full_test_id, question_id, position)
-             VALUES (?, ?, ?)",
-            params![full_test_id, question_id, (index as i32) + 1],
-        )
-        .map_err(|e| e.to_string())?;
+    // A thumbnail in the other format is left from an earlier image under
+    // this name, unless another request has just made it.
+    for format in [media::JPEG, media::PNG] {
+        let other = thumbnail_path(format);
+        if format != thumbnail.format && modified(&other).is_some_and(|made| made < source_modified)
+        {
+            let _ = fs::remove_file(other);
+        }
     }
+    Ok((path, thumbnail.format.mime))
+}
 
-    Ok(ImportFullTestResult {
-        imported_tests: 1,
-        imported_questions: source_to_imported.len(),
-        full_test_id,
-    })
+/// Makes the thumbnail of an image that was just stored, so the question
+/// list does not have to. A failure is only logged: the thumbnail is then
+/// made when it is first asked for.
+fn prepare_thumbnail(app_dir: &Path, asset_id: &str) {
+    let source = app_dir.join("images").join(asset_id);
+    if let Err(error) = ensure_thumbnail(app_dir, asset_id, &source) {
+        println!(
+            "Warning: failed to make a thumbnail of image {}: {}",
+            asset_id, error
+        );
+    }
 }
 
+/// Reads the thumbnail of a prompt image by asset ID, for lists that would
+/// be slow to fill with full-size images.
 #[tauri::command]
-pub async fn export_questions(
+pub async fn get_image_thumbnail(
     app_handle: AppHandle,
-    question_ids: Vec<i64>,
-) -> Result<String, String> {
-    if question_ids.is_empty() {
-        return Err("Choose at least one question to export".to_string());
-    }
-
-    let conn = init_db(&app_handle)?;
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    let selected_ids: HashSet<i64> = question_ids.into_iter().collect();
-
-    let mut stmt = conn.prepare(
-        "SELECT id, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time, active
-         FROM questions WHERE active = 1 ORDER BY part, sub_part, id"
-    ).map_err(|e| e.to_string())?;
+    asset_id: String,
+) -> Result<ImageThumbnail, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    let roots = asset_roots(&app_handle, &app_dir, "images");
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let source = assets::resolve(&roots, &asset_id)?;
+        let (path, mime) = ensure_thumbnail(&app_dir, &asset_id, &source)?;
+        let data = fs::read(&path)
+            .map_err(|e| format!("Failed to read the thumbnail of image {}: {}", asset_id, e))?;
+        Ok(ImageThumbnail {
+            mime: mime.to_string(),
+            data,
+        })
+    })
+    .await
+    .map_err(|e| format!("Failed to make a thumbnail: {}", e))?
+    .map_err(|error: String| {
+        let err_msg = format!("Failed to read image thumbnail: {}", error);
+        println!("Error: {}", err_msg);
+        err_msg
+    })
+}
 
-    let question_rows = stmt
+/// How many uses each stored asset has, from the `asset_references` view.
+fn asset_reference_counts(conn: &Connection) -> Result<HashMap<(String, String), u64>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT folder, TRIM(asset_id), COUNT(*)
+             FROM asset_references
+             GROUP BY folder, TRIM(asset_id)",
+        )
+        .map_err(|e| e.to_string())?;
+    let counts = stmt
         .query_map([], |row| {
-            Ok(Question {
-                id: row.get(0)?,
-                part: row.get(1)?,
-                sub_part: row.get(2)?,
-                audio_path: row.get(3)?,
-                image_path: row.get(4)?,
-                text: row.get(5)?,
-                pack_id: row.get(6)?,
-                pack_order: row.get(7)?,
-                response_time: row.get(8)?,
-                active: row.get(9)?,
-            })
+            Ok((
+                (row.get::<_, String>(0)?, row.get::<_, String>(1)?),
+                row.get::<_, i64>(2)?.max(0) as u64,
+            ))
         })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<HashMap<_, _>, _>>()
         .map_err(|e| e.to_string())?;
+    Ok(counts)
+}
 
-    let mut exported_questions = Vec::new();
-    for row in question_rows {
-        let question = row.map_err(|e| e.to_string())?;
-        if !selected_ids.contains(&question.id) {
-            continue;
+/// The files directly inside `dir` with their size and modification time.
+/// A folder that does not exist yet has none.
+fn stored_files(dir: &Path) -> Result<Vec<(String, fs::Metadata)>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(format!("Failed to list {:?}: {}", dir, error)),
+    };
+
+    let mut files = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+        if let (true, Some(name)) = (metadata.is_file(), entry.file_name().to_str()) {
+            files.push((name.to_string(), metadata));
         }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
 
-        exported_questions.push(ExportedQuestion {
-            source_id: question.id,
-            part: question.part,
-            sub_part: question.sub_part,
-            audio_path: question.audio_path.clone(),
-            image_path: question.image_path.clone(),
-            text: question.text.clone(),
-            pack_id: question.pack_id.clone(),
-            pack_order: question.pack_order,
-            response_time: question.response_time,
-            audio_file: read_exported_asset(&app_handle, &app_dir, "audio", &question.audio_path)?,
-            image_file: read_exported_asset(&app_handle, &app_dir, "images", &question.image_path)?,
-        });
+/// Size of every file under `dir`, with the paths of those in `referenced`
+/// counted separately.
+fn folder_usage_recursive(
+    dir: &Path,
+    referenced: &HashSet<PathBuf>,
+    usage: &mut AssetFolderUsage,
+) -> Result<(), String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(format!("Failed to list {:?}: {}", dir, error)),
+    };
+
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+        if metadata.is_dir() {
+            folder_usage_recursive(&entry.path(), referenced, usage)?;
+        } else if metadata.is_file() {
+            usage.file_count += 1;
+            usage.total_bytes += metadata.len();
+            if referenced.contains(&entry.path()) {
+                usage.referenced_count += 1;
+                usage.referenced_bytes += metadata.len();
+            }
+        }
     }
+    Ok(())
+}
 
-    if exported_questions.is_empty() {
-        return Err("No active questions matched the export selection".to_string());
+/// Disk use of the question audio and image stores and of the recordings.
+#[tauri::command]
+pub async fn get_asset_storage_report(
+    app_handle: AppHandle,
+    db: State<'_, Db>,
+) -> Result<Vec<AssetFolderUsage>, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    db.run(move |conn| asset_storage_report(conn, &app_dir))
+        .await
+}
+
+fn asset_storage_report(
+    conn: &Connection,
+    app_dir: &Path,
+) -> Result<Vec<AssetFolderUsage>, String> {
+    let counts = asset_reference_counts(conn)?;
+    let mut report = Vec::new();
+
+    for folder in ASSET_FOLDERS {
+        let mut usage = AssetFolderUsage {
+            folder: folder.to_string(),
+            file_count: 0,
+            total_bytes: 0,
+            referenced_count: 0,
+            referenced_bytes: 0,
+            reference_count: None,
+        };
+        let mut reference_count = 0;
+        for (name, metadata) in stored_files(&app_dir.join(folder))? {
+            usage.file_count += 1;
+            usage.total_bytes += metadata.len();
+            if let Some(count) = counts.get(&(folder.to_string(), name)) {
+                usage.referenced_count += 1;
+                usage.referenced_bytes += metadata.len();
+                reference_count += count;
+            }
+        }
+        usage.reference_count = Some(reference_count);
+        report.push(usage);
     }
 
-    let export_file = QuestionsExportFile {
-        format: "cefr-speaking-questions".to_string(),
-        version: 1,
-        exported_at: chrono::Local::now().to_rfc3339(),
-        questions: exported_questions,
+    // A thumbnail is in use while its image is stored.
+    let mut usage = AssetFolderUsage {
+        folder: THUMBNAIL_DIR.to_string(),
+        file_count: 0,
+        total_bytes: 0,
+        referenced_count: 0,
+        referenced_bytes: 0,
+        reference_count: None,
+    };
+    for (name, metadata) in stored_files(&app_dir.join(THUMBNAIL_DIR))? {
+        usage.file_count += 1;
+        usage.total_bytes += metadata.len();
+        if app_dir
+            .join("images")
+            .join(thumbnail_image(&name))
+            .is_file()
+        {
+            usage.referenced_count += 1;
+            usage.referenced_bytes += metadata.len();
+        }
+    }
+    report.push(usage);
+
+    let mut stmt = conn
+        .prepare("SELECT audio_path FROM responses WHERE audio_path <> ''")
+        .map_err(|e| e.to_string())?;
+    let recordings = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let mut usage = AssetFolderUsage {
+        folder: "responses".to_string(),
+        file_count: 0,
+        total_bytes: 0,
+        referenced_count: 0,
+        referenced_bytes: 0,
+        reference_count: Some(recordings.len() as u64),
     };
+    let referenced = recordings.into_iter().map(PathBuf::from).collect();
+    folder_usage_recursive(&app_dir.join("responses"), &referenced, &mut usage)?;
+    report.push(usage);
+
+    Ok(report)
+}
 
-    serde_json::to_string_pretty(&export_file)
-        .map_err(|e| format!("Failed to create questions export: {}", e))
+/// The asset ID of the image a file in the thumbnail folder was made from.
+fn thumbnail_image(name: &str) -> &str {
+    name.rsplit_once('.').map_or(name, |(asset_id, _)| asset_id)
 }
 
+/// Lists the files in the question audio and image stores that nothing
+/// refers to, and removes them unless `dry_run` is set. Files younger than
+/// `UNUSED_ASSET_MIN_AGE` are listed but kept, since an upload is saved
+/// before its question; the same goes for a thumbnail, which is made as soon
+/// as its image is stored. Thumbnails go with their image. Recordings are not
+/// touched.
 #[tauri::command]
-pub async fn import_questions(
+pub async fn collect_unused_assets(
     app_handle: AppHandle,
-    export_json: String,
-) -> Result<ImportQuestionsResult, String> {
-    let import_file: QuestionsExportFile = serde_json::from_str(&export_json)
-        .map_err(|e| format!("This file is not a valid CEFR questions export: {}", e))?;
+    db: State<'_, Db>,
+    dry_run: bool,
+) -> Result<Vec<UnusedAsset>, String> {
+    let app_dir = app_data_dir(&app_handle)?;
+    db.run(move |conn| remove_unused_assets(conn, &app_dir, dry_run))
+        .await
+}
 
-    if import_file.format != "cefr-speaking-questions" {
-        return Err("This file is not a CEFR speaking questions export".to_string());
-    }
+fn remove_unused_assets(
+    conn: &Connection,
+    app_dir: &Path,
+    dry_run: bool,
+) -> Result<Vec<UnusedAsset>, String> {
+    let counts = asset_reference_counts(conn)?;
+    let now = std::time::SystemTime::now();
+    let old_enough = |metadata: &fs::Metadata| {
+        metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .is_some_and(|age| age >= UNUSED_ASSET_MIN_AGE)
+    };
+    let mut unused = Vec::new();
 
-    if import_file.questions.is_empty() {
-        return Err("The selected export file does not contain any questions".to_string());
-    }
+    for folder in ASSET_FOLDERS {
+        let root = app_dir.join(folder);
+        for (name, metadata) in stored_files(&root)? {
+            if counts.contains_key(&(folder.to_string(), name.clone())) {
+                continue;
+            }
 
-    let conn = init_db(&app_handle)?;
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+            let removed = !dry_run && old_enough(&metadata);
+            if removed {
+                fs::remove_file(root.join(&name))
+                    .map_err(|e| format!("Failed to remove {} file {}: {}", folder, name, e))?;
+                conn.execute(
+                    "DELETE FROM asset_hashes WHERE folder = ? AND filename = ?",
+                    params![folder, name],
+                )
+                .map_err(|e| e.to_string())?;
+            }
 
-    for question in &import_file.questions {
-        validate_exported_question(question)?;
+            unused.push(UnusedAsset {
+                folder: folder.to_string(),
+                size_bytes: metadata.len(),
+                asset_id: name,
+                removed,
+            });
+        }
     }
 
-    let imported = import_file.questions.len();
-    for question in import_file.questions {
-        insert_exported_question(&conn, &app_dir, question)?;
+    let thumbnail_dir = app_dir.join(THUMBNAIL_DIR);
+    for (name, metadata) in stored_files(&thumbnail_dir)? {
+        if app_dir
+            .join("images")
+            .join(thumbnail_image(&name))
+            .is_file()
+        {
+            continue;
+        }
+        let removed = !dry_run && old_enough(&metadata);
+        if removed {
+            fs::remove_file(thumbnail_dir.join(&name))
+                .map_err(|e| format!("Failed to remove thumbnail {}: {}", name, e))?;
+        }
+        unused.push(UnusedAsset {
+            folder: THUMBNAIL_DIR.to_string(),
+            size_bytes: metadata.len(),
+            asset_id: name,
+            removed,
+        });
     }
 
-    Ok(ImportQuestionsResult { imported })
+    Ok(unused)
 }
 
-#[tauri::command]
-pub async fn export_full_test(app_handle: AppHandle, full_test_id: i64) -> Result<String, String> {
-    let conn = init_db(&app_handle)?;
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    let exported_at = chrono::Local::now().to_rfc3339();
-    let export_file =
-        build_full_test_export(&conn, &app_handle, &app_dir, full_test_id, &exported_at)?;
-
-    serde_json::to_string_pretty(&export_file)
-        .map_err(|e| format!("Failed to create full test export: {}", e))
+/// The asset roots `check_bank` looks files up in, by folder.
+struct BankRoots {
+    audio: Vec<PathBuf>,
+    images: Vec<PathBuf>,
+    responses: PathBuf,
 }
 
-#[tauri::command]
-pub async fn export_full_tests(
-    app_handle: AppHandle,
-    full_test_ids: Vec<i64>,
-) -> Result<String, String> {
-    let mut seen_ids = HashSet::new();
-    let mut ordered_ids = Vec::new();
-    for full_test_id in full_test_ids {
-        if full_test_id <= 0 {
-            return Err("Invalid full test selected for export".to_string());
-        }
-        if seen_ids.insert(full_test_id) {
-            ordered_ids.push(full_test_id);
+impl BankRoots {
+    fn new(app_handle: &AppHandle, app_dir: &Path) -> Self {
+        BankRoots {
+            audio: asset_roots(app_handle, app_dir, "audio"),
+            images: asset_roots(app_handle, app_dir, "images"),
+            responses: app_dir.join("responses"),
         }
     }
 
-    if ordered_ids.is_empty() {
-        return Err("Choose at least one full test to export".to_string());
+    fn for_folder(&self, folder: &str) -> &[PathBuf] {
+        match folder {
+            "audio" => &self.audio,
+            _ => &self.images,
+        }
     }
+}
 
-    let conn = init_db(&app_handle)?;
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    let exported_at = chrono::Local::now().to_rfc3339();
+/// Checks every file of one run of `check_bank` at most once.
+struct BankChecker<'a> {
+    conn: &'a Connection,
+    roots: &'a BankRoots,
+    results: HashMap<(String, String), Option<(String, String)>>,
+}
 
-    let mut tests = Vec::new();
-    for full_test_id in ordered_ids {
-        tests.push(build_full_test_export(
-            &conn,
-            &app_handle,
-            &app_dir,
-            full_test_id,
-            &exported_at,
-        )?);
+impl BankChecker<'_> {
+    /// The problem with asset `id` under `folder`, as `(problem, detail)`.
+    fn asset_problem(
+        &mut self,
+        folder: &str,
+        id: &str,
+    ) -> Result<Option<(String, String)>, String> {
+        let key = (folder.to_string(), id.to_string());
+        if let Some(result) = self.results.get(&key) {
+            return Ok(result.clone());
+        }
+        let result = self.check_asset(folder, id)?;
+        self.results.insert(key, result.clone());
+        Ok(result)
     }
 
-    let export_file = FullTestsExportFile {
-        format: "cefr-speaking-full-tests".to_string(),
-        version: 1,
-        exported_at,
-        tests,
-    };
+    fn check_asset(&self, folder: &str, id: &str) -> Result<Option<(String, String)>, String> {
+        let problem = |problem: &str, detail: String| Ok(Some((problem.to_string(), detail)));
 
-    serde_json::to_string_pretty(&export_file)
-        .map_err(|e| format!("Failed to create full tests export: {}", e))
-}
+        if let Err(error) = assets::validate_asset_id(id) {
+            return problem("invalid_name", error);
+        }
+        let path = match assets::resolve(self.roots.for_folder(folder), id) {
+            Ok(path) => path,
+            Err(error) => return problem("missing", error),
+        };
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(error) => return problem("unreadable", format!("{:?}: {}", path, error)),
+        };
 
-#[tauri::command]
-pub async fn import_full_test(
-    app_handle: AppHandle,
-    export_json: String,
-) -> Result<ImportFullTestResult, String> {
-    let parsed: serde_json::Value = serde_json::from_str(&export_json)
-        .map_err(|e| format!("This file is not a valid CEFR full test export: {}", e))?;
+        let recorded: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT sha256 FROM asset_hashes WHERE folder = ? AND filename = ?",
+                params![folder, id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let expected = assets::content_hash(id).map(str::to_string).or(recorded);
+        let actual = sha256_hex(&data);
+        if let Some(expected) = expected.filter(|expected| !expected.eq_ignore_ascii_case(&actual))
+        {
+            return problem(
+                "checksum_mismatch",
+                format!("Expected SHA-256 {} but the file has {}", expected, actual),
+            );
+        }
 
-    let conn = init_db(&app_handle)?;
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
+        match media::verify(&data) {
+            Ok(format) if Some(format.kind) == MediaKind::for_folder(folder) => Ok(None),
+            Ok(format) => problem(
+                "wrong_type",
+                format!(
+                    "The file is {} but is used as {}",
+                    format.mime,
+                    if folder == "audio" {
+                        "audio"
+                    } else {
+                        "an image"
+                    }
+                ),
+            ),
+            Err(error) => problem("corrupt", error),
+        }
+    }
 
-    let export_format = parsed
-        .get("format")
-        .and_then(|value| value.as_str())
-        .unwrap_or_default();
+    /// The audio or image file under `folder` that a broken `reference` most
+    /// likely meant: one with the same file name, the same name in another
+    /// case, or the same content hash under another extension.
+    fn relink_candidate(
+        &mut self,
+        folder: &str,
+        reference: &str,
+    ) -> Result<Option<String>, String> {
+        let name = reference
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or_default()
+            .trim();
+        if name.is_empty() {
+            return Ok(None);
+        }
+        let hash = assets::content_hash(name).map(str::to_string);
+
+        let mut candidates = vec![name.to_string()];
+        for root in self.roots.for_folder(folder) {
+            for (stored, _) in stored_files(root)? {
+                let same_hash = hash.is_some() && assets::content_hash(&stored) == hash.as_deref();
+                if stored.eq_ignore_ascii_case(name) || same_hash {
+                    candidates.push(stored);
+                }
+            }
+        }
 
-    if export_format == "cefr-speaking-full-test" {
-        let import_file: FullTestExportFile = serde_json::from_value(parsed)
-            .map_err(|e| format!("This file is not a valid CEFR full test export: {}", e))?;
-        return import_full_test_payload(&conn, &app_dir, import_file);
+        for candidate in candidates {
+            if candidate != reference && self.asset_problem(folder, &candidate)?.is_none() {
+                return Ok(Some(candidate));
+            }
+        }
+        Ok(None)
     }
 
-    if export_format != "cefr-speaking-full-tests" {
-        return Err("This file is not a CEFR speaking full test export".to_string());
+    /// The problem with a recording saved at `audio_path`.
+    fn recording_problem(&self, audio_path: &str) -> Option<(String, String)> {
+        let path = Path::new(audio_path);
+        if !path.is_file() {
+            return Some(("missing".to_string(), format!("{:?} does not exist", path)));
+        }
+        if let Err(error) = assets::confine(&self.roots.responses, path) {
+            return Some(("invalid_name".to_string(), error));
+        }
+        match fs::read(path) {
+            Ok(data) => webm::duration_ms(&data)
+                .err()
+                .map(|error| ("corrupt".to_string(), error)),
+            Err(error) => Some(("unreadable".to_string(), error.to_string())),
+        }
     }
 
-    let import_file: FullTestsExportFile = serde_json::from_value(parsed)
-        .map_err(|e| format!("This file is not a valid CEFR full tests export: {}", e))?;
+    /// The same recording in this app's responses folder, for a path saved
+    /// on another machine or under another user.
+    fn recording_candidate(&self, audio_path: &str) -> Option<String> {
+        let parts = audio_path.split(['/', '\\']).collect::<Vec<_>>();
+        let start = parts.iter().rposition(|part| *part == "responses")? + 1;
+        let mut candidate = self.roots.responses.clone();
+        for part in &parts[start..] {
+            if assets::validate_asset_id(part).is_err() {
+                return None;
+            }
+            candidate.push(part);
+        }
 
-    if import_file.tests.is_empty() {
-        return Err("The selected full tests export does not contain any tests".to_string());
+        let candidate = candidate.to_string_lossy().to_string();
+        (candidate != audio_path && self.recording_problem(&candidate).is_none())
+            .then_some(candidate)
     }
+}
 
-    for test in &import_file.tests {
-        validate_full_test_export_file(test)?;
+/// The column of `questions` that holds assets of `folder`.
+fn question_asset_column(folder: &str) -> Result<&'static str, String> {
+    match folder {
+        "audio" => Ok("audio_path"),
+        "images" => Ok("image_path"),
+        _ => Err(format!("Questions have no {} files", folder)),
     }
+}
 
-    let mut imported_tests = 0;
-    let mut imported_questions = 0;
-    let mut last_full_test_id = 0;
+fn bank_check(conn: &Connection, roots: &BankRoots) -> Result<BankCheckReport, String> {
+    let mut checker = BankChecker {
+        conn,
+        roots,
+        results: HashMap::new(),
+    };
+    let mut report = BankCheckReport {
+        questions_checked: 0,
+        full_tests_checked: 0,
+        responses_checked: 0,
+        files_checked: 0,
+        issues: Vec::new(),
+    };
 
-    for test in import_file.tests {
-        let result = import_full_test_payload(&conn, &app_dir, test)?;
-        imported_tests += result.imported_tests;
-        imported_questions += result.imported_questions;
-        last_full_test_id = result.full_test_id;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, part, sub_part, COALESCE(audio_path, ''), COALESCE(image_path, '')
+             FROM questions WHERE active = 1 ORDER BY id",
+        )
+        .map_err(|e| e.to_string())?;
+    let questions = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, i32>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    // Files looked at only as relink candidates are not counted as checked.
+    let mut question_files = HashSet::new();
+    let mut broken_questions = HashSet::new();
+    for (question_id, part, sub_part, audio_path, image_path) in questions {
+        report.questions_checked += 1;
+        let label = format!(
+            "{} question {}",
+            section_label(conn, None, part, sub_part)?,
+            question_id
+        );
+        for (folder, reference) in [("audio", audio_path), ("images", image_path)] {
+            let reference = reference.trim();
+            if reference.is_empty() {
+                continue;
+            }
+            question_files.insert((folder, reference.to_string()));
+            let Some((problem, detail)) = checker.asset_problem(folder, reference)? else {
+                continue;
+            };
+            broken_questions.insert(question_id);
+            report.issues.push(BankIssue {
+                item_kind: "question".to_string(),
+                item_id: question_id,
+                item_label: label.clone(),
+                folder: folder.to_string(),
+                reference: reference.to_string(),
+                problem,
+                detail,
+                relink_to: checker.relink_candidate(folder, reference)?,
+            });
+        }
     }
 
-    Ok(ImportFullTestResult {
-        imported_tests,
-        imported_questions,
-        full_test_id: last_full_test_id,
-    })
-}
-
-#[tauri::command]
-pub async fn delete_question(app_handle: AppHandle, question_id: i64) -> Result<String, String> {
-    let conn = init_db(&app_handle)?;
+    let mut stmt = conn
+        .prepare("SELECT id, name FROM full_tests WHERE active = 1 ORDER BY id")
+        .map_err(|e| e.to_string())?;
+    let full_tests = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
 
-    conn.execute(
-        "UPDATE questions SET active = 0 WHERE id = ?",
-        [question_id],
-    )
-    .map_err(|e| e.to_string())?;
+    for (full_test_id, name) in full_tests {
+        report.full_tests_checked += 1;
+        let mut stmt = conn
+            .prepare(
+                "SELECT full_test_questions.question_id, COALESCE(questions.active, 0)
+                 FROM full_test_questions
+                 LEFT JOIN questions ON full_test_questions.question_id = questions.id
+                 WHERE full_test_questions.full_test_id = ?
+                 ORDER BY full_test_questions.position, full_test_questions.question_id",
+            )
+            .map_err(|e| e.to_string())?;
+        let linked = stmt
+            .query_map(params![full_test_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?))
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
 
-    println!("Question {} deactivated successfully", question_id);
-    Ok(format!("Question {} deactivated", question_id))
-}
+        for (question_id, active) in linked {
+            let (problem, detail) = if !active {
+                (
+                    "question_unavailable",
+                    format!("Question {} has been deleted", question_id),
+                )
+            } else if broken_questions.contains(&question_id) {
+                (
+                    "question_broken",
+                    format!("Question {} has a damaged or missing file", question_id),
+                )
+            } else {
+                continue;
+            };
+            report.issues.push(BankIssue {
+                item_kind: "full_test".to_string(),
+                item_id: full_test_id,
+                item_label: name.clone(),
+                folder: String::new(),
+                reference: question_id.to_string(),
+                problem: problem.to_string(),
+                detail,
+                relink_to: None,
+            });
+        }
+    }
 
-#[tauri::command]
-pub async fn rate_response(
-    app_handle: AppHandle,
-    response_id: i64,
-    fluency: i32,
-    lexical: i32,
-    grammar: i32,
-    pronunciation: i32,
-    comment: String,
-) -> Result<(), String> {
-    let conn = init_db(&app_handle)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT responses.id, responses.audio_path, attempts.student_name
+             FROM responses
+             JOIN attempts ON responses.attempt_id = attempts.id
+             WHERE responses.audio_path <> ''
+             ORDER BY responses.id",
+        )
+        .map_err(|e| e.to_string())?;
+    let responses = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
 
-    conn.execute(
-        "INSERT OR REPLACE INTO ratings (response_id, fluency, lexical, grammar, pronunciation, comment)
-         VALUES (?, ?, ?, ?, ?, ?)",
-        params![response_id, fluency, lexical, grammar, pronunciation, comment],
-    ).map_err(|e| e.to_string())?;
+    for (response_id, audio_path, student_name) in responses {
+        report.responses_checked += 1;
+        report.files_checked += 1;
+        let Some((problem, detail)) = checker.recording_problem(&audio_path) else {
+            continue;
+        };
+        report.issues.push(BankIssue {
+            item_kind: "response".to_string(),
+            item_id: response_id,
+            item_label: format!("Recording {} by {}", response_id, student_name),
+            folder: "responses".to_string(),
+            relink_to: checker.recording_candidate(&audio_path),
+            reference: audio_path,
+            problem,
+            detail,
+        });
+    }
 
-    Ok(())
+    report.files_checked += question_files.len() as u64;
+    Ok(report)
 }
 
+/// Checks every active question, full test and recording: that the files
+/// they rely on exist, decode as the media they should be and still match
+/// their checksum. Nothing is changed; see `repair_bank`.
 #[tauri::command]
-pub async fn save_image_file(
+pub async fn check_bank(
     app_handle: AppHandle,
-    filename: String,
-    image_data: Vec<u8>,
-) -> Result<String, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    fs::create_dir_all(&app_dir).map_err(|e| e.to_string())?;
-
-    let image_dir = app_dir.join("images");
-    fs::create_dir_all(&image_dir).map_err(|e| e.to_string())?;
-
-    let safe_filename = unique_filename(&image_dir, &filename)?;
-    let image_file_path = image_dir.join(&safe_filename);
+    db: State<'_, Db>,
+) -> Result<BankCheckReport, String> {
+    let roots = BankRoots::new(&app_handle, &app_data_dir(&app_handle)?);
+    db.run(move |conn| bank_check(conn, &roots)).await
+}
 
-    fs::write(&image_file_path, &image_data).map_err(|e| {
-        let err_msg = format!("Failed to write image file: {}", e);
-        println!("Error: {}", err_msg);
-        err_msg
-    })?;
+fn apply_bank_repair(
+    conn: &Connection,
+    roots: &BankRoots,
+    repair: &BankRepair,
+) -> Result<(), String> {
+    let mut checker = BankChecker {
+        conn,
+        roots,
+        results: HashMap::new(),
+    };
 
-    Ok(safe_filename)
+    match (repair.action.as_str(), repair.item_kind.as_str()) {
+        ("relink", "question") => {
+            let column = question_asset_column(&repair.folder)?;
+            let target = checker
+                .relink_candidate(&repair.folder, &repair.reference)?
+                .ok_or_else(|| format!("No stored file matches {}", repair.reference))?;
+            let updated = conn
+                .execute(
+                    &format!("UPDATE questions SET {column} = ? WHERE id = ? AND {column} = ?"),
+                    params![target, repair.item_id, repair.reference],
+                )
+                .map_err(|e| e.to_string())?;
+            if updated == 0 {
+                return Err(format!(
+                    "Question {} no longer refers to {}",
+                    repair.item_id, repair.reference
+                ));
+            }
+            record_question_revision(conn, repair.item_id)?;
+        }
+        ("relink", "response") => {
+            let target = checker
+                .recording_candidate(&repair.reference)
+                .ok_or_else(|| format!("No recording matches {}", repair.reference))?;
+            conn.execute(
+                "UPDATE responses SET audio_path = ? WHERE id = ? AND audio_path = ?",
+                params![target, repair.item_id, repair.reference],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        ("deactivate", "question") => {
+            conn.execute(
+                "UPDATE questions SET active = 0 WHERE id = ?",
+                params![repair.item_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        ("deactivate", "full_test") => {
+            conn.execute(
+                "UPDATE full_tests SET active = 0 WHERE id = ?",
+                params![repair.item_id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        (action, kind) => {
+            return Err(format!("A {} cannot be repaired with {}", kind, action));
+        }
+    }
+    Ok(())
 }
 
+/// Applies fixes chosen from a `check_bank` report and checks the bank
+/// again. The fixes are applied together or not at all.
 #[tauri::command]
-pub async fn get_image_file(app_handle: AppHandle, filename: String) -> Result<Vec<u8>, String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-
-    let image_file_path = app_dir.join("images").join(&filename);
-
-    let data = fs::read(&image_file_path).map_err(|e| {
-        let err_msg = format!("Failed to read image file {}: {}", filename, e);
-        println!("Error: {}", err_msg);
-        err_msg
-    })?;
+pub async fn repair_bank(
+    app_handle: AppHandle,
+    db: State<'_, Db>,
+    repairs: Vec<BankRepair>,
+) -> Result<BankCheckReport, String> {
+    let roots = BankRoots::new(&app_handle, &app_data_dir(&app_handle)?);
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for repair in &repairs {
+            apply_bank_repair(&tx, &roots, repair)?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
 
-    Ok(data)
+        bank_check(conn, &roots)
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn get_recordings(app_handle: AppHandle) -> Result<Vec<Recording>, String> {
-    let conn = init_db(&app_handle)?;
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT responses.id,
-                responses.attempt_id,
-                attempts.student_name,
-                attempts.started_at,
-                responses.question_id,
-                questions.part,
-                questions.sub_part,
-                questions.text,
-                responses.recorded_at,
-                responses.duration
-         FROM responses
-         JOIN attempts ON responses.attempt_id = attempts.id
-         JOIN questions ON responses.question_id = questions.id
-         ORDER BY attempts.started_at DESC, responses.recorded_at DESC",
-        )
-        .map_err(|e| e.to_string())?;
+pub async fn get_recordings(db: State<'_, Db>) -> Result<Vec<Recording>, String> {
+    db.run(move |conn| {
+        let mut stmt = conn
+            .prepare(&format!(
+                "{} ORDER BY attempts.started_at DESC, responses.recorded_at DESC",
+                RECORDING_SELECT_SQL
+            ))
+            .map_err(|e| e.to_string())?;
 
-    let recordings = stmt
-        .query_map([], |row| {
-            Ok(Recording {
-                id: row.get(0)?,
-                attempt_id: row.get(1)?,
-                student_name: row.get(2)?,
-                attempt_started_at: row.get(3)?,
-                question_id: row.get(4)?,
-                part: row.get(5)?,
-                sub_part: row.get(6)?,
-                question_text: row.get(7)?,
-                recorded_at: row.get(8)?,
-                duration: row.get(9)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        let recordings = stmt
+            .query_map([], recording_from_row)
+            .map_err(|e| e.to_string())?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
 
-    Ok(recordings)
+        Ok(recordings)
+    })
+    .await
 }
 
 #[tauri::command]
 pub async fn get_response_audio(
     app_handle: AppHandle,
+    db: State<'_, Db>,
     response_id: i64,
 ) -> Result<Vec<u8>, String> {
-    let conn = init_db(&app_handle)?;
+    let responses_root = app_data_dir(&app_handle)?.join("responses");
+    db.run(move |conn| {
+        let audio_path: String = conn
+            .query_row(
+                "SELECT audio_path FROM responses WHERE id = ?",
+                params![response_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
 
-    let audio_path: String = conn
-        .query_row(
-            "SELECT audio_path FROM responses WHERE id = ?",
-            params![response_id],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+        // Recordings are only ever read from the responses folder.
+        let audio_path = assets::confine(&responses_root, Path::new(&audio_path))?;
+        let data = fs::read(&audio_path).map_err(|e| {
+            let err_msg = format!("Failed to read response audio: {}", e);
+            println!("Error: {}", err_msg);
+            err_msg
+        })?;
 
-    let data = fs::read(&audio_path).map_err(|e| {
-        let err_msg = format!("Failed to read response audio: {}", e);
-        println!("Error: {}", err_msg);
-        err_msg
-    })?;
+        Ok(data)
+    })
+    .await
+}
 
-    Ok(data)
+/// Removes a recording whose response rows are already gone. A file that
+/// cannot be removed is only logged, since nothing refers to it any more.
+fn remove_deleted_recording(audio_path: &str) {
+    if let Err(error) = fs::remove_file(audio_path) {
+        if error.kind() != std::io::ErrorKind::NotFound {
+            println!(
+                "Warning: failed to delete recording {}: {}",
+                audio_path, error
+            );
+        }
+    }
 }
 
 #[tauri::command]
-pub async fn delete_response(app_handle: AppHandle, response_id: i64) -> Result<(), String> {
-    let conn = init_db(&app_handle)?;
+pub async fn delete_response(db: State<'_, Db>, response_id: i64) -> Result<(), String> {
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let audio_path: Option<String> = tx
+            .query_row(
+                "SELECT audio_path FROM responses WHERE id = ?",
+                params![response_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
 
-    let audio_path: Option<String> = conn
-        .query_row(
-            "SELECT audio_path FROM responses WHERE id = ?",
+        tx.execute(
+            "DELETE FROM ratings WHERE response_id = ?",
             params![response_id],
-            |row| row.get(0),
         )
-        .optional()
         .map_err(|e| e.to_string())?;
 
-    if let Some(path) = audio_path {
-        let remove_result = fs::remove_file(&path);
-        if let Err(error) = remove_result {
-            if error.kind() != std::io::ErrorKind::NotFound {
-                return Err(format!("Failed to delete audio file: {}", error));
-            }
-        }
-    }
+        tx.execute(
+            "DELETE FROM telegram_deliveries WHERE response_id = ?",
+            params![response_id],
+        )
+        .map_err(|e| e.to_string())?;
 
-    conn.execute(
-        "DELETE FROM ratings WHERE response_id = ?",
-        params![response_id],
-    )
-    .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM responses WHERE id = ?", params![response_id])
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
 
-    conn.execute("DELETE FROM responses WHERE id = ?", params![response_id])
-        .map_err(|e| e.to_string())?;
+        if let Some(path) = audio_path.filter(|path| !path.is_empty()) {
+            remove_deleted_recording(&path);
+        }
 
-    Ok(())
+        Ok(())
+    })
+    .await
 }
 
 #[tauri::command]
-pub async fn delete_attempt(app_handle: AppHandle, attempt_id: i64) -> Result<(), String> {
-    let conn = init_db(&app_handle)?;
+pub async fn delete_attempt(
+    db: State<'_, Db>,
+    app_handle: AppHandle,
+    attempt_id: i64,
+) -> Result<(), String> {
+    let responses_root = app_data_dir(&app_handle)?.join("responses");
+    db.run(move |conn| {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let audio_paths = {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT audio_path FROM responses WHERE attempt_id = ? AND audio_path <> ''",
+                )
+                .map_err(|e| e.to_string())?;
+            let rows = stmt
+                .query_map(params![attempt_id], |row| row.get::<_, String>(0))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?
+        };
 
-    let mut stmt = conn
-        .prepare("SELECT id, audio_path FROM responses WHERE attempt_id = ?")
+        tx.execute(
+            "DELETE FROM ratings
+             WHERE response_id IN (SELECT id FROM responses WHERE attempt_id = ?)",
+            params![attempt_id],
+        )
         .map_err(|e| e.to_string())?;
-
-    let response_rows = stmt
-        .query_map(params![attempt_id], |row| {
-            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
-        })
+        tx.execute(
+            "DELETE FROM telegram_deliveries
+             WHERE response_id IN (SELECT id FROM responses WHERE attempt_id = ?)",
+            params![attempt_id],
+        )
         .map_err(|e| e.to_string())?;
-
-    let mut response_ids = Vec::new();
-    for row in response_rows {
-        let (response_id, audio_path) = row.map_err(|e| e.to_string())?;
-        response_ids.push(response_id);
-        if !audio_path.is_empty() {
-            let remove_result = fs::remove_file(&audio_path);
-            if let Err(error) = remove_result {
-                if error.kind() != std::io::ErrorKind::NotFound {
-                    return Err(format!("Failed to delete audio file: {}", error));
-                }
-            }
-        }
-    }
-
-    if !response_ids.is_empty() {
-        let placeholders = response_ids
-            .iter()
-            .map(|_| "?")
-            .collect::<Vec<_>>()
-            .join(",");
-        let mut params: Vec<&dyn rusqlite::ToSql> = Vec::new();
-        for id in &response_ids {
-            params.push(id);
-        }
-        conn.execute(
-            &format!(
-                "DELETE FROM ratings WHERE response_id IN ({})",
-                placeholders
-            ),
-            params.as_slice(),
+        tx.execute(
+            "DELETE FROM responses WHERE attempt_id = ?",
+            params![attempt_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM attempt_questions WHERE attempt_id = ?",
+            params![attempt_id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.execute(
+            "DELETE FROM attempt_draws WHERE attempt_id = ?",
+            params![attempt_id],
         )
         .map_err(|e| e.to_string())?;
-    }
 
-    conn.execute(
-        "DELETE FROM responses WHERE attempt_id = ?",
-        params![attempt_id],
-    )
-    .map_err(|e| e.to_string())?;
+        tx.execute("DELETE FROM attempts WHERE id = ?", params![attempt_id])
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
 
-    conn.execute("DELETE FROM attempts WHERE id = ?", params![attempt_id])
-        .map_err(|e| e.to_string())?;
+        let mut response_dirs = HashSet::new();
+        for audio_path in &audio_paths {
+            remove_deleted_recording(audio_path);
+            if let Some(parent) = Path::new(audio_path).parent() {
+                response_dirs.insert(parent.to_path_buf());
+            }
+        }
+        for dir in response_dirs {
+            remove_empty_dirs_up_to(&dir, &responses_root);
+        }
 
-    Ok(())
+        Ok(())
+    })
+    .await
 }
+
+#[cfg(test)]
+mod asset_tests;
+#[cfg(test)]
+mod full_test_tests;
+#[cfg(test)]
+mod telegram_tests;
+#[cfg(test)]
+mod timing_tests;
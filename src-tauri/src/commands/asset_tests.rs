@@ -0,0 +1,131 @@
+//! Checks what is kept about stored question files: which ones are still in
+//! use, and the gain audio is played at.
+
+use super::*;
+use std::time::SystemTime;
+
+/// One second of a quiet 440 Hz tone as a mono 16-bit WAV file at 8 kHz.
+fn quiet_wav() -> Vec<u8> {
+    let samples: Vec<i16> = (0..8000)
+        .map(|i| {
+            let phase = 2.0 * std::f32::consts::PI * 440.0 * i as f32 / 8000.0;
+            (phase.sin() * 0.05 * i16::MAX as f32) as i16
+        })
+        .collect();
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&8000u32.to_le_bytes());
+    bytes.extend_from_slice(&16000u32.to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+/// Backdates a stored file so it is old enough to be collected.
+fn age_file(path: &Path) {
+    let past = SystemTime::now() - UNUSED_ASSET_MIN_AGE - Duration::from_secs(60);
+    fs::File::options()
+        .append(true)
+        .open(path)
+        .and_then(|file| file.set_modified(past))
+        .expect("backdate stored file");
+}
+
+#[test]
+fn deleting_a_question_makes_its_files_collectable() {
+    let app_dir = tempfile::tempdir().expect("create temp app dir");
+    let db = Db::open(app_dir.path()).expect("open database");
+
+    db.with_connection(|conn| {
+        let audio = store_asset(conn, app_dir.path(), "audio", "prompt.mp3", b"prompt audio")?;
+        let image = store_asset(
+            conn,
+            app_dir.path(),
+            "images",
+            "prompt.png",
+            b"prompt image",
+        )?;
+        age_file(&app_dir.path().join("audio").join(&audio));
+        age_file(&app_dir.path().join("images").join(&image));
+
+        conn.execute(
+            "INSERT INTO questions (uuid, part, audio_path, image_path, response_time, active)
+             VALUES (?, 2, ?, ?, 45, 1)",
+            params![new_content_id(), audio, image],
+        )
+        .map_err(|e| e.to_string())?;
+        let question_id = conn.last_insert_rowid();
+        record_question_revision(conn, question_id)?;
+
+        let unused = remove_unused_assets(conn, app_dir.path(), false)?;
+        assert!(unused.is_empty(), "files of a live question were collected");
+
+        conn.execute(
+            "UPDATE questions SET active = 0 WHERE id = ?",
+            params![question_id],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let unused = remove_unused_assets(conn, app_dir.path(), false)?;
+        let mut removed: Vec<(String, String)> = unused
+            .into_iter()
+            .filter(|asset| asset.removed)
+            .map(|asset| (asset.folder, asset.asset_id))
+            .collect();
+        removed.sort();
+        assert_eq!(
+            removed,
+            vec![("audio".to_string(), audio), ("images".to_string(), image)]
+        );
+        Ok(())
+    })
+    .unwrap();
+}
+
+#[tokio::test]
+async fn measures_missing_audio_gains_ahead_of_playback() {
+    let app_dir = tempfile::tempdir().expect("create temp app dir");
+    let db = Db::open(app_dir.path()).expect("open database");
+
+    let (quiet, gone) = db
+        .with_connection(|conn| {
+            let quiet = store_asset(conn, app_dir.path(), "audio", "quiet.wav", &quiet_wav())?;
+            let gone = store_asset(conn, app_dir.path(), "audio", "gone.wav", b"gone")?;
+            fs::remove_file(app_dir.path().join("audio").join(&gone)).unwrap();
+            assert_eq!(stored_audio_gain(conn, &quiet)?, 0.0);
+            Ok((quiet, gone))
+        })
+        .unwrap();
+
+    measure_missing_audio_gains(&db, app_dir.path())
+        .await
+        .unwrap();
+
+    db.with_connection(|conn| {
+        assert!(stored_audio_gain(conn, &quiet)? > 0.0);
+        let gone_gain: Option<f64> = conn
+            .query_row(
+                "SELECT gain_db FROM asset_hashes WHERE folder = 'audio' AND filename = ?",
+                params![gone],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        assert_eq!(
+            gone_gain, None,
+            "a file that could not be read was given a gain"
+        );
+        Ok(())
+    })
+    .unwrap();
+}
@@ -0,0 +1,34 @@
+//! Checks how a full test's questions have to fill its exam format.
+
+use super::*;
+
+fn section(part: i32, label: &str, question_count: i32) -> BlueprintSection {
+    BlueprintSection {
+        part,
+        sub_part: 0,
+        label: label.to_string(),
+        question_count,
+        selection: default_section_selection(),
+        prep_time: 0,
+        image_required: false,
+        instruction_audio: String::new(),
+        instruction_text: String::new(),
+    }
+}
+
+#[test]
+fn full_test_sections_need_their_exact_question_count() {
+    let sections = vec![section(1, "Part 1", 3), section(2, "Part 2", 1)];
+
+    assert!(
+        full_test_section_problems("CEFR", &sections, &[(1, 0), (1, 0), (1, 0), (2, 0)]).is_empty()
+    );
+
+    assert_eq!(
+        full_test_section_problems("CEFR", &sections, &[(1, 0), (2, 0), (2, 0)]),
+        vec![
+            "The CEFR format takes 3 Part 1 question(s), but the full test has 1".to_string(),
+            "The CEFR format takes 1 Part 2 question(s), but the full test has 2".to_string(),
+        ]
+    );
+}
@@ -0,0 +1,340 @@
+//! Runs the Telegram delivery flow end to end against an in-process mock of
+//! the Bot API, the same way a self-hosted telegram-bot-api server is used.
+
+use super::*;
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+const TEST_BOT_TOKEN: &str = "123456:TEST_TOKEN_abcdefghijklmnopqrstuvwxyz";
+
+struct Fixture {
+    app_dir: tempfile::TempDir,
+    audio_path: PathBuf,
+}
+
+fn fixture() -> Fixture {
+    let app_dir = tempfile::tempdir().expect("create temp app dir");
+    let images_dir = app_dir.path().join("images");
+    fs::create_dir_all(&images_dir).unwrap();
+    fs::write(images_dir.join("prompt.png"), b"\x89PNG\r\n\x1a\nprompt").unwrap();
+
+    let audio_path = app_dir.path().join("q7.webm");
+    fs::write(&audio_path, b"not really webm").unwrap();
+
+    Fixture {
+        app_dir,
+        audio_path,
+    }
+}
+
+fn ok_response() -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "ok": true, "result": {} }))
+}
+
+async fn mount_ok(server: &MockServer, prefix: &str, endpoint: &str) {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "{}/bot{}/{}",
+            prefix, TEST_BOT_TOKEN, endpoint
+        )))
+        .respond_with(ok_response())
+        .mount(server)
+        .await;
+}
+
+async fn received(server: &MockServer) -> Vec<Request> {
+    server.received_requests().await.unwrap_or_default()
+}
+
+fn endpoint_of(request: &Request) -> String {
+    request
+        .url
+        .path()
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn body_of(request: &Request) -> String {
+    String::from_utf8_lossy(&request.body).to_string()
+}
+
+fn job(fixture: &Fixture, chat_id: &str, image_path: &str) -> TelegramDeliveryJob {
+    TelegramDeliveryJob {
+        id: 1,
+        chat_id: chat_id.to_string(),
+        attempts: 0,
+        student_name: "Ali Karimov".to_string(),
+        question_id: 7,
+        section_label: "Part 1.2".to_string(),
+        question_text: "Describe the picture".to_string(),
+        image_path: image_path.to_string(),
+        prep_time: 60,
+        duration: 28,
+        audio_path: fixture.audio_path.to_string_lossy().to_string(),
+        steps_sent: 0,
+    }
+}
+
+async fn send(
+    api: &TelegramBotApi,
+    fixture: &Fixture,
+    chat_id: &str,
+    image_path: &str,
+) -> Result<(), TelegramError> {
+    let mut job = job(fixture, chat_id, image_path);
+    send_response_to_telegram(api, fixture.app_dir.path(), &mut job).await
+}
+
+/// A database with one response queued for delivery as delivery 1.
+fn queued_delivery(fixture: &Fixture) -> Db {
+    let db = Db::open(fixture.app_dir.path()).expect("open database");
+    db.with_connection(|conn| {
+        conn.execute_batch(
+            "INSERT INTO attempts (id, student_name) VALUES (1, 'Ali Karimov');
+             INSERT INTO questions (id, part, sub_part, audio_path, response_time)
+                 VALUES (7, 1, 2, 'q7.mp3', 30);
+             INSERT INTO responses (id, attempt_id, question_id, audio_path, duration)
+                 VALUES (1, 1, 7, 'q7.webm', 28);",
+        )
+        .map_err(|e| e.to_string())?;
+        enqueue_telegram_deliveries(conn, 1, &["764168975".to_string()])?;
+        Ok(())
+    })
+    .unwrap();
+    db
+}
+
+fn delivery_state(db: &Db) -> (String, i32, i32) {
+    db.with_connection(|conn| {
+        conn.query_row(
+            "SELECT status, attempts, steps_sent FROM telegram_deliveries WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| e.to_string())
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn sends_message_photo_and_recording_in_order() {
+    let server = MockServer::start().await;
+    for endpoint in ["sendMessage", "sendPhoto", "sendDocument"] {
+        mount_ok(&server, "", endpoint).await;
+    }
+    let fixture = fixture();
+    let api = TelegramBotApi::new(&server.uri(), TEST_BOT_TOKEN).unwrap();
+
+    send(&api, &fixture, "764168975", "prompt.png")
+        .await
+        .expect("delivery succeeds");
+
+    let requests = received(&server).await;
+    let endpoints = requests.iter().map(endpoint_of).collect::<Vec<_>>();
+    assert_eq!(endpoints, ["sendMessage", "sendPhoto", "sendDocument"]);
+
+    let message = body_of(&requests[0]);
+    assert!(message.contains("chat_id=764168975"));
+    assert!(message.contains("Ali+Karimov"));
+    assert!(message.contains("Part+1.2"));
+    assert!(message.contains("Preparation%3A+60s"));
+
+    let photo = body_of(&requests[1]);
+    assert!(photo.contains("name=\"photo\"; filename=\"prompt.png\""));
+    assert!(photo.contains("Prompt image for Part 1.2 (Question 7)"));
+
+    let document = body_of(&requests[2]);
+    assert!(document.contains("name=\"document\""));
+    assert!(document.contains("Answer recording"));
+    assert!(document.contains("Student Ali Karimov"));
+}
+
+#[tokio::test]
+async fn skips_photo_when_prompt_image_is_missing() {
+    let server = MockServer::start().await;
+    for endpoint in ["sendMessage", "sendPhoto", "sendDocument"] {
+        mount_ok(&server, "", endpoint).await;
+    }
+    let fixture = fixture();
+    let api = TelegramBotApi::new(&server.uri(), TEST_BOT_TOKEN).unwrap();
+
+    send(&api, &fixture, "764168975", "missing.png")
+        .await
+        .expect("delivery succeeds without the image");
+
+    let endpoints = received(&server)
+        .await
+        .iter()
+        .map(endpoint_of)
+        .collect::<Vec<_>>();
+    assert_eq!(endpoints, ["sendMessage", "sendDocument"]);
+}
+
+#[tokio::test]
+async fn honours_api_base_with_path_prefix_and_trailing_slash() {
+    let server = MockServer::start().await;
+    for endpoint in ["sendMessage", "sendDocument"] {
+        mount_ok(&server, "/telegram", endpoint).await;
+    }
+    let fixture = fixture();
+    let api = TelegramBotApi::new(&format!("{}/telegram/", server.uri()), TEST_BOT_TOKEN).unwrap();
+
+    send(&api, &fixture, "-1001234567890", "")
+        .await
+        .expect("delivery succeeds against a prefixed API base");
+
+    let requests = received(&server).await;
+    assert_eq!(requests.len(), 2);
+    assert!(requests
+        .iter()
+        .all(|request| request.url.path().starts_with("/telegram/bot")));
+}
+
+#[tokio::test]
+async fn surfaces_retry_after_when_rate_limited() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/bot{}/sendMessage", TEST_BOT_TOKEN)))
+        .respond_with(ResponseTemplate::new(429).set_body_json(json!({
+            "ok": false,
+            "error_code": 429,
+            "description": "Too Many Requests: retry after 17",
+            "parameters": { "retry_after": 17 }
+        })))
+        .mount(&server)
+        .await;
+    let fixture = fixture();
+    let api = TelegramBotApi::new(&server.uri(), TEST_BOT_TOKEN).unwrap();
+
+    let error = send(&api, &fixture, "764168975", "prompt.png")
+        .await
+        .expect_err("rate limit is reported");
+
+    assert_eq!(error.retry_after, Some(17));
+    assert!(error.message.contains("HTTP 429"));
+    assert_eq!(received(&server).await.len(), 1);
+}
+
+#[tokio::test]
+async fn reports_telegram_error_description() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/bot{}/sendMessage", TEST_BOT_TOKEN)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ok": false,
+            "description": "Bad Request: chat not found"
+        })))
+        .mount(&server)
+        .await;
+    let fixture = fixture();
+    let api = TelegramBotApi::new(&server.uri(), TEST_BOT_TOKEN).unwrap();
+
+    let error = send(&api, &fixture, "42", "")
+        .await
+        .expect_err("Telegram error is reported");
+
+    assert_eq!(error.retry_after, None);
+    assert!(error.message.contains("chat not found"));
+}
+
+#[tokio::test]
+async fn retries_resume_after_the_parts_already_sent() {
+    let server = MockServer::start().await;
+    mount_ok(&server, "", "sendMessage").await;
+    Mock::given(method("POST"))
+        .and(path(format!("/bot{}/sendPhoto", TEST_BOT_TOKEN)))
+        .respond_with(ResponseTemplate::new(500).set_body_string("upstream error"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    mount_ok(&server, "", "sendPhoto").await;
+    mount_ok(&server, "", "sendDocument").await;
+    let fixture = fixture();
+    let api = TelegramBotApi::new(&server.uri(), TEST_BOT_TOKEN).unwrap();
+    let mut job = job(&fixture, "764168975", "prompt.png");
+
+    send_response_to_telegram(&api, fixture.app_dir.path(), &mut job)
+        .await
+        .expect_err("the photo fails the first time");
+    assert_eq!(job.steps_sent, 1);
+
+    send_response_to_telegram(&api, fixture.app_dir.path(), &mut job)
+        .await
+        .expect("the retry succeeds");
+    assert_eq!(job.steps_sent, DELIVERY_STEPS);
+
+    let endpoints = received(&server)
+        .await
+        .iter()
+        .map(endpoint_of)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        endpoints,
+        ["sendMessage", "sendPhoto", "sendPhoto", "sendDocument"]
+    );
+}
+
+#[test]
+fn records_progress_and_keeps_attempts_when_rate_limited() {
+    let fixture = fixture();
+    let db = queued_delivery(&fixture);
+
+    let mut job = db
+        .with_connection(|conn| claim_next_telegram_delivery(conn))
+        .unwrap()
+        .expect("the delivery is due");
+    job.steps_sent = 1;
+    let rate_limited = TelegramError {
+        message: "Too Many Requests".to_string(),
+        retry_after: Some(5),
+    };
+    db.with_connection(|conn| record_telegram_delivery_result(conn, &job, Err(rate_limited)))
+        .unwrap();
+    assert_eq!(delivery_state(&db), ("pending".to_string(), 0, 1));
+
+    let failed = TelegramError {
+        message: "Bad Gateway".to_string(),
+        retry_after: None,
+    };
+    db.with_connection(|conn| record_telegram_delivery_result(conn, &job, Err(failed)))
+        .unwrap();
+    assert_eq!(delivery_state(&db), ("pending".to_string(), 1, 1));
+}
+
+#[tokio::test]
+async fn fetches_bot_info_with_get_me() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/bot{}/getMe", TEST_BOT_TOKEN)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ok": true,
+            "result": {
+                "id": 123456,
+                "is_bot": true,
+                "first_name": "CEFR Mock",
+                "username": "cefr_mock_bot"
+            }
+        })))
+        .mount(&server)
+        .await;
+    let api = TelegramBotApi::new(&server.uri(), TEST_BOT_TOKEN).unwrap();
+
+    let info = fetch_telegram_bot_info(&api).await.expect("getMe succeeds");
+
+    assert_eq!(info.id, 123456);
+    assert_eq!(info.username.as_deref(), Some("cefr_mock_bot"));
+}
+
+#[test]
+fn normalizes_api_base_setting() {
+    assert_eq!(
+        normalize_telegram_api_base(" http://127.0.0.1:8081/ ").unwrap(),
+        "http://127.0.0.1:8081"
+    );
+    assert!(normalize_telegram_api_base("ftp://example.com").is_err());
+    assert!(normalize_telegram_api_base("https://example.com/?x=1").is_err());
+    assert!(normalize_telegram_api_base("not a url").is_err());
+}
@@ -0,0 +1,37 @@
+//! Checks the timing flags a response is saved with.
+
+use super::*;
+use chrono::{Duration as TimeDelta, NaiveDate, NaiveDateTime};
+
+fn answered_at() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, 5, 4)
+        .and_then(|date| date.and_hms_opt(10, 30, 0))
+        .unwrap()
+}
+
+#[test]
+fn flags_responses_answered_long_after_the_question_was_served() {
+    let answered_at = answered_at();
+    let on_time = check_response_timing(
+        b"",
+        40,
+        60,
+        30,
+        Some(answered_at - TimeDelta::seconds(150)),
+        answered_at,
+    );
+    assert!(!on_time.flags.contains(&TIMING_FLAG_ANSWERED_LATE));
+
+    let late = check_response_timing(
+        b"",
+        40,
+        60,
+        30,
+        Some(answered_at - TimeDelta::minutes(10)),
+        answered_at,
+    );
+    assert!(late.flags.contains(&TIMING_FLAG_ANSWERED_LATE));
+
+    let never_served = check_response_timing(b"", 40, 60, 30, None, answered_at);
+    assert!(!never_served.flags.contains(&TIMING_FLAG_ANSWERED_LATE));
+}
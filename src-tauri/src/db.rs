@@ -0,0 +1,108 @@
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::migrations;
+
+const MAX_IDLE_CONNECTIONS: usize = 4;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shared SQLite access held in Tauri managed state. The schema is migrated
+/// once when the app starts; after that commands borrow a connection from a
+/// small idle pool and run their queries on a blocking thread, so a large
+/// bank never stalls the async runtime.
+#[derive(Clone)]
+pub struct Db {
+    inner: Arc<DbInner>,
+}
+
+struct DbInner {
+    path: PathBuf,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl Db {
+    pub fn open(app_dir: &Path) -> Result<Db, String> {
+        fs::create_dir_all(app_dir).map_err(|e| e.to_string())?;
+        let path = app_dir.join("exam.db");
+
+        let mut conn = open_connection(&path)?;
+        migrations::run_migrations(&mut conn, app_dir)?;
+
+        Ok(Db {
+            inner: Arc::new(DbInner {
+                path,
+                idle: Mutex::new(vec![conn]),
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    /// Runs `f` on the calling thread. Only for code that is already off the
+    /// async runtime, such as app setup.
+    pub fn with_connection<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut conn = self.checkout()?;
+        let result = f(&mut conn);
+        self.checkin(conn);
+        result
+    }
+
+    /// Runs `f` with a pooled connection on Tauri's blocking thread pool.
+    pub async fn run<T, F>(&self, f: F) -> Result<T, String>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, String> + Send + 'static,
+    {
+        let db = self.clone();
+        tauri::async_runtime::spawn_blocking(move || db.with_connection(f))
+            .await
+            .map_err(|e| format!("Database task failed: {}", e))?
+    }
+
+    fn checkout(&self) -> Result<Connection, String> {
+        let idle = self
+            .inner
+            .idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .pop();
+
+        match idle {
+            Some(conn) => Ok(conn),
+            None => open_connection(&self.inner.path),
+        }
+    }
+
+    fn checkin(&self, conn: Connection) {
+        let mut idle = self
+            .inner
+            .idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if idle.len() < MAX_IDLE_CONNECTIONS {
+            idle.push(conn);
+        }
+    }
+}
+
+fn open_connection(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+
+    conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to enable WAL mode: {}", e))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "foreign_keys", "ON")
+        .map_err(|e| e.to_string())?;
+
+    Ok(conn)
+}
@@ -1,41 +1,113 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod assets;
 mod commands;
+mod db;
+mod media;
+mod migrations;
+mod package;
+mod webm;
 
 use commands::*;
+use db::Db;
+use tauri::Manager;
 
 fn main() {
     tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .manage(TelegramDeliveryWorker::default())
+        .setup(|app| {
+            let app_dir = app.path().app_data_dir()?;
+            let db = Db::open(&app_dir)?;
+            remove_stale_import_staging(&app_dir);
+            if let Err(error) = migrate_response_storage(&db, &app_dir) {
+                println!("Warning: failed to migrate recording storage: {}", error);
+            }
+            if let Err(error) = migrate_asset_storage(&db, &app_dir) {
+                println!("Warning: failed to migrate question assets: {}", error);
+            }
+            app.manage(db);
+            start_telegram_delivery_worker(app.handle().clone());
+            start_audio_gain_measurement(app.handle().clone());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             create_attempt,
+            serve_question,
+            get_attempt_questions,
             get_random_questions,
+            get_exposure_lookback_days,
+            set_exposure_lookback_days,
             save_response,
             finish_attempt,
+            pause_attempt,
+            abandon_attempt,
+            void_attempt,
+            resume_attempt,
             get_attempts,
+            get_resumable_attempts,
             save_audio_file,
             get_audio_file,
+            get_audio_gain,
+            get_asset_storage_report,
+            collect_unused_assets,
+            check_bank,
+            repair_bank,
             add_question,
+            update_question,
+            get_question_revisions,
             get_questions,
+            get_blueprints,
+            get_blueprint,
+            create_blueprint,
+            update_blueprint,
+            set_default_blueprint,
+            delete_blueprint,
             create_full_test,
             get_full_tests,
             delete_full_test,
+            rename_full_test,
+            add_full_test_question,
+            remove_full_test_question,
+            reorder_full_test_questions,
+            clone_full_test,
             export_full_test,
             export_full_tests,
             import_full_test,
+            preview_import,
+            pick_export_package_path,
+            pick_import_file,
+            export_questions_package,
+            export_full_tests_package,
+            import_export_file,
             export_questions,
             import_questions,
             delete_question,
             rate_response,
+            get_response_rating,
+            get_attempt_ratings,
+            get_unrated_recordings,
             save_image_file,
             get_image_file,
+            get_image_thumbnail,
             get_recordings,
             get_response_audio,
             delete_response,
             delete_attempt,
+            get_database_diagnostics,
             get_telegram_chat_id,
             set_telegram_chat_id,
             get_telegram_chat_ids,
-            set_telegram_chat_ids
+            set_telegram_chat_ids,
+            get_telegram_bot_token_status,
+            set_telegram_bot_token,
+            validate_telegram_bot_token,
+            get_telegram_api_base,
+            set_telegram_api_base,
+            get_telegram_deliveries,
+            retry_telegram_delivery,
+            retry_failed_telegram_deliveries,
+            cancel_telegram_delivery
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,539 @@
+//! Tells question media apart by content rather than by file name, checks
+//! that a file really decodes as what it claims to be, prepares uploads for
+//! storage and makes image thumbnails.
+//!
+//! Question audio may be MP3, WAV, Ogg or M4A; prompt images PNG, JPEG, GIF
+//! or WebP. Opus in WebM, which is what the exam screen records, is checked
+//! with the container reader in `webm`, since there is no Opus decoder here;
+//! for the same reason it is not taken as new question audio.
+
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::webm;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+    Audio,
+    Image,
+}
+
+impl MediaKind {
+    /// The kind of media stored in asset folder `folder`.
+    pub fn for_folder(folder: &str) -> Option<MediaKind> {
+        match folder {
+            "audio" => Some(MediaKind::Audio),
+            "images" => Some(MediaKind::Image),
+            _ => None,
+        }
+    }
+}
+
+/// A media format the app can store and play.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MediaFormat {
+    pub kind: MediaKind,
+    /// The extension files of this format are saved with.
+    pub extension: &'static str,
+    pub mime: &'static str,
+}
+
+pub const PNG: MediaFormat = MediaFormat {
+    kind: MediaKind::Image,
+    extension: "png",
+    mime: "image/png",
+};
+pub const JPEG: MediaFormat = MediaFormat {
+    kind: MediaKind::Image,
+    extension: "jpg",
+    mime: "image/jpeg",
+};
+pub const GIF: MediaFormat = MediaFormat {
+    kind: MediaKind::Image,
+    extension: "gif",
+    mime: "image/gif",
+};
+pub const WEBP: MediaFormat = MediaFormat {
+    kind: MediaKind::Image,
+    extension: "webp",
+    mime: "image/webp",
+};
+pub const MP3: MediaFormat = MediaFormat {
+    kind: MediaKind::Audio,
+    extension: "mp3",
+    mime: "audio/mpeg",
+};
+pub const WAV: MediaFormat = MediaFormat {
+    kind: MediaKind::Audio,
+    extension: "wav",
+    mime: "audio/wav",
+};
+pub const OGG: MediaFormat = MediaFormat {
+    kind: MediaKind::Audio,
+    extension: "ogg",
+    mime: "audio/ogg",
+};
+pub const WEBM: MediaFormat = MediaFormat {
+    kind: MediaKind::Audio,
+    extension: "webm",
+    mime: "audio/webm",
+};
+pub const M4A: MediaFormat = MediaFormat {
+    kind: MediaKind::Audio,
+    extension: "m4a",
+    mime: "audio/mp4",
+};
+
+/// Longest side, in pixels, of a stored prompt image.
+pub const MAX_IMAGE_SIDE: u32 = 2048;
+
+/// Longest side, in pixels, of an image thumbnail.
+pub const THUMBNAIL_SIDE: u32 = 320;
+
+/// Quality photos are saved at when an image has to be rewritten.
+const JPEG_QUALITY: u8 = 90;
+
+/// Integrated loudness, in LUFS, question audio is brought to.
+pub const TARGET_LOUDNESS: f64 = -16.0;
+
+/// Highest sample peak, in dBFS, audio may reach once its gain is applied.
+const PEAK_CEILING: f64 = -1.0;
+
+/// An upload ready to store, in the format it ended up in.
+#[derive(Debug)]
+pub struct Prepared {
+    pub data: Vec<u8>,
+    pub format: MediaFormat,
+    /// The gain audio plays at, see `playback_gain`; `None` for images.
+    pub gain_db: Option<f64>,
+}
+
+/// Recognises a supported format from the first bytes of `data`.
+pub fn sniff(data: &[u8]) -> Option<MediaFormat> {
+    let riff_type = (data.len() >= 12 && &data[..4] == b"RIFF").then(|| &data[8..12]);
+    match data {
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(PNG),
+        [0xFF, 0xD8, 0xFF, ..] => Some(JPEG),
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(GIF),
+        _ if riff_type == Some(b"WEBP") => Some(WEBP),
+        _ if riff_type == Some(b"WAVE") => Some(WAV),
+        [b'O', b'g', b'g', b'S', ..] => Some(OGG),
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => Some(WEBM),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some(M4A),
+        [b'I', b'D', b'3', ..] => Some(MP3),
+        // An MPEG audio frame header: sync bits and a layer other than the
+        // reserved one.
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 && second & 0x06 != 0 => Some(MP3),
+        _ => None,
+    }
+}
+
+/// Sniffs `data` and decodes all of it, so a truncated or damaged file is
+/// caught and not only one with a wrong name.
+pub fn verify(data: &[u8]) -> Result<MediaFormat, String> {
+    let format = sniff(data).ok_or("The file is not a supported audio or image format")?;
+    match format.kind {
+        MediaKind::Image => decode_image(data, format).map(|_| ()),
+        MediaKind::Audio if format == WEBM => webm::duration_ms(data).map(|_| ()),
+        MediaKind::Audio => decode_audio(data, format).map(|_| ()),
+    }?;
+    Ok(format)
+}
+
+/// Checks an uploaded prompt image. One larger than `MAX_IMAGE_SIDE` is
+/// scaled down, and one carrying EXIF or XMP metadata, which can hold the
+/// place a photo was taken, is rewritten without it after its orientation is
+/// applied. Anything else is kept byte for byte.
+///
+/// Rewritten photos are saved as JPEG and other images as PNG; an animated
+/// GIF keeps only its first frame.
+pub fn prepare_image(data: &[u8]) -> Result<Prepared, String> {
+    let format = sniff_image(data)?;
+    let (mut image, orientation) = decode_with_orientation(data, format)?;
+
+    let oversized = image.width().max(image.height()) > MAX_IMAGE_SIDE;
+    if !oversized && !has_image_metadata(data, format) {
+        return Ok(Prepared {
+            data: data.to_vec(),
+            format,
+            gain_db: None,
+        });
+    }
+
+    image.apply_orientation(orientation);
+    if oversized {
+        image = image.resize(MAX_IMAGE_SIDE, MAX_IMAGE_SIDE, FilterType::Lanczos3);
+    }
+
+    encode_image(&image, matches!(format, JPEG | WEBP))
+}
+
+/// A preview of an image for lists, no larger than `THUMBNAIL_SIDE`, turned
+/// upright and without metadata. Saved as JPEG unless the image has
+/// transparency.
+pub fn thumbnail(data: &[u8]) -> Result<Prepared, String> {
+    let format = sniff_image(data)?;
+    let (mut image, orientation) = decode_with_orientation(data, format)?;
+
+    image.apply_orientation(orientation);
+    if image.width().max(image.height()) > THUMBNAIL_SIDE {
+        image = image.thumbnail(THUMBNAIL_SIDE, THUMBNAIL_SIDE);
+    }
+    encode_image(&image, true)
+}
+
+fn sniff_image(data: &[u8]) -> Result<MediaFormat, String> {
+    sniff(data)
+        .filter(|format| format.kind == MediaKind::Image)
+        .ok_or_else(|| "The file is not a PNG, JPEG, GIF or WebP image".to_string())
+}
+
+/// Decodes an image along with the orientation its metadata asks for.
+fn decode_with_orientation(
+    data: &[u8],
+    format: MediaFormat,
+) -> Result<(DynamicImage, Orientation), String> {
+    let error =
+        |e: image::ImageError| format!("The {} image cannot be decoded: {}", format.extension, e);
+
+    let mut decoder = ImageReader::with_format(Cursor::new(data), image_format(format))
+        .into_decoder()
+        .map_err(error)?;
+    let orientation = decoder.orientation().map_err(error)?;
+    let image = DynamicImage::from_decoder(decoder).map_err(error)?;
+    Ok((image, orientation))
+}
+
+/// Saves `image` as JPEG when it is a `photo` without transparency, and as
+/// PNG otherwise.
+fn encode_image(image: &DynamicImage, photo: bool) -> Result<Prepared, String> {
+    let mut encoded = Cursor::new(Vec::new());
+    let (written, format) = if photo && !image.color().has_alpha() {
+        let encoder = JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY);
+        (image.to_rgb8().write_with_encoder(encoder), JPEG)
+    } else {
+        (image.write_to(&mut encoded, ImageFormat::Png), PNG)
+    };
+    written.map_err(|e| format!("Failed to save the {} image: {}", format.extension, e))?;
+
+    Ok(Prepared {
+        data: encoded.into_inner(),
+        format,
+        gain_db: None,
+    })
+}
+
+/// Whether an image file has an EXIF, XMP or text block. Only the container
+/// is walked; the pixels are not decoded.
+fn has_image_metadata(data: &[u8], format: MediaFormat) -> bool {
+    let u16_be = |at: usize| {
+        data.get(at..at + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+    };
+    let u32_at = |at: usize, big_endian: bool| {
+        data.get(at..at + 4).map(|b| {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            if big_endian {
+                u32::from_be_bytes(bytes) as usize
+            } else {
+                u32::from_le_bytes(bytes) as usize
+            }
+        })
+    };
+
+    match format {
+        JPEG => {
+            let mut at = 2;
+            while let (Some(&0xFF), Some(&marker)) = (data.get(at), data.get(at + 1)) {
+                match marker {
+                    0xFF => at += 1,
+                    0x01 | 0xD0..=0xD7 => at += 2,
+                    // Start of scan: only pixel data follows.
+                    0xDA | 0xD9 => return false,
+                    // APP1 holds EXIF and XMP, APP13 Photoshop's IPTC block.
+                    0xE1 | 0xED => return true,
+                    _ => match u16_be(at + 2) {
+                        Some(length) => at += 2 + length,
+                        None => return false,
+                    },
+                }
+            }
+            false
+        }
+        PNG => {
+            let mut at = 8;
+            while let (Some(length), Some(kind)) = (u32_at(at, true), data.get(at + 4..at + 8)) {
+                match kind {
+                    b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" => return true,
+                    b"IEND" => return false,
+                    _ => at += 12 + length,
+                }
+            }
+            false
+        }
+        WEBP => {
+            let mut at = 12;
+            while let (Some(kind), Some(length)) = (data.get(at..at + 4), u32_at(at + 4, false)) {
+                if kind == b"EXIF" || kind == b"XMP " {
+                    return true;
+                }
+                at += 8 + length + (length & 1);
+            }
+            false
+        }
+        _ => false,
+    }
+}
+
+/// Checks uploaded question audio, which is then kept byte for byte, and
+/// measures the gain it plays at, see `playback_gain`.
+///
+/// WebM is refused, since there is no Opus decoder to measure its loudness
+/// with.
+pub fn prepare_audio(data: &[u8]) -> Result<Prepared, String> {
+    let format = sniff(data)
+        .filter(|format| format.kind == MediaKind::Audio && *format != WEBM)
+        .ok_or("The file is not an MP3, WAV, Ogg or M4A recording")?;
+    let samples = decode_audio(data, format)?;
+    Ok(Prepared {
+        data: data.to_vec(),
+        format,
+        gain_db: Some(normalising_gain(&samples)),
+    })
+}
+
+/// The gain, in dB, to play question audio at so every prompt sounds equally
+/// loud in the exam room: it brings the audio to `TARGET_LOUDNESS`, but stops
+/// short of pushing its peak over `PEAK_CEILING`, so a clip with sharp peaks
+/// may stay quieter. Clips too short or too quiet to measure, and WebM, play
+/// as they are.
+pub fn playback_gain(data: &[u8]) -> Result<f64, String> {
+    let format = sniff(data)
+        .filter(|format| format.kind == MediaKind::Audio)
+        .ok_or("The file is not a supported audio format")?;
+    if format == WEBM {
+        return Ok(0.0);
+    }
+    Ok(normalising_gain(&decode_audio(data, format)?))
+}
+
+fn image_format(format: MediaFormat) -> ImageFormat {
+    match format {
+        JPEG => ImageFormat::Jpeg,
+        GIF => ImageFormat::Gif,
+        WEBP => ImageFormat::WebP,
+        _ => ImageFormat::Png,
+    }
+}
+
+/// Decodes an image in one of the supported formats.
+pub fn decode_image(data: &[u8], format: MediaFormat) -> Result<image::DynamicImage, String> {
+    image::load_from_memory_with_format(data, image_format(format))
+        .map_err(|e| format!("The {} image cannot be decoded: {}", format.extension, e))
+}
+
+/// Decoded audio, mixed down to one channel.
+struct Audio {
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+/// Decodes every packet of the first audio track and fails when none of it
+/// plays.
+fn decode_audio(data: &[u8], format: MediaFormat) -> Result<Audio, String> {
+    let error =
+        |e: SymphoniaError| format!("The {} audio cannot be decoded: {}", format.extension, e);
+
+    let source = MediaSourceStream::new(Box::new(Cursor::new(data.to_vec())), Default::default());
+    let mut hint = Hint::new();
+    hint.with_extension(format.extension);
+    let mut reader = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(error)?
+        .format;
+
+    let track = reader
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| format!("The {} file has no audio track", format.extension))?;
+    let track_id = track.id;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(error)?;
+
+    let mut audio = Audio {
+        sample_rate: 0,
+        samples: Vec::new(),
+    };
+    loop {
+        let packet = match reader.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(error(e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A damaged packet is skipped by players too.
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(error(e)),
+        };
+
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        if audio.sample_rate == 0 {
+            audio.sample_rate = spec.rate;
+        }
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        audio.samples.extend(
+            buffer
+                .samples()
+                .chunks(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+
+    if audio.samples.is_empty() || audio.sample_rate == 0 {
+        return Err(format!("The {} file contains no audio", format.extension));
+    }
+    Ok(audio)
+}
+
+/// The gain, in dB, that brings `audio` to `TARGET_LOUDNESS` without its
+/// peak going over `PEAK_CEILING`.
+fn normalising_gain(audio: &Audio) -> f64 {
+    let Some(loudness) = integrated_loudness(audio) else {
+        return 0.0;
+    };
+    let peak = audio
+        .samples
+        .iter()
+        .fold(0.0f64, |peak, sample| peak.max(sample.abs() as f64));
+    (TARGET_LOUDNESS - loudness).min(PEAK_CEILING - 20.0 * peak.log10())
+}
+
+/// Integrated loudness of `audio` in LUFS, as ITU-R BS.1770 measures it:
+/// K-weighted 400 ms blocks, gated at -70 LUFS and again at 10 LU below the
+/// mean of the blocks left. `None` for audio shorter than one block or
+/// quieter than the gate.
+fn integrated_loudness(audio: &Audio) -> Option<f64> {
+    let rate = audio.sample_rate as f64;
+    let mut shelf = Biquad::high_shelf(rate);
+    let mut high_pass = Biquad::high_pass(rate);
+    let energy: Vec<f64> = audio
+        .samples
+        .iter()
+        .map(|&sample| high_pass.process(shelf.process(sample as f64)).powi(2))
+        .collect();
+
+    let block = (rate * 0.4) as usize;
+    let step = (rate * 0.1) as usize;
+    if step == 0 || energy.len() < block {
+        return None;
+    }
+    let blocks: Vec<f64> = (0..=energy.len() - block)
+        .step_by(step)
+        .map(|start| energy[start..start + block].iter().sum::<f64>() / block as f64)
+        .collect();
+
+    let loudness = |energy: f64| -0.691 + 10.0 * energy.log10();
+    let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+    let audible: Vec<f64> = blocks
+        .into_iter()
+        .filter(|&b| loudness(b) > -70.0)
+        .collect();
+    if audible.is_empty() {
+        return None;
+    }
+    let relative_gate = loudness(mean(&audible)) - 10.0;
+    let gated: Vec<f64> = audible
+        .into_iter()
+        .filter(|&b| loudness(b) > relative_gate)
+        .collect();
+    Some(loudness(mean(&gated)))
+}
+
+/// One second-order filter stage of the K-weighting curve.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    inputs: [f64; 2],
+    outputs: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b: [b[0] / a[0], b[1] / a[0], b[2] / a[0]],
+            a: [a[1] / a[0], a[2] / a[0]],
+            inputs: [0.0; 2],
+            outputs: [0.0; 2],
+        }
+    }
+
+    /// The first stage, a +4 dB shelf above about 1.7 kHz. The constants
+    /// reproduce the coefficients BS.1770 gives for 48 kHz at any rate.
+    fn high_shelf(rate: f64) -> Self {
+        let k = (std::f64::consts::PI * 1681.974450955533 / rate).tan();
+        let q = 0.7071752369554196;
+        let high_gain = 10f64.powf(3.999843853973347 / 20.0);
+        let band_gain = high_gain.powf(0.4996667741545416);
+        Biquad::new(
+            [
+                high_gain + band_gain * k / q + k * k,
+                2.0 * (k * k - high_gain),
+                high_gain - band_gain * k / q + k * k,
+            ],
+            [
+                1.0 + k / q + k * k,
+                2.0 * (k * k - 1.0),
+                1.0 - k / q + k * k,
+            ],
+        )
+    }
+
+    /// The second stage, a high-pass at about 38 Hz.
+    fn high_pass(rate: f64) -> Self {
+        let k = (std::f64::consts::PI * 38.13547087602444 / rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+        Biquad::new(
+            [a0, -2.0 * a0, a0],
+            [a0, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        )
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.inputs[0] + self.b[2] * self.inputs[1]
+            - self.a[0] * self.outputs[0]
+            - self.a[1] * self.outputs[1];
+        self.inputs = [input, self.inputs[0]];
+        self.outputs = [output, self.outputs[0]];
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests;
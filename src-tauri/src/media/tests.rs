@@ -0,0 +1,224 @@
+//! Recognises formats from their first bytes, decodes small files built in
+//! memory, whole and damaged, and prepares uploads from them.
+
+use super::*;
+
+/// A mono 16-bit PCM WAV file at 8 kHz.
+fn wav(samples: &[i16]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&8000u32.to_le_bytes());
+    bytes.extend_from_slice(&16000u32.to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    bytes
+}
+
+/// `seconds` of a 440 Hz tone at 8 kHz, peaking at `amplitude` of full scale.
+fn tone(amplitude: f32, seconds: f32) -> Vec<i16> {
+    (0..(8000.0 * seconds) as usize)
+        .map(|i| {
+            let phase = 2.0 * std::f32::consts::PI * 440.0 * i as f32 / 8000.0;
+            (phase.sin() * amplitude * i16::MAX as f32) as i16
+        })
+        .collect()
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let image = image::RgbImage::from_pixel(width, height, image::Rgb([200, 30, 30]));
+    let mut bytes = Cursor::new(Vec::new());
+    image.write_to(&mut bytes, ImageFormat::Png).unwrap();
+    bytes.into_inner()
+}
+
+/// A JPEG photo whose EXIF block says to turn it a quarter clockwise.
+fn rotated_jpeg(width: u32, height: u32) -> Vec<u8> {
+    let image = image::RgbImage::from_pixel(width, height, image::Rgb([30, 200, 30]));
+    let mut encoded = Cursor::new(Vec::new());
+    image.write_to(&mut encoded, ImageFormat::Jpeg).unwrap();
+    let encoded = encoded.into_inner();
+
+    let mut exif = b"Exif\0\0II*\0\x08\0\0\0\x01\0".to_vec();
+    // Orientation (0x0112), one SHORT: 6, rotate 90 degrees clockwise.
+    exif.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+    exif.extend_from_slice(&[0, 0, 0, 0]);
+
+    let mut bytes = encoded[..2].to_vec();
+    bytes.extend_from_slice(&[0xFF, 0xE1]);
+    bytes.extend_from_slice(&(exif.len() as u16 + 2).to_be_bytes());
+    bytes.extend_from_slice(&exif);
+    bytes.extend_from_slice(&encoded[2..]);
+    bytes
+}
+
+fn loudness_of(data: &[u8]) -> Option<f64> {
+    integrated_loudness(&decode_audio(data, WAV).unwrap())
+}
+
+fn peak_of(data: &[u8]) -> f32 {
+    let audio = decode_audio(data, WAV).unwrap();
+    audio.samples.iter().fold(0.0, |peak, s| peak.max(s.abs()))
+}
+
+#[test]
+fn sniffs_formats_from_magic_bytes() {
+    assert_eq!(sniff(&png(2, 2)), Some(PNG));
+    assert_eq!(sniff(&[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10]), Some(JPEG));
+    assert_eq!(sniff(b"GIF89a\x01\x00"), Some(GIF));
+    assert_eq!(sniff(b"RIFF\x10\x00\x00\x00WEBPVP8 "), Some(WEBP));
+    assert_eq!(sniff(&wav(&[0; 10])), Some(WAV));
+    assert_eq!(sniff(b"OggS\x00\x02"), Some(OGG));
+    assert_eq!(sniff(&[0x1A, 0x45, 0xDF, 0xA3, 0x9F]), Some(WEBM));
+    assert_eq!(sniff(b"\x00\x00\x00\x20ftypM4A "), Some(M4A));
+    assert_eq!(sniff(b"ID3\x04\x00"), Some(MP3));
+    assert_eq!(sniff(&[0xFF, 0xFB, 0x90, 0x64]), Some(MP3));
+}
+
+#[test]
+fn does_not_guess_unknown_content() {
+    assert_eq!(sniff(b""), None);
+    assert_eq!(sniff(b"RIFF"), None);
+    assert_eq!(sniff(b"RIFF\x10\x00\x00\x00AVI LIST"), None);
+    assert_eq!(sniff(b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>"), None);
+    assert_eq!(sniff(b"MZ\x90\x00"), None);
+    assert_eq!(sniff(&[0xFF, 0xE0]), None);
+}
+
+#[test]
+fn verifies_files_that_decode() {
+    assert_eq!(verify(&png(4, 3)), Ok(PNG));
+    assert_eq!(verify(&wav(&[0; 800])), Ok(WAV));
+}
+
+#[test]
+fn rejects_damaged_files() {
+    let image = png(16, 16);
+    assert!(verify(&image[..image.len() / 2]).is_err());
+
+    assert!(verify(&wav(&[])).is_err());
+    assert!(verify(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00]).is_err());
+    assert!(verify(b"not media at all").is_err());
+}
+
+#[test]
+fn keeps_small_images_without_metadata() {
+    let image = png(40, 30);
+    let prepared = prepare_image(&image).unwrap();
+
+    assert_eq!(prepared.format, PNG);
+    assert_eq!(prepared.data, image);
+}
+
+#[test]
+fn scales_down_large_images() {
+    let prepared = prepare_image(&png(MAX_IMAGE_SIDE * 2, 100)).unwrap();
+    let image = decode_image(&prepared.data, prepared.format).unwrap();
+
+    assert_eq!(prepared.format, PNG);
+    assert_eq!((image.width(), image.height()), (MAX_IMAGE_SIDE, 50));
+}
+
+#[test]
+fn strips_exif_after_applying_its_orientation() {
+    let photo = rotated_jpeg(8, 4);
+    assert!(has_image_metadata(&photo, JPEG));
+
+    let prepared = prepare_image(&photo).unwrap();
+    let image = decode_image(&prepared.data, prepared.format).unwrap();
+
+    assert_eq!(prepared.format, JPEG);
+    assert!(!has_image_metadata(&prepared.data, JPEG));
+    assert_eq!((image.width(), image.height()), (4, 8));
+}
+
+#[test]
+fn refuses_uploads_of_the_wrong_kind() {
+    assert!(prepare_image(&wav(&tone(0.5, 1.0))).is_err());
+    assert!(prepare_image(b"<svg/>").is_err());
+    assert!(prepare_audio(&png(4, 4)).is_err());
+    assert!(prepare_audio(b"not audio").is_err());
+}
+
+#[test]
+fn keeps_uploaded_audio_as_it_is() {
+    let recording = wav(&tone(0.5, 1.0));
+    let prepared = prepare_audio(&recording).unwrap();
+
+    assert_eq!(prepared.format, WAV);
+    assert_eq!(prepared.data, recording);
+    assert_eq!(prepared.gain_db, Some(playback_gain(&recording).unwrap()));
+}
+
+#[test]
+fn refuses_webm_prompts() {
+    let error = prepare_audio(&[0x1A, 0x45, 0xDF, 0xA3, 0x9F]).unwrap_err();
+    assert!(error.contains("not an MP3, WAV, Ogg or M4A"), "{error}");
+}
+
+#[test]
+fn gain_brings_audio_to_the_target_loudness() {
+    for amplitude in [0.02, 0.9] {
+        let recording = wav(&tone(amplitude, 2.0));
+        let loudness = loudness_of(&recording).unwrap() + playback_gain(&recording).unwrap();
+
+        assert!(
+            (loudness - TARGET_LOUDNESS).abs() < 0.5,
+            "{amplitude} would play at {loudness} LUFS"
+        );
+    }
+}
+
+#[test]
+fn gain_never_clips() {
+    // A quiet tone with one full-scale click: reaching the target would push
+    // the click far past full scale.
+    let mut samples = tone(0.01, 2.0);
+    samples[4000] = i16::MAX;
+    let recording = wav(&samples);
+    let gain = playback_gain(&recording).unwrap();
+
+    assert!(20.0 * (peak_of(&recording) as f64).log10() + gain <= PEAK_CEILING + 0.01);
+    assert!(loudness_of(&recording).unwrap() + gain < TARGET_LOUDNESS);
+}
+
+#[test]
+fn leaves_silence_as_it_is() {
+    assert_eq!(playback_gain(&wav(&[0; 8000])), Ok(0.0));
+}
+
+#[test]
+fn thumbnails_fit_the_preview_size() {
+    let prepared = thumbnail(&png(THUMBNAIL_SIDE * 4, THUMBNAIL_SIDE)).unwrap();
+    let image = decode_image(&prepared.data, prepared.format).unwrap();
+    assert_eq!(prepared.format, JPEG);
+    assert_eq!(
+        (image.width(), image.height()),
+        (THUMBNAIL_SIDE, THUMBNAIL_SIDE / 4)
+    );
+
+    let prepared = thumbnail(&rotated_jpeg(8, 4)).unwrap();
+    let image = decode_image(&prepared.data, prepared.format).unwrap();
+    assert_eq!((image.width(), image.height()), (4, 8));
+}
+
+#[test]
+fn thumbnails_keep_transparency() {
+    let image = image::RgbaImage::from_pixel(600, 400, image::Rgba([0, 0, 0, 0]));
+    let mut encoded = Cursor::new(Vec::new());
+    image.write_to(&mut encoded, ImageFormat::Png).unwrap();
+
+    let prepared = thumbnail(&encoded.into_inner()).unwrap();
+    assert_eq!(prepared.format, PNG);
+    assert!(thumbnail(&wav(&[0; 100])).is_err());
+}
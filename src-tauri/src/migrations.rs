@@ -0,0 +1,903 @@
+use rusqlite::{params, Connection, Transaction};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One schema change. Migrations run in `version` order, each inside its own
+/// transaction, and bump `PRAGMA user_version` when they commit.
+struct Migration {
+    version: i32,
+    description: &'static str,
+    apply: fn(&Transaction) -> Result<(), String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AppliedMigration {
+    pub version: i32,
+    pub description: String,
+    pub applied_at: String,
+}
+
+const MAX_BACKUPS: usize = 10;
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Initial exam schema",
+        apply: initial_schema,
+    },
+    Migration {
+        version: 2,
+        description: "Telegram delivery outbox",
+        apply: telegram_delivery_outbox,
+    },
+    Migration {
+        version: 3,
+        description: "Indexes for attempt, response and delivery lookups",
+        apply: lookup_indexes,
+    },
+    Migration {
+        version: 4,
+        description: "Content IDs for questions and full tests, asset hashes",
+        apply: content_identity,
+    },
+    Migration {
+        version: 5,
+        description: "Question revision history",
+        apply: question_revisions,
+    },
+    Migration {
+        version: 6,
+        description: "Full test link on attempts, question order within sections",
+        apply: full_test_editing,
+    },
+    Migration {
+        version: 7,
+        description: "Attempt mode and served questions",
+        apply: attempt_forms,
+    },
+    Migration {
+        version: 8,
+        description: "Attempt lifecycle states",
+        apply: attempt_states,
+    },
+    Migration {
+        version: 9,
+        description: "Response timing checks",
+        apply: response_timing,
+    },
+    Migration {
+        version: 10,
+        description: "Exam blueprints",
+        apply: exam_blueprints,
+    },
+    Migration {
+        version: 11,
+        description: "Question preparation time",
+        apply: question_prep_time,
+    },
+    Migration {
+        version: 12,
+        description: "Attempt selection seeds",
+        apply: selection_seeds,
+    },
+    Migration {
+        version: 13,
+        description: "Asset references",
+        apply: asset_references,
+    },
+    Migration {
+        version: 14,
+        description: "Telegram delivery progress",
+        apply: telegram_delivery_progress,
+    },
+    Migration {
+        version: 15,
+        description: "Attempt draws",
+        apply: attempt_draws,
+    },
+    Migration {
+        version: 16,
+        description: "Asset references from question history",
+        apply: history_asset_references,
+    },
+    Migration {
+        version: 17,
+        description: "Question audio playback gain",
+        apply: audio_playback_gain,
+    },
+];
+
+pub fn latest_schema_version() -> i32 {
+    MIGRATIONS
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or(0)
+}
+
+pub fn schema_version(conn: &Connection) -> Result<i32, String> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// Brings the database up to the latest schema. A copy of the database is
+/// written to `backups/` before the first pending migration runs, so a failed
+/// upgrade never leaves an admin without their data.
+pub fn run_migrations(conn: &mut Connection, app_dir: &Path) -> Result<(), String> {
+    let current_version = schema_version(conn)?;
+    let latest_version = latest_schema_version();
+
+    if current_version > latest_version {
+        return Err(format!(
+            "The database uses schema version {} but this app only supports up to {}. Update the app before opening this database.",
+            current_version, latest_version
+        ));
+    }
+
+    if current_version == latest_version {
+        return Ok(());
+    }
+
+    if has_user_tables(conn)? {
+        backup_database(conn, app_dir, current_version)?;
+    }
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| migration.version > current_version)
+    {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        (migration.apply)(&tx).map_err(|e| {
+            format!(
+                "Database migration {} ({}) failed: {}",
+                migration.version, migration.description, e
+            )
+        })?;
+
+        tx.execute(
+            "INSERT OR REPLACE INTO schema_migrations (version, description) VALUES (?, ?)",
+            params![migration.version, migration.description],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        println!(
+            "Applied database migration {}: {}",
+            migration.version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+pub fn applied_migrations(conn: &Connection) -> Result<Vec<AppliedMigration>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT version, description, applied_at
+             FROM schema_migrations
+             ORDER BY version ASC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let migrations = stmt
+        .query_map([], |row| {
+            Ok(AppliedMigration {
+                version: row.get(0)?,
+                description: row.get(1)?,
+                applied_at: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(migrations)
+}
+
+fn has_user_tables(conn: &Connection) -> Result<bool, String> {
+    let count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(count > 0)
+}
+
+fn backup_database(
+    conn: &Connection,
+    app_dir: &Path,
+    from_version: i32,
+) -> Result<PathBuf, String> {
+    let backup_dir = app_dir.join("backups");
+    fs::create_dir_all(&backup_dir).map_err(|e| e.to_string())?;
+
+    let backup_path = backup_dir.join(format!(
+        "exam-v{}-{}.db",
+        from_version,
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    if backup_path.exists() {
+        fs::remove_file(&backup_path).map_err(|e| e.to_string())?;
+    }
+
+    conn.execute(
+        "VACUUM INTO ?",
+        params![backup_path.to_string_lossy().to_string()],
+    )
+    .map_err(|e| format!("Failed to back up database before migrating: {}", e))?;
+
+    prune_backups(&backup_dir);
+    Ok(backup_path)
+}
+
+fn prune_backups(backup_dir: &Path) {
+    let Ok(entries) = fs::read_dir(backup_dir) else {
+        return;
+    };
+
+    let mut backups = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(|name| name.starts_with("exam-v") && name.ends_with(".db"))
+                .unwrap_or(false)
+        })
+        .collect::<Vec<_>>();
+
+    if backups.len() <= MAX_BACKUPS {
+        return;
+    }
+
+    backups.sort_by_key(|path| {
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    });
+    for path in &backups[..backups.len() - MAX_BACKUPS] {
+        if let Err(error) = fs::remove_file(path) {
+            println!("Warning: failed to remove old backup {:?}: {}", path, error);
+        }
+    }
+}
+
+fn column_exists(tx: &Transaction, table: &str, column: &str) -> Result<bool, String> {
+    let mut stmt = tx
+        .prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query([]).map_err(|e| e.to_string())?;
+
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let name: String = row.get(1).map_err(|e| e.to_string())?;
+        if name == column {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+fn add_column_if_missing(
+    tx: &Transaction,
+    table: &str,
+    column: &str,
+    column_def: &str,
+) -> Result<(), String> {
+    if column_exists(tx, table, column)? {
+        return Ok(());
+    }
+
+    tx.execute(
+        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, column_def),
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// Databases created before versioning have user_version 0 and may be missing
+// any of the columns that used to be added on the fly, so this migration has
+// to tolerate every earlier shape of the schema.
+fn initial_schema(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS attempts (
+            id INTEGER PRIMARY KEY,
+            student_name TEXT NOT NULL,
+            started_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            finished_at DATETIME
+        );
+
+        CREATE TABLE IF NOT EXISTS questions (
+            id INTEGER PRIMARY KEY,
+            part INTEGER NOT NULL,
+            sub_part INTEGER NOT NULL DEFAULT 0,
+            audio_path TEXT NOT NULL,
+            image_path TEXT NOT NULL DEFAULT '',
+            text TEXT NOT NULL DEFAULT '',
+            pack_id TEXT NOT NULL DEFAULT '',
+            pack_order INTEGER NOT NULL DEFAULT 0,
+            response_time INTEGER NOT NULL,
+            active BOOLEAN DEFAULT 1
+        );
+
+        CREATE TABLE IF NOT EXISTS responses (
+            id INTEGER PRIMARY KEY,
+            attempt_id INTEGER NOT NULL,
+            question_id INTEGER NOT NULL,
+            audio_path TEXT NOT NULL,
+            duration INTEGER NOT NULL,
+            recorded_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (attempt_id) REFERENCES attempts (id),
+            FOREIGN KEY (question_id) REFERENCES questions (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS ratings (
+            response_id INTEGER PRIMARY KEY,
+            fluency INTEGER CHECK (fluency BETWEEN 1 AND 9),
+            lexical INTEGER CHECK (lexical BETWEEN 1 AND 9),
+            grammar INTEGER CHECK (grammar BETWEEN 1 AND 9),
+            pronunciation INTEGER CHECK (pronunciation BETWEEN 1 AND 9),
+            comment TEXT,
+            FOREIGN KEY (response_id) REFERENCES responses (id)
+        );
+
+        CREATE TABLE IF NOT EXISTS app_settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL DEFAULT ''
+        );
+
+        CREATE TABLE IF NOT EXISTS full_tests (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            active BOOLEAN DEFAULT 1
+        );
+
+        CREATE TABLE IF NOT EXISTS full_test_questions (
+            full_test_id INTEGER NOT NULL,
+            question_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (full_test_id, question_id),
+            FOREIGN KEY (full_test_id) REFERENCES full_tests (id),
+            FOREIGN KEY (question_id) REFERENCES questions (id)
+        );",
+    )
+    .map_err(|e| e.to_string())?;
+
+    add_column_if_missing(tx, "questions", "sub_part", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(tx, "questions", "image_path", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(tx, "questions", "text", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(tx, "questions", "pack_id", "TEXT NOT NULL DEFAULT ''")?;
+    add_column_if_missing(tx, "questions", "pack_order", "INTEGER NOT NULL DEFAULT 0")?;
+
+    // Part 1 questions saved before sub-parts existed belong to Part 1.1.
+    tx.execute(
+        "UPDATE questions SET sub_part = 1 WHERE part = 1 AND sub_part = 0",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+fn telegram_delivery_outbox(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS telegram_deliveries (
+            id INTEGER PRIMARY KEY,
+            response_id INTEGER NOT NULL,
+            chat_id TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT NOT NULL DEFAULT '',
+            next_attempt_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            sent_at DATETIME,
+            UNIQUE (response_id, chat_id),
+            FOREIGN KEY (response_id) REFERENCES responses (id)
+        );",
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn lookup_indexes(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_responses_attempt ON responses (attempt_id);
+        CREATE INDEX IF NOT EXISTS idx_responses_question ON responses (question_id);
+        CREATE INDEX IF NOT EXISTS idx_questions_section ON questions (part, sub_part, active);
+        CREATE INDEX IF NOT EXISTS idx_questions_pack ON questions (pack_id, pack_order);
+        CREATE INDEX IF NOT EXISTS idx_full_test_questions_position
+            ON full_test_questions (full_test_id, position);
+        CREATE INDEX IF NOT EXISTS idx_telegram_deliveries_due
+            ON telegram_deliveries (status, next_attempt_at);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Imports match questions and full tests across centres by `uuid`, so every
+// existing row gets one here. `asset_hashes` lets an import reuse a file that
+// is already stored instead of writing a second copy.
+fn content_identity(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(tx, "questions", "uuid", "TEXT")?;
+    add_column_if_missing(tx, "full_tests", "uuid", "TEXT")?;
+
+    for table in ["questions", "full_tests"] {
+        let ids = {
+            let mut stmt = tx
+                .prepare(&format!("SELECT id FROM {} WHERE uuid IS NULL", table))
+                .map_err(|e| e.to_string())?;
+            let ids = stmt
+                .query_map([], |row| row.get::<_, i64>(0))
+                .map_err(|e| e.to_string())?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| e.to_string())?;
+            ids
+        };
+
+        for id in ids {
+            tx.execute(
+                &format!("UPDATE {} SET uuid = ? WHERE id = ?", table),
+                params![uuid::Uuid::new_v4().to_string(), id],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    tx.execute_batch(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_questions_uuid ON questions (uuid);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_full_tests_uuid ON full_tests (uuid);
+
+        CREATE TABLE IF NOT EXISTS asset_hashes (
+            folder TEXT NOT NULL,
+            filename TEXT NOT NULL,
+            sha256 TEXT NOT NULL,
+            PRIMARY KEY (folder, filename)
+        );
+        CREATE INDEX IF NOT EXISTS idx_asset_hashes_sha256 ON asset_hashes (folder, sha256);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Every saved version of a question is kept so a response can show the
+// wording the candidate actually heard, even after the question is edited.
+// Existing questions start at revision 1 and their responses point at it.
+fn question_revisions(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS question_revisions (
+            id INTEGER PRIMARY KEY,
+            question_id INTEGER NOT NULL,
+            revision INTEGER NOT NULL,
+            part INTEGER NOT NULL,
+            sub_part INTEGER NOT NULL DEFAULT 0,
+            audio_path TEXT NOT NULL,
+            image_path TEXT NOT NULL DEFAULT '',
+            text TEXT NOT NULL DEFAULT '',
+            pack_id TEXT NOT NULL DEFAULT '',
+            pack_order INTEGER NOT NULL DEFAULT 0,
+            response_time INTEGER NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (question_id) REFERENCES questions (id),
+            UNIQUE (question_id, revision)
+        );
+
+        INSERT INTO question_revisions
+            (question_id, revision, part, sub_part, audio_path, image_path, text, pack_id, pack_order, response_time)
+        SELECT id, 1, part, COALESCE(sub_part, 0), COALESCE(audio_path, ''), COALESCE(image_path, ''),
+               COALESCE(text, ''), COALESCE(pack_id, ''), COALESCE(pack_order, 0), response_time
+        FROM questions
+        WHERE id NOT IN (SELECT question_id FROM question_revisions);",
+    )
+    .map_err(|e| e.to_string())?;
+
+    add_column_if_missing(
+        tx,
+        "responses",
+        "question_revision_id",
+        "INTEGER REFERENCES question_revisions (id)",
+    )?;
+
+    tx.execute_batch(
+        "UPDATE responses
+         SET question_revision_id = (
+             SELECT id FROM question_revisions
+             WHERE question_revisions.question_id = responses.question_id
+             ORDER BY revision DESC LIMIT 1
+         )
+         WHERE question_revision_id IS NULL;",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Attempts now record the full test they were started from, so tests that
+// candidates have taken can be protected from edits. Older attempts are
+// linked when the questions they answered belong to exactly one test.
+//
+// `position` used to break ties after `pack_order`; it is renumbered here to
+// the order admins already see so it alone orders questions within a section.
+fn full_test_editing(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(
+        tx,
+        "attempts",
+        "full_test_id",
+        "INTEGER REFERENCES full_tests (id)",
+    )?;
+
+    tx.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_attempts_full_test_id ON attempts (full_test_id);
+
+        WITH answered AS (
+            SELECT attempt_id, COUNT(DISTINCT question_id) AS question_count
+            FROM responses
+            GROUP BY attempt_id
+        ),
+        matches AS (
+            SELECT responses.attempt_id, full_test_questions.full_test_id
+            FROM responses
+            JOIN full_test_questions ON full_test_questions.question_id = responses.question_id
+            JOIN answered ON answered.attempt_id = responses.attempt_id
+            GROUP BY responses.attempt_id, full_test_questions.full_test_id
+            HAVING COUNT(DISTINCT responses.question_id) = MAX(answered.question_count)
+        ),
+        unique_matches AS (
+            SELECT attempt_id, MIN(full_test_id) AS full_test_id
+            FROM matches
+            GROUP BY attempt_id
+            HAVING COUNT(*) = 1
+        )
+        UPDATE attempts
+        SET full_test_id = (
+            SELECT full_test_id FROM unique_matches WHERE unique_matches.attempt_id = attempts.id
+        )
+        WHERE full_test_id IS NULL
+          AND id IN (SELECT attempt_id FROM unique_matches);
+
+        UPDATE full_test_questions
+        SET position = (
+            SELECT ranked.new_position
+            FROM (
+                SELECT full_test_questions.full_test_id,
+                       full_test_questions.question_id,
+                       ROW_NUMBER() OVER (
+                           PARTITION BY full_test_questions.full_test_id
+                           ORDER BY questions.part,
+                                    questions.sub_part,
+                                    CASE WHEN questions.pack_order > 0 THEN questions.pack_order ELSE 999999 END,
+                                    full_test_questions.position,
+                                    questions.id
+                       ) AS new_position
+                FROM full_test_questions
+                JOIN questions ON full_test_questions.question_id = questions.id
+            ) AS ranked
+            WHERE ranked.full_test_id = full_test_questions.full_test_id
+              AND ranked.question_id = full_test_questions.question_id
+        )
+        WHERE EXISTS (SELECT 1 FROM questions WHERE questions.id = full_test_questions.question_id);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// An attempt records how it was started and every question shown to the
+// candidate, in order, so unanswered questions can be told apart from ones
+// that were never served. The mode of older attempts is unknown unless they
+// were linked to a full test; their served list is rebuilt from responses.
+fn attempt_forms(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(tx, "attempts", "mode", "TEXT")?;
+
+    tx.execute_batch(
+        "UPDATE attempts SET mode = 'full_test' WHERE mode IS NULL AND full_test_id IS NOT NULL;
+
+        CREATE TABLE IF NOT EXISTS attempt_questions (
+            attempt_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            question_id INTEGER NOT NULL,
+            question_revision_id INTEGER,
+            served_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (attempt_id, position),
+            UNIQUE (attempt_id, question_id),
+            FOREIGN KEY (attempt_id) REFERENCES attempts (id),
+            FOREIGN KEY (question_id) REFERENCES questions (id),
+            FOREIGN KEY (question_revision_id) REFERENCES question_revisions (id)
+        );
+
+        INSERT OR IGNORE INTO attempt_questions
+            (attempt_id, position, question_id, question_revision_id, served_at)
+        SELECT attempt_id,
+               ROW_NUMBER() OVER (PARTITION BY attempt_id ORDER BY first_recorded_at, first_response_id),
+               question_id,
+               question_revision_id,
+               first_recorded_at
+        FROM (
+            SELECT attempt_id,
+                   question_id,
+                   MIN(question_revision_id) AS question_revision_id,
+                   MIN(recorded_at) AS first_recorded_at,
+                   MIN(id) AS first_response_id
+            FROM responses
+            GROUP BY attempt_id, question_id
+        );",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Attempts move through explicit states instead of being told apart only by
+// `finished_at`. Unfinished attempts from before this change cannot be
+// resumed reliably, so they are recorded as abandoned.
+fn attempt_states(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(tx, "attempts", "status", "TEXT NOT NULL DEFAULT 'created'")?;
+
+    tx.execute_batch(
+        "UPDATE attempts SET status = 'finished' WHERE finished_at IS NOT NULL;
+        UPDATE attempts SET status = 'abandoned' WHERE finished_at IS NULL;
+        CREATE INDEX IF NOT EXISTS idx_attempts_status ON attempts (status);",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// The duration of a response is measured from its audio on the backend. What
+// the exam screen reported is kept alongside, with flags for recordings whose
+// timing does not add up. Older responses only have the reported duration.
+fn response_timing(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(tx, "responses", "claimed_duration", "INTEGER")?;
+    add_column_if_missing(tx, "responses", "audio_duration_ms", "INTEGER")?;
+    add_column_if_missing(tx, "responses", "timing_flags", "TEXT NOT NULL DEFAULT ''")?;
+
+    tx.execute(
+        "UPDATE responses SET claimed_duration = duration WHERE claimed_duration IS NULL",
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Exam formats used to be hard-coded as Parts 1.1, 1.2, 2 and 3. They are now
+// blueprints, and the original format is seeded as the default so existing
+// full tests and attempts keep their meaning.
+fn exam_blueprints(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS blueprints (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT NOT NULL DEFAULT '',
+            is_default INTEGER NOT NULL DEFAULT 0,
+            active INTEGER NOT NULL DEFAULT 1,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS blueprint_sections (
+            blueprint_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            part INTEGER NOT NULL,
+            sub_part INTEGER NOT NULL DEFAULT 0,
+            label TEXT NOT NULL,
+            question_count INTEGER NOT NULL,
+            selection TEXT NOT NULL DEFAULT 'random',
+            prep_time INTEGER NOT NULL DEFAULT 0,
+            image_required INTEGER NOT NULL DEFAULT 0,
+            instruction_audio TEXT NOT NULL DEFAULT '',
+            instruction_text TEXT NOT NULL DEFAULT '',
+            PRIMARY KEY (blueprint_id, position),
+            UNIQUE (blueprint_id, part, sub_part),
+            FOREIGN KEY (blueprint_id) REFERENCES blueprints (id)
+        );",
+    )
+    .map_err(|e| e.to_string())?;
+
+    let has_blueprints: bool = tx
+        .query_row("SELECT EXISTS (SELECT 1 FROM blueprints)", [], |row| {
+            row.get(0)
+        })
+        .map_err(|e| e.to_string())?;
+    if !has_blueprints {
+        tx.execute(
+            "INSERT INTO blueprints (name, description, is_default)
+             VALUES ('CEFR Multilevel', 'Parts 1.1, 1.2, 2 and 3 of the CEFR speaking exam.', 1)",
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+        let blueprint_id = tx.last_insert_rowid();
+
+        let sections = [
+            (1, 1, "Part 1.1", 3, "pack", 5, false, "part_1.1.mp3",
+             "Part 1.1. In this part, I will ask you a few questions about yourself. For each question, you will have 30 seconds to answer. You should speak after this sound."),
+            (1, 2, "Part 1.2", 3, "pack", 5, true, "part_1.2.mp3",
+             "Part 1.2 You will now see two pictures. You will need to answer some questions based on these pictures. You will have 30 seconds to answer each question. Please speak after this sound."),
+            (2, 0, "Part 2", 1, "random", 60, false, "part_2.mp3",
+             "Part 2. In this part, you will be given a picture followed by one question. You do not need to describe the picture, but focus on the question provided. You will have one minute to prepare and two minutes to answer. You will hear this sound when the time for preparation is over."),
+            (3, 0, "Part 3", 1, "random", 60, true, "part_3.mp3",
+             "Part 3. You will now be given one discussion question related to Part 2. You will have one minute to prepare for the task and two minutes to speak. You will hear this sound when the time for preparation is over."),
+        ];
+        for (position, section) in sections.iter().enumerate() {
+            let (part, sub_part, label, count, selection, prep_time, image_required, audio, text) =
+                section;
+            tx.execute(
+                "INSERT INTO blueprint_sections
+                     (blueprint_id, position, part, sub_part, label, question_count, selection,
+                      prep_time, image_required, instruction_audio, instruction_text)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params![
+                    blueprint_id,
+                    position as i64 + 1,
+                    part,
+                    sub_part,
+                    label,
+                    count,
+                    selection,
+                    prep_time,
+                    image_required,
+                    audio,
+                    text
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+    }
+
+    add_column_if_missing(tx, "full_tests", "blueprint_id", "INTEGER")?;
+    add_column_if_missing(tx, "attempts", "blueprint_id", "INTEGER")?;
+    tx.execute_batch(
+        "UPDATE full_tests
+         SET blueprint_id = (SELECT id FROM blueprints WHERE is_default = 1 LIMIT 1)
+         WHERE blueprint_id IS NULL;
+        UPDATE attempts
+         SET blueprint_id = (SELECT id FROM blueprints WHERE is_default = 1 LIMIT 1)
+         WHERE blueprint_id IS NULL;",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Preparation time was a property of the section only. Questions now carry
+// their own, starting from what the default blueprint gives their section.
+fn question_prep_time(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(tx, "questions", "prep_time", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(
+        tx,
+        "question_revisions",
+        "prep_time",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+
+    for table in ["questions", "question_revisions"] {
+        tx.execute(
+            &format!(
+                "UPDATE {table}
+                 SET prep_time = COALESCE((
+                     SELECT blueprint_sections.prep_time
+                     FROM blueprint_sections
+                     JOIN blueprints ON blueprints.id = blueprint_sections.blueprint_id
+                     WHERE blueprints.is_default = 1
+                       AND blueprint_sections.part = {table}.part
+                       AND blueprint_sections.sub_part = {table}.sub_part
+                     LIMIT 1
+                 ), 0)"
+            ),
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+// Random exams are drawn from a seed kept on the attempt, so what a candidate
+// was served can be drawn again. `shared_seed` marks seeds given out as a
+// paper code, which draw the same paper for everyone. Older attempts have no
+// seed.
+fn selection_seeds(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(tx, "attempts", "selection_seed", "INTEGER")?;
+    add_column_if_missing(tx, "attempts", "shared_seed", "INTEGER NOT NULL DEFAULT 0")?;
+    Ok(())
+}
+
+// Every use of a stored asset, one row each. Deleted questions only count
+// through revisions that were served in an attempt, so their files can be
+// collected once nothing can play them again.
+fn asset_references(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE VIEW IF NOT EXISTS asset_references AS
+         SELECT 'audio' AS folder, audio_path AS asset_id, 'question' AS source, id AS source_id
+         FROM questions WHERE active = 1 AND audio_path <> ''
+         UNION ALL
+         SELECT 'images', image_path, 'question', id
+         FROM questions WHERE active = 1 AND image_path <> ''
+         UNION ALL
+         SELECT 'audio', audio_path, 'question_revision', id
+         FROM question_revisions
+         WHERE audio_path <> ''
+           AND (id IN (SELECT question_revision_id FROM attempt_questions)
+                OR id IN (SELECT question_revision_id FROM responses))
+         UNION ALL
+         SELECT 'images', image_path, 'question_revision', id
+         FROM question_revisions
+         WHERE image_path <> ''
+           AND (id IN (SELECT question_revision_id FROM attempt_questions)
+                OR id IN (SELECT question_revision_id FROM responses))
+         UNION ALL
+         SELECT 'audio', instruction_audio, 'blueprint', blueprint_id
+         FROM blueprint_sections WHERE instruction_audio <> '';",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// A delivery is a message, a photo and a recording sent one after another.
+// `steps_sent` counts the parts already delivered, so a retry sends only the
+// rest instead of repeating them in the chat.
+fn telegram_delivery_progress(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(
+        tx,
+        "telegram_deliveries",
+        "steps_sent",
+        "INTEGER NOT NULL DEFAULT 0",
+    )
+}
+
+// The questions a random exam drew for each section, in order. Draws also
+// depend on what other candidates were shown, which keeps changing, so the
+// seed alone cannot bring a paper back; these rows can.
+fn attempt_draws(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS attempt_draws (
+            attempt_id INTEGER NOT NULL,
+            part INTEGER NOT NULL,
+            sub_part INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            question_id INTEGER NOT NULL,
+            PRIMARY KEY (attempt_id, part, sub_part, position),
+            FOREIGN KEY (attempt_id) REFERENCES attempts (id),
+            FOREIGN KEY (question_id) REFERENCES questions (id)
+        );",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Earlier versions of a question keep their files while the question is in
+// the bank, so its history can be played back as it was. Once it is deleted
+// only versions a candidate was served or answered still count, as before.
+// Replaces the view from `asset_references`.
+fn history_asset_references(tx: &Transaction) -> Result<(), String> {
+    tx.execute_batch(
+        "DROP VIEW IF EXISTS asset_references;
+
+         CREATE VIEW asset_references AS
+         SELECT 'audio' AS folder, audio_path AS asset_id, 'question' AS source, id AS source_id
+         FROM questions WHERE active = 1 AND audio_path <> ''
+         UNION ALL
+         SELECT 'images', image_path, 'question', id
+         FROM questions WHERE active = 1 AND image_path <> ''
+         UNION ALL
+         SELECT 'audio', audio_path, 'question_revision', id
+         FROM question_revisions
+         WHERE audio_path <> ''
+           AND (question_id IN (SELECT id FROM questions WHERE active = 1)
+                OR id IN (SELECT question_revision_id FROM attempt_questions)
+                OR id IN (SELECT question_revision_id FROM responses))
+         UNION ALL
+         SELECT 'images', image_path, 'question_revision', id
+         FROM question_revisions
+         WHERE image_path <> ''
+           AND (question_id IN (SELECT id FROM questions WHERE active = 1)
+                OR id IN (SELECT question_revision_id FROM attempt_questions)
+                OR id IN (SELECT question_revision_id FROM responses))
+         UNION ALL
+         SELECT 'audio', instruction_audio, 'blueprint', blueprint_id
+         FROM blueprint_sections WHERE instruction_audio <> '';",
+    )
+    .map_err(|e| e.to_string())
+}
+
+// Question audio is kept as uploaded and levelled when it is played.
+// `gain_db` caches the gain worked out for a stored audio file; it is filled
+// in the first time the file is asked for.
+fn audio_playback_gain(tx: &Transaction) -> Result<(), String> {
+    add_column_if_missing(tx, "asset_hashes", "gain_db", "REAL")
+}
@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const PACKAGE_EXTENSION: &str = "cefrpkg";
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const ASSETS_PREFIX: &str = "assets/";
+const ZIP_MAGIC: &[u8; 4] = b"PK\x03\x04";
+const MAX_MANIFEST_BYTES: u64 = 64 * 1024 * 1024;
+const MAX_ASSET_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Writes a `.cefrpkg` package: raw asset files under `assets/` and a
+/// `manifest.json` in one of the export formats that points at them. The zip
+/// is built in a `.partial` file next to the destination and only renamed
+/// into place once it is complete.
+pub struct PackageWriter {
+    zip: Option<ZipWriter<BufWriter<File>>>,
+    partial_path: PathBuf,
+    destination: PathBuf,
+    written_entries: HashSet<String>,
+    finished: bool,
+}
+
+impl PackageWriter {
+    pub fn create(destination: &Path) -> Result<Self, String> {
+        let file_name = destination
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("Invalid package path: {:?}", destination))?;
+        let partial_path = destination.with_file_name(format!("{}.partial", file_name));
+
+        let file = File::create(&partial_path)
+            .map_err(|e| format!("Failed to create package {:?}: {}", destination, e))?;
+
+        Ok(PackageWriter {
+            zip: Some(ZipWriter::new(BufWriter::new(file))),
+            partial_path,
+            destination: destination.to_path_buf(),
+            written_entries: HashSet::new(),
+            finished: false,
+        })
+    }
+
+    /// Adds an asset file and returns its entry name. Entries are named by
+    /// content hash, so a file shared by several questions is stored once.
+    pub fn add_asset(
+        &mut self,
+        folder: &str,
+        sha256: &str,
+        filename: &str,
+        data: &[u8],
+    ) -> Result<String, String> {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .filter(|ext| ext.len() <= 8 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+            .map(|ext| format!(".{}", ext.to_ascii_lowercase()))
+            .unwrap_or_default();
+        let entry = format!("{}{}/{}{}", ASSETS_PREFIX, folder, sha256, extension);
+
+        if self.written_entries.insert(entry.clone()) {
+            // Audio and images are already compressed.
+            let options = SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Stored)
+                .large_file(data.len() as u64 >= u32::MAX as u64);
+            let zip = self.zip_mut()?;
+            zip.start_file(entry.as_str(), options)
+                .map_err(|e| format!("Failed to add {} to package: {}", entry, e))?;
+            zip.write_all(data)
+                .map_err(|e| format!("Failed to write {} to package: {}", entry, e))?;
+        }
+
+        Ok(entry)
+    }
+
+    /// Writes the manifest, closes the zip and moves it to the destination.
+    /// Returns the size of the finished package in bytes.
+    pub fn finish(mut self, manifest: &[u8]) -> Result<u64, String> {
+        let mut zip = self
+            .zip
+            .take()
+            .ok_or_else(|| "Package is already closed".to_string())?;
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file(MANIFEST_ENTRY, options)
+            .map_err(|e| format!("Failed to add manifest to package: {}", e))?;
+        zip.write_all(manifest)
+            .map_err(|e| format!("Failed to write package manifest: {}", e))?;
+
+        let mut writer = zip
+            .finish()
+            .map_err(|e| format!("Failed to finish package: {}", e))?;
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to finish package: {}", e))?;
+        drop(writer);
+
+        if self.destination.exists() {
+            fs::remove_file(&self.destination)
+                .map_err(|e| format!("Failed to replace {:?}: {}", self.destination, e))?;
+        }
+        fs::rename(&self.partial_path, &self.destination)
+            .map_err(|e| format!("Failed to save package {:?}: {}", self.destination, e))?;
+        self.finished = true;
+
+        fs::metadata(&self.destination)
+            .map(|metadata| metadata.len())
+            .map_err(|e| e.to_string())
+    }
+
+    fn zip_mut(&mut self) -> Result<&mut ZipWriter<BufWriter<File>>, String> {
+        self.zip
+            .as_mut()
+            .ok_or_else(|| "Package is already closed".to_string())
+    }
+}
+
+impl Drop for PackageWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            // Close the file before removing it so this also works on Windows.
+            self.zip.take();
+            let _ = fs::remove_file(&self.partial_path);
+        }
+    }
+}
+
+/// Reads a `.cefrpkg` package written by `PackageWriter`.
+pub struct PackageReader {
+    zip: ZipArchive<BufReader<File>>,
+}
+
+impl PackageReader {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file =
+            File::open(path).map_err(|e| format!("Failed to open package {:?}: {}", path, e))?;
+        let zip = ZipArchive::new(BufReader::new(file))
+            .map_err(|e| format!("This file is not a valid CEFR package: {}", e))?;
+        Ok(PackageReader { zip })
+    }
+
+    pub fn read_manifest(&mut self) -> Result<String, String> {
+        let data = self.read_entry(MANIFEST_ENTRY, MAX_MANIFEST_BYTES)?;
+        String::from_utf8(data)
+            .map_err(|e| format!("The package manifest is not valid text: {}", e))
+    }
+
+    pub fn read_asset(&mut self, entry: &str) -> Result<Vec<u8>, String> {
+        if !entry.starts_with(ASSETS_PREFIX) {
+            return Err(format!("Package entry {} is not an asset", entry));
+        }
+        self.read_entry(entry, MAX_ASSET_BYTES)
+    }
+
+    fn read_entry(&mut self, entry: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+        let file = self
+            .zip
+            .by_name(entry)
+            .map_err(|e| format!("The package is missing {}: {}", entry, e))?;
+        if file.size() > max_bytes {
+            return Err(format!("Package entry {} is too large", entry));
+        }
+
+        let mut data = Vec::with_capacity(file.size() as usize);
+        file.take(max_bytes + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| format!("Failed to read {} from package: {}", entry, e))?;
+        if data.len() as u64 > max_bytes {
+            return Err(format!("Package entry {} is too large", entry));
+        }
+
+        Ok(data)
+    }
+}
+
+/// True when `path` starts with a zip header, i.e. it is a package rather
+/// than a legacy JSON export.
+pub fn is_package(path: &Path) -> Result<bool, String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let mut magic = [0u8; 4];
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == ZIP_MAGIC),
+        Err(_) => Ok(false),
+    }
+}
@@ -0,0 +1,368 @@
+//! Reads the playing time of WebM recordings straight from the container, so
+//! the backend does not have to trust the duration reported by the exam
+//! screen.
+//!
+//! Browsers record with `MediaRecorder`, which streams the file and never goes
+//! back to write a `Duration`: the segment and its clusters have unknown sizes
+//! and the length has to be worked out from the block timestamps. Opus packets
+//! carry their own length in the TOC byte, which covers the last block.
+
+const EBML_HEADER_ID: u32 = 0x1A45_DFA3;
+const SEGMENT_ID: u32 = 0x1853_8067;
+const INFO_ID: u32 = 0x1549_A966;
+const TIMECODE_SCALE_ID: u32 = 0x2A_D7B1;
+const DURATION_ID: u32 = 0x4489;
+const TRACKS_ID: u32 = 0x1654_AE6B;
+const TRACK_ENTRY_ID: u32 = 0xAE;
+const TRACK_NUMBER_ID: u32 = 0xD7;
+const CODEC_ID_ID: u32 = 0x86;
+const CLUSTER_ID: u32 = 0x1F43_B675;
+const CLUSTER_TIMECODE_ID: u32 = 0xE7;
+const SIMPLE_BLOCK_ID: u32 = 0xA3;
+const BLOCK_GROUP_ID: u32 = 0xA0;
+const BLOCK_ID: u32 = 0xA1;
+const BLOCK_DURATION_ID: u32 = 0x9B;
+
+const DEFAULT_TIMECODE_SCALE_NS: u64 = 1_000_000;
+const OPUS_CODEC_ID: &str = "A_OPUS";
+
+/// Returns how long a WebM recording plays, in milliseconds.
+pub fn duration_ms(data: &[u8]) -> Result<u64, String> {
+    let mut reader = Reader::new(data);
+
+    let header = reader
+        .element()
+        .filter(|element| element.id == EBML_HEADER_ID)
+        .ok_or("The recording is not a WebM file")?;
+    reader.skip(&header)?;
+
+    let segment = reader
+        .element()
+        .filter(|element| element.id == SEGMENT_ID)
+        .ok_or("The WebM file has no segment")?;
+    let segment_end = segment.end(data.len());
+
+    let mut timeline = Timeline::default();
+    let mut cluster_time: i64 = 0;
+    while reader.pos < segment_end {
+        // A file cut off mid-element still plays up to that point.
+        let Some(element) = reader.element() else {
+            break;
+        };
+        match element.id {
+            // Cluster children follow directly, whatever the cluster size.
+            CLUSTER_ID => {}
+            CLUSTER_TIMECODE_ID => {
+                let Some(body) = reader.body(&element) else {
+                    break;
+                };
+                cluster_time = read_uint(body) as i64;
+            }
+            SIMPLE_BLOCK_ID => {
+                let Some(body) = reader.body(&element) else {
+                    break;
+                };
+                timeline.add_block(body, cluster_time, None);
+            }
+            BLOCK_GROUP_ID => {
+                let Some(body) = reader.body(&element) else {
+                    break;
+                };
+                read_block_group(body, cluster_time, &mut timeline);
+            }
+            INFO_ID => {
+                let body = reader
+                    .body(&element)
+                    .ok_or("The WebM segment info is truncated")?;
+                read_info(body, &mut timeline)?;
+            }
+            TRACKS_ID => {
+                let body = reader
+                    .body(&element)
+                    .ok_or("The WebM track list is truncated")?;
+                read_tracks(body, &mut timeline)?;
+            }
+            _ => {
+                if element.size.is_none() {
+                    return Err(format!(
+                        "Unsupported WebM element {:#X} of unknown size",
+                        element.id
+                    ));
+                }
+                if reader.body(&element).is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    timeline.duration_ms()
+}
+
+#[derive(Default)]
+struct Timeline {
+    timecode_scale_ns: Option<u64>,
+    /// The `Duration` from the segment info, in timecode units.
+    declared: Option<f64>,
+    opus_tracks: Vec<u64>,
+    /// Block start times and `BlockDuration`s in timecode units, with the
+    /// Opus packet length in nanoseconds when known. They are converted only
+    /// at the end, once the timecode scale is certain to have been read.
+    blocks: Vec<(i64, Option<u64>, Option<u64>)>,
+}
+
+impl Timeline {
+    fn add_block(&mut self, block: &[u8], cluster_time: i64, duration: Option<u64>) {
+        let mut reader = Reader::new(block);
+        let Some(track) = reader.vint() else {
+            return;
+        };
+        let Some(header) = block.get(reader.pos..reader.pos + 3) else {
+            return;
+        };
+        let relative = i16::from_be_bytes([header[0], header[1]]) as i64;
+        let laced = header[2] & 0x06 != 0;
+        let frame = &block[reader.pos + 3..];
+
+        let opus_ns = if !laced && self.opus_tracks.contains(&track) {
+            opus_packet_duration_ns(frame)
+        } else {
+            None
+        };
+        self.blocks
+            .push((cluster_time.saturating_add(relative), duration, opus_ns));
+    }
+
+    fn duration_ms(&self) -> Result<u64, String> {
+        let scale = self.timecode_scale_ns.unwrap_or(DEFAULT_TIMECODE_SCALE_NS) as i64;
+        if let Some(declared) = self.declared.filter(|value| *value > 0.0) {
+            return Ok((declared * scale as f64 / 1_000_000.0).round() as u64);
+        }
+
+        let spans = self.blocks.iter().map(|(time, duration, opus_ns)| {
+            let start = time.saturating_mul(scale);
+            let length = match (duration, opus_ns) {
+                (Some(duration), _) => (*duration as i64).saturating_mul(scale),
+                (None, Some(opus_ns)) => *opus_ns as i64,
+                (None, None) => 0,
+            };
+            (start, start.saturating_add(length))
+        });
+        let first_start = spans.clone().map(|(start, _)| start).min();
+        let last_end = spans.map(|(_, end)| end).max();
+
+        match (first_start, last_end) {
+            (Some(start), Some(end)) => {
+                Ok((end.saturating_sub(start).max(0) as u64 + 500_000) / 1_000_000)
+            }
+            _ => Err("The recording contains no audio".to_string()),
+        }
+    }
+}
+
+fn read_info(body: &[u8], timeline: &mut Timeline) -> Result<(), String> {
+    let mut reader = Reader::new(body);
+    while let Some(element) = reader.element() {
+        let value = reader
+            .body(&element)
+            .ok_or("The WebM segment info is truncated")?;
+        match element.id {
+            TIMECODE_SCALE_ID => timeline.timecode_scale_ns = Some(read_uint(value)),
+            DURATION_ID => timeline.declared = read_float(value),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn read_tracks(body: &[u8], timeline: &mut Timeline) -> Result<(), String> {
+    let mut reader = Reader::new(body);
+    while let Some(element) = reader.element() {
+        let entry = reader
+            .body(&element)
+            .ok_or("The WebM track list is truncated")?;
+        if element.id != TRACK_ENTRY_ID {
+            continue;
+        }
+
+        let mut number = None;
+        let mut codec = "";
+        let mut fields = Reader::new(entry);
+        while let Some(field) = fields.element() {
+            let value = fields.body(&field).ok_or("The WebM track is truncated")?;
+            match field.id {
+                TRACK_NUMBER_ID => number = Some(read_uint(value)),
+                CODEC_ID_ID => codec = std::str::from_utf8(value).unwrap_or(""),
+                _ => {}
+            }
+        }
+        if let Some(number) = number {
+            if codec.trim_end_matches('\0') == OPUS_CODEC_ID {
+                timeline.opus_tracks.push(number);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_block_group(body: &[u8], cluster_time: i64, timeline: &mut Timeline) {
+    let mut reader = Reader::new(body);
+    let mut block = None;
+    let mut duration = None;
+    while let Some(element) = reader.element() {
+        let Some(value) = reader.body(&element) else {
+            break;
+        };
+        match element.id {
+            BLOCK_ID => block = Some(value),
+            BLOCK_DURATION_ID => duration = Some(read_uint(value)),
+            _ => {}
+        }
+    }
+    if let Some(block) = block {
+        timeline.add_block(block, cluster_time, duration);
+    }
+}
+
+/// The length of an Opus packet from its TOC byte (RFC 6716, section 3.1).
+fn opus_packet_duration_ns(packet: &[u8]) -> Option<u64> {
+    let toc = *packet.first()?;
+    let config = (toc >> 3) as usize;
+    let frame_ns: u64 = match config {
+        0..=11 => [10_000_000, 20_000_000, 40_000_000, 60_000_000][config % 4],
+        12..=15 => [10_000_000, 20_000_000][config % 2],
+        _ => [2_500_000, 5_000_000, 10_000_000, 20_000_000][config % 4],
+    };
+    let frames = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => u64::from(*packet.get(1)? & 0x3F),
+    };
+    Some(frame_ns * frames)
+}
+
+fn read_uint(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .take(8)
+        .fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+fn read_float(bytes: &[u8]) -> Option<f64> {
+    match bytes.len() {
+        4 => Some(f32::from_be_bytes(bytes.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(bytes.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+struct Element {
+    id: u32,
+    /// `None` for elements written with an unknown size.
+    size: Option<u64>,
+    body_start: usize,
+}
+
+impl Element {
+    fn end(&self, limit: usize) -> usize {
+        match self.size {
+            Some(size) => self
+                .body_start
+                .saturating_add(size.min(usize::MAX as u64) as usize)
+                .min(limit),
+            None => limit,
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data, pos: 0 }
+    }
+
+    /// Reads an element header. Returns `None` at the end of the data or when
+    /// the header is cut off or malformed.
+    fn element(&mut self) -> Option<Element> {
+        let start = self.pos;
+        let id = self.id();
+        let size = id.and_then(|_| self.size());
+        match (id, size) {
+            (Some(id), Some(size)) => Some(Element {
+                id,
+                size,
+                body_start: self.pos,
+            }),
+            _ => {
+                self.pos = start;
+                None
+            }
+        }
+    }
+
+    /// Returns the element body and moves past it, or `None` if the body
+    /// runs past the end of the data.
+    fn body(&mut self, element: &Element) -> Option<&'a [u8]> {
+        let size = element.size? as usize;
+        let end = element.body_start.checked_add(size)?;
+        let body = self.data.get(element.body_start..end)?;
+        self.pos = end;
+        Some(body)
+    }
+
+    fn skip(&mut self, element: &Element) -> Result<(), String> {
+        self.body(element)
+            .map(|_| ())
+            .ok_or_else(|| format!("WebM element {:#X} is truncated", element.id))
+    }
+
+    /// Element IDs keep their length marker bits.
+    fn id(&mut self) -> Option<u32> {
+        let first = *self.data.get(self.pos)?;
+        let length = first.leading_zeros() as usize + 1;
+        if length > 4 {
+            return None;
+        }
+        let bytes = self.data.get(self.pos..self.pos + length)?;
+        self.pos += length;
+        Some(
+            bytes
+                .iter()
+                .fold(0, |value, byte| (value << 8) | u32::from(*byte)),
+        )
+    }
+
+    /// An element size; all value bits set means the size is unknown.
+    fn size(&mut self) -> Option<Option<u64>> {
+        let length = self.data.get(self.pos)?.leading_zeros() as usize + 1;
+        let value = self.vint()?;
+        let unknown = (1u64 << (7 * length)) - 1;
+        Some((value != unknown).then_some(value))
+    }
+
+    /// A variable-length integer with its length marker removed.
+    fn vint(&mut self) -> Option<u64> {
+        let first = *self.data.get(self.pos)?;
+        let length = first.leading_zeros() as usize + 1;
+        if length > 8 {
+            return None;
+        }
+        let bytes = self.data.get(self.pos..self.pos + length)?;
+        self.pos += length;
+        let marker_mask = if length == 8 { 0 } else { 0xFF >> length };
+        Some(
+            bytes[1..]
+                .iter()
+                .fold(u64::from(first & marker_mask), |value, byte| {
+                    (value << 8) | u64::from(*byte)
+                }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,128 @@
+//! Builds small WebM files by hand, laid out the way `MediaRecorder` writes
+//! them, and checks the measured durations.
+
+use super::*;
+
+/// One 20 ms CELT frame.
+const OPUS_20MS_TOC: u8 = 31 << 3;
+
+fn id_bytes(id: u32) -> Vec<u8> {
+    id.to_be_bytes()
+        .into_iter()
+        .skip_while(|byte| *byte == 0)
+        .collect()
+}
+
+fn element(id: u32, body: &[u8]) -> Vec<u8> {
+    let mut bytes = id_bytes(id);
+    bytes.push(0x01);
+    bytes.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+fn unknown_size(id: u32) -> Vec<u8> {
+    let mut bytes = id_bytes(id);
+    bytes.extend_from_slice(&[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    bytes
+}
+
+fn uint(id: u32, value: u64) -> Vec<u8> {
+    element(id, &value.to_be_bytes())
+}
+
+fn simple_block(relative: i16, toc: u8) -> Vec<u8> {
+    let [high, low] = relative.to_be_bytes();
+    element(SIMPLE_BLOCK_ID, &[0x81, high, low, 0x80, toc, 0xAA, 0xBB])
+}
+
+fn header() -> Vec<u8> {
+    let mut bytes = element(EBML_HEADER_ID, &element(0x4282, b"webm"));
+    bytes.extend(unknown_size(SEGMENT_ID));
+    bytes.extend(element(INFO_ID, &uint(TIMECODE_SCALE_ID, 1_000_000)));
+    let track = [uint(TRACK_NUMBER_ID, 1), element(CODEC_ID_ID, b"A_OPUS")].concat();
+    bytes.extend(element(TRACKS_ID, &element(TRACK_ENTRY_ID, &track)));
+    bytes
+}
+
+/// A streamed recording: unknown-size clusters of 20 ms Opus blocks.
+fn recording(clusters: &[(u64, usize)]) -> Vec<u8> {
+    let mut bytes = header();
+    for (timecode, blocks) in clusters {
+        bytes.extend(unknown_size(CLUSTER_ID));
+        bytes.extend(uint(CLUSTER_TIMECODE_ID, *timecode));
+        for index in 0..*blocks {
+            bytes.extend(simple_block((index * 20) as i16, OPUS_20MS_TOC));
+        }
+    }
+    bytes
+}
+
+#[test]
+fn measures_streamed_recordings_from_block_timestamps() {
+    let data = recording(&[(0, 50), (1000, 50), (2000, 25)]);
+
+    assert_eq!(duration_ms(&data).unwrap(), 2500);
+}
+
+#[test]
+fn prefers_the_declared_duration() {
+    let mut data = element(EBML_HEADER_ID, &[]);
+    let info = [
+        uint(TIMECODE_SCALE_ID, 1_000_000),
+        element(DURATION_ID, &12_345.0f32.to_be_bytes()),
+    ]
+    .concat();
+    let segment = [
+        element(INFO_ID, &info),
+        element(CLUSTER_ID, &uint(CLUSTER_TIMECODE_ID, 0)),
+    ]
+    .concat();
+    data.extend(element(SEGMENT_ID, &segment));
+
+    assert_eq!(duration_ms(&data).unwrap(), 12_345);
+}
+
+#[test]
+fn uses_block_durations_in_block_groups() {
+    let mut data = header();
+    data.extend(unknown_size(CLUSTER_ID));
+    data.extend(uint(CLUSTER_TIMECODE_ID, 500));
+    let block = [0x82, 0x00, 0x00, 0x00, 0x01];
+    let group = [element(BLOCK_ID, &block), uint(BLOCK_DURATION_ID, 1500)].concat();
+    data.extend(element(BLOCK_GROUP_ID, &group));
+
+    assert_eq!(duration_ms(&data).unwrap(), 1500);
+}
+
+#[test]
+fn stops_at_a_truncated_block() {
+    let mut data = recording(&[(0, 10)]);
+    data.extend(&simple_block(200, OPUS_20MS_TOC)[..4]);
+
+    assert_eq!(duration_ms(&data).unwrap(), 200);
+}
+
+#[test]
+fn rejects_files_that_are_not_webm() {
+    assert!(duration_ms(b"RIFF\x00\x00\x00\x00WAVEfmt ").is_err());
+    assert!(duration_ms(&[]).is_err());
+}
+
+#[test]
+fn rejects_recordings_without_audio() {
+    assert!(duration_ms(&recording(&[(0, 0)])).is_err());
+}
+
+#[test]
+fn reads_opus_frame_counts() {
+    assert_eq!(opus_packet_duration_ns(&[OPUS_20MS_TOC]), Some(20_000_000));
+    // Two 10 ms SILK frames.
+    assert_eq!(opus_packet_duration_ns(&[0x01]), Some(20_000_000));
+    // Three 60 ms SILK frames, with the count in the second byte.
+    assert_eq!(
+        opus_packet_duration_ns(&[(3 << 3) | 3, 3]),
+        Some(180_000_000)
+    );
+    assert_eq!(opus_packet_duration_ns(&[]), None);
+}
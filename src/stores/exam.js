@@ -15,20 +15,38 @@ export const useExamStore = defineStore('exam', () => {
   const timerInterval = ref(null)
   const startMode = ref('random')
   const selectedQuestionsBySection = ref({})
+  // Served but unanswered questions of a resumed random exam, asked again
+  // before new ones are drawn for their section.
+  const pendingQuestionsBySection = ref({})
+  const resumedQuestionIds = ref([])
 
-  const defaultSections = [
-    { part: 1, subPart: 1, count: 3, label: '1.1', key: '1-1' },
-    { part: 1, subPart: 2, count: 3, label: '1.2', key: '1-2' },
-    { part: 2, subPart: 0, count: 1, label: '2', key: '2-0' },
-    { part: 3, subPart: 0, count: 1, label: '3', key: '3-0' }
-  ]
-  const sections = ref([...defaultSections])
+  // The exam format being taken and all of its sections; `sections` holds
+  // the ones this attempt still goes through.
+  const blueprintId = ref(null)
+  const blueprintSections = ref([])
+  const sections = ref([])
   const sectionCounts = ref({})
 
   function getSectionKey(part, subPart) {
     return `${part}-${subPart || 0}`
   }
 
+  async function loadBlueprint(id = null) {
+    const blueprint = await invoke('get_blueprint', { blueprintId: id })
+    blueprintId.value = blueprint.id
+    blueprintSections.value = blueprint.sections.map((section) => ({
+      part: section.part,
+      subPart: section.sub_part,
+      count: section.question_count,
+      label: section.label,
+      key: getSectionKey(section.part, section.sub_part),
+      prepTime: section.prep_time,
+      imageRequired: section.image_required,
+      instructionAudio: section.instruction_audio,
+      instructionText: section.instruction_text
+    }))
+  }
+
   function setSectionCount(part, subPart, count) {
     const key = getSectionKey(part, subPart)
     sectionCounts.value = { ...sectionCounts.value, [key]: count }
@@ -46,16 +64,8 @@ export const useExamStore = defineStore('exam', () => {
     stopTimer()
   }
 
-  function sortQuestionsForExam(questionList) {
-    return [...questionList].sort((a, b) => {
-      const orderA = Number(a.pack_order) || Number.MAX_SAFE_INTEGER
-      const orderB = Number(b.pack_order) || Number.MAX_SAFE_INTEGER
-
-      if (orderA !== orderB) return orderA - orderB
-      return a.id - b.id
-    })
-  }
-
+  // Full tests arrive in the order set by the admin, so questions keep that
+  // order within each section.
   function buildSelectedSections(selectedQuestions) {
     const grouped = {}
 
@@ -65,39 +75,52 @@ export const useExamStore = defineStore('exam', () => {
       grouped[key].push(question)
     })
 
-    const selectedSections = defaultSections.filter((section) => grouped[section.key]?.length)
+    const selectedSections = blueprintSections.value.filter((section) => grouped[section.key]?.length)
 
     return {
-      grouped: Object.fromEntries(
-        Object.entries(grouped).map(([key, value]) => [key, sortQuestionsForExam(value)])
-      ),
+      grouped,
       selectedSections
     }
   }
   
   // Actions
-  async function startExam(name) {
+  // A paper code gives everyone who enters it the same questions.
+  async function startExam(name, formatId = null, paperCode = null) {
     try {
+      await loadBlueprint(formatId)
+
       // Create attempt in database
-      const id = await invoke('create_attempt', { studentName: name })
+      const id = await invoke('create_attempt', {
+        studentName: name,
+        mode: 'random',
+        blueprintId: blueprintId.value,
+        seed: paperCode
+      })
       attemptId.value = id
       startMode.value = 'random'
       selectedQuestionsBySection.value = {}
-      sections.value = [...defaultSections]
+      pendingQuestionsBySection.value = {}
+      resumedQuestionIds.value = []
+      sections.value = [...blueprintSections.value]
       resetFlowState(name)
 
-      // Load questions for part 1.1
+      // Load questions for the first section
       const firstSection = sections.value[0]
       const part1Questions = await invoke('get_random_questions', {
-        part: firstSection.part,
-        subPart: firstSection.subPart,
-        count: firstSection.count,
-        excludeIds: []
+        draw: {
+          part: firstSection.part,
+          sub_part: firstSection.subPart,
+          count: firstSection.count,
+          exclude_ids: [],
+          blueprint_id: blueprintId.value,
+          attempt_id: id
+        }
       })
 
       questions.value = part1Questions
       timeRemaining.value = part1Questions[0]?.response_time || 30
       setSectionCount(firstSection.part, firstSection.subPart, part1Questions.length)
+      await markCurrentQuestionServed()
       
     } catch (error) {
       console.error('Failed to start exam:', error)
@@ -105,11 +128,65 @@ export const useExamStore = defineStore('exam', () => {
     }
   }
 
-  async function startSelectedExam(name, selectedQuestions) {
+  // Carries on with an attempt that was paused or cut short. Only the
+  // questions still to be answered are shown; a random exam asks the ones
+  // already served again and draws the rest of each section as usual.
+  async function resumeExam(id) {
+    try {
+      const resumed = await invoke('resume_attempt', { attemptId: id })
+      await loadBlueprint(resumed.blueprint_id)
+      pendingQuestionsBySection.value = {}
+      resumedQuestionIds.value = []
+
+      if (resumed.mode === 'full_test') {
+        const { grouped, selectedSections } = buildSelectedSections(resumed.remaining)
+        startMode.value = 'selected'
+        selectedQuestionsBySection.value = grouped
+        sections.value = selectedSections
+      } else {
+        const answeredBySection = {}
+        resumed.answered.forEach((question) => {
+          const key = getSectionKey(question.part, question.sub_part)
+          answeredBySection[key] = (answeredBySection[key] || 0) + 1
+        })
+        const { grouped } = buildSelectedSections(resumed.remaining)
+
+        startMode.value = 'random'
+        selectedQuestionsBySection.value = {}
+        pendingQuestionsBySection.value = grouped
+        resumedQuestionIds.value = [...resumed.answered, ...resumed.remaining].map(q => q.id)
+        sections.value = blueprintSections.value
+          .map((section) => ({
+            ...section,
+            count: Math.max(section.count - (answeredBySection[section.key] || 0), 0)
+          }))
+          .filter((section) => section.count > 0)
+      }
+
+      attemptId.value = resumed.attempt_id
+      resetFlowState(resumed.student_name)
+
+      if (sections.value.length === 0) {
+        await finishExam()
+        isFinished.value = true
+        return
+      }
+
+      const firstSection = sections.value[0]
+      await loadPartQuestions(firstSection.part, firstSection.count, firstSection.subPart)
+      await markCurrentQuestionServed()
+    } catch (error) {
+      console.error('Failed to resume exam:', error)
+      throw error
+    }
+  }
+
+  async function startSelectedExam(name, selectedQuestions, fullTestId = null, formatId = null) {
     if (!Array.isArray(selectedQuestions) || selectedQuestions.length === 0) {
       throw new Error('Choose at least one question for the selected-question mode.')
     }
 
+    await loadBlueprint(formatId)
     const { grouped, selectedSections } = buildSelectedSections(selectedQuestions)
 
     if (selectedSections.length === 0) {
@@ -117,15 +194,23 @@ export const useExamStore = defineStore('exam', () => {
     }
 
     try {
-      const id = await invoke('create_attempt', { studentName: name })
+      const id = await invoke('create_attempt', {
+        studentName: name,
+        mode: 'full_test',
+        fullTestId,
+        blueprintId: blueprintId.value
+      })
       attemptId.value = id
       startMode.value = 'selected'
       selectedQuestionsBySection.value = grouped
+      pendingQuestionsBySection.value = {}
+      resumedQuestionIds.value = []
       sections.value = selectedSections
       resetFlowState(name)
 
       const firstSection = sections.value[0]
       await loadPartQuestions(firstSection.part, grouped[firstSection.key].length, firstSection.subPart)
+      await markCurrentQuestionServed()
     } catch (error) {
       console.error('Failed to start selected exam:', error)
       throw error
@@ -154,6 +239,22 @@ export const useExamStore = defineStore('exam', () => {
     }
   }
   
+  // Records the question now on screen in the attempt's served list. A
+  // failure here must not interrupt the exam.
+  async function markCurrentQuestionServed() {
+    const question = questions.value[currentQuestion.value]
+    if (!attemptId.value || !question) return
+
+    try {
+      await invoke('serve_question', {
+        attemptId: attemptId.value,
+        questionId: question.id
+      })
+    } catch (error) {
+      console.error('Failed to record served question:', error)
+    }
+  }
+
   async function nextQuestion() {
     const nextIndex = currentQuestion.value + 1
     
@@ -164,6 +265,7 @@ export const useExamStore = defineStore('exam', () => {
         const nextSection = sections.value[currentSectionIndex.value]
         await loadPartQuestions(nextSection.part, nextSection.count, nextSection.subPart)
         currentQuestion.value = 0
+        await markCurrentQuestionServed()
       } else {
         await finishExam()
         isFinished.value = true
@@ -172,6 +274,7 @@ export const useExamStore = defineStore('exam', () => {
     } else {
       currentQuestion.value = nextIndex
       timeRemaining.value = questions.value[nextIndex]?.response_time || 30
+      await markCurrentQuestionServed()
     }
     
     stopTimer()
@@ -189,18 +292,27 @@ export const useExamStore = defineStore('exam', () => {
       return
     }
 
-    const excludeIds = questions.value.map(q => q.id)
+    const excludeIds = [...questions.value.map(q => q.id), ...resumedQuestionIds.value]
+    const pendingQuestions = pendingQuestionsBySection.value[key] || []
+    const drawCount = count - pendingQuestions.length
     
-    const newQuestions = await invoke('get_random_questions', {
-      part,
-      subPart: subPart || undefined,
-      count,
-      excludeIds
-    })
+    const newQuestions = drawCount > 0
+      ? await invoke('get_random_questions', {
+        draw: {
+          part,
+          sub_part: subPart || undefined,
+          count: drawCount,
+          exclude_ids: excludeIds,
+          blueprint_id: blueprintId.value,
+          attempt_id: attemptId.value
+        }
+      })
+      : []
+    const sectionQuestions = [...pendingQuestions, ...newQuestions]
     
-    questions.value = newQuestions
-    timeRemaining.value = newQuestions[0]?.response_time || 30
-    setSectionCount(part, subPart, newQuestions.length)
+    questions.value = sectionQuestions
+    timeRemaining.value = sectionQuestions[0]?.response_time || 30
+    setSectionCount(part, subPart, sectionQuestions.length)
   }
   
   async function finishExam() {
@@ -208,6 +320,13 @@ export const useExamStore = defineStore('exam', () => {
       await invoke('finish_attempt', { attemptId: attemptId.value })
     }
   }
+
+  async function pauseExam() {
+    stopTimer()
+    if (attemptId.value) {
+      await invoke('pause_attempt', { attemptId: attemptId.value })
+    }
+  }
   
   async function saveResponse(audioBlob, duration) {
     if (!attemptId.value || !questions.value[currentQuestion.value]) return
@@ -252,7 +371,11 @@ export const useExamStore = defineStore('exam', () => {
     isFinished.value = false
     startMode.value = 'random'
     selectedQuestionsBySection.value = {}
-    sections.value = [...defaultSections]
+    pendingQuestionsBySection.value = {}
+    resumedQuestionIds.value = []
+    blueprintId.value = null
+    blueprintSections.value = []
+    sections.value = []
     sectionCounts.value = {}
   }
   
@@ -261,9 +384,10 @@ export const useExamStore = defineStore('exam', () => {
     return questions.value[currentQuestion.value] || null
   })
   
-  const currentPart = computed(() => sections.value[currentSectionIndex.value]?.part || 1)
-  const currentSubPart = computed(() => sections.value[currentSectionIndex.value]?.subPart || 0)
-  const currentPartLabel = computed(() => `Part ${sections.value[currentSectionIndex.value]?.label || '1'}`)
+  const currentSection = computed(() => sections.value[currentSectionIndex.value] || null)
+  const currentPart = computed(() => currentSection.value?.part || 1)
+  const currentSubPart = computed(() => currentSection.value?.subPart || 0)
+  const currentPartLabel = computed(() => currentSection.value?.label || 'Part 1')
 
   function getProgressSectionCount(section) {
     if (startMode.value === 'selected') {
@@ -299,6 +423,8 @@ export const useExamStore = defineStore('exam', () => {
     // State
     attemptId,
     studentName,
+    blueprintSections,
+    currentSection,
     currentPart,
     currentSubPart,
     currentPartLabel,
@@ -311,12 +437,14 @@ export const useExamStore = defineStore('exam', () => {
     // Actions
     startExam,
     startSelectedExam,
+    resumeExam,
     nextQuestion,
     saveResponse,
     startRecording,
     stopRecording,
     resetExam,
     finishExam,
+    pauseExam,
     startTimer,
     stopTimer,
     